@@ -2,13 +2,19 @@ package main
 
 import (
 	"context"
-	"encoding/base64"
+	"crypto/sha256"
+	"encoding/hex"
 	"flag"
 	fmt "fmt"
+	"image"
+	"image/color"
+	"image/png"
 	"io"
 	"io/ioutil"
 	"log"
 	"math/rand"
+	"os"
+	"path/filepath"
 	"sync"
 	"sync/atomic"
 	"time"
@@ -31,11 +37,30 @@ type WorkerSpec struct {
 }
 
 type TilingSpec struct {
+	// Size of the square tiles. Frames of animated scenes are sent whole
+	// when it is 0.
 	Size int `yaml:"size"`
 }
 
+// WorkUnit is a single job for a worker: a tile of a frame, or a whole frame
+// when Tile is nil.
+type WorkUnit struct {
+	Frame uint32
+	Tile  *pb.Tile
+}
+
+// Job returns the message asking a worker to render the unit. Static scenes
+// are sent as plain tiles.
+func (u *WorkUnit) Job(animated bool) *pb.Job {
+	if !animated {
+		return &pb.Job{Request: &pb.Job_Tile{Tile: u.Tile}}
+	}
+	return &pb.Job{Request: &pb.Job_Frame{Frame: &pb.Frame{Number: u.Frame, Tile: u.Tile}}}
+}
+
 type TileInProgress struct {
 	Tile       *pb.Tile
+	Frame      uint32
 	StartedAt  time.Time
 	Color      rl.Color
 	WorkerName string
@@ -52,6 +77,7 @@ type Worker struct {
 var (
 	networkFile = flag.String("network", "", "network specification file")
 	sceneFile   = flag.String("scene", "", "scene specification file")
+	outputDir   = flag.String("output", "", "directory to write the rendered frames to, as PNG")
 )
 
 var RaylibColors = []rl.Color{
@@ -82,8 +108,11 @@ func main() {
 	rand.Shuffle(len(RaylibColors), func(a, b int) { RaylibColors[a], RaylibColors[b] = RaylibColors[b], RaylibColors[a] })
 
 	var sceneRaw string
+	assets := make(map[string][]byte)
 	height := int32(0)
 	width := int32(0)
+	firstFrame, lastFrame := uint32(1), uint32(1)
+	animated := false
 	{
 		log.Println("reading scene specification...")
 		contents, err := ioutil.ReadFile(*sceneFile)
@@ -98,20 +127,23 @@ func main() {
 		height = int32(yamlMap["camera"].(map[interface{}]interface{})["height"].(int))
 		width = int32(yamlMap["camera"].(map[interface{}]interface{})["width"].(int))
 
-		// load all textures...
-		if _, ok := yamlMap["textures"]; ok {
-			for key, texture := range yamlMap["textures"].(map[interface{}]interface{}) {
-				path := texture.(map[interface{}]interface{})["path"].(string)
-				file, err := ioutil.ReadFile(path)
-				if err != nil {
-					log.Fatal(err)
-				}
+		if animation, ok := yamlMap["animation"].(map[interface{}]interface{}); ok {
+			if frames, ok := animation["frames"].([]interface{}); ok && len(frames) == 2 {
+				firstFrame = uint32(frames[0].(int))
+				lastFrame = uint32(frames[1].(int))
+				animated = true
+				log.Printf("scene is animated, frames %d to %d", firstFrame, lastFrame)
+			}
+		}
 
-				encoded := base64.StdEncoding.EncodeToString(file)
-				yamlMap["textures"].(map[interface{}]interface{})[key] = map[interface{}]interface{}{
-					"data": encoded,
-				}
-				log.Printf("encoded texture %q in base64, %d bytes", key, len(encoded))
+		// Replace the textures and models read from files by their hashes,
+		// the animation tracks have paths too but those point into the scene.
+		for key, value := range yamlMap {
+			if key == "animation" {
+				continue
+			}
+			if err := hashAssets(value, assets); err != nil {
+				log.Fatal("could not read asset: ", err)
 			}
 		}
 
@@ -151,6 +183,12 @@ func main() {
 		}()
 		client := pb.NewWorkerClient(connection)
 
+		uploaded, err := uploadAssets(ctx, client, assets)
+		if err != nil {
+			log.Fatalf("could not upload assets to %s: %s", worker.Address, err)
+		}
+		log.Printf("uploaded %d of %d assets to %s", uploaded, len(assets), worker.Address)
+
 		renderClient, err := client.Render(ctx)
 		if err != nil {
 			log.Fatalf("could not establish render connection with %s: %s", worker.Address, err)
@@ -183,46 +221,81 @@ func main() {
 		})
 	}
 
-	// Make sure all the tiles are dealt with, exactly once.
-	tiles := make(chan *pb.Tile)
-	tileList := make([]*pb.Tile, 0, int(width*height))
-	go func() {
-		defer close(tiles)
+	if network.Tiling.Size <= 0 && !animated {
+		log.Fatal("tiling size must be set to render static scenes")
+	}
 
+	// Make sure all the work units are dealt with, exactly once. Frames are
+	// handed out in order, only the tiles within a frame are shuffled, so
+	// that workers rarely have to rebuild the scene for a different frame.
+	units := make(chan *WorkUnit)
+	unitList := make([]*WorkUnit, 0)
+	for frame := firstFrame; frame <= lastFrame; frame++ {
+		if network.Tiling.Size <= 0 {
+			unitList = append(unitList, &WorkUnit{Frame: frame})
+			continue
+		}
+
+		frameUnits := make([]*WorkUnit, 0)
 		for x := int32(0); x < width; x += int32(network.Tiling.Size) {
 			for y := int32(0); y < height; y += int32(network.Tiling.Size) {
-				tileList = append(tileList, &pb.Tile{X: uint32(x), Y: uint32(y), Size: uint32(network.Tiling.Size)})
+				frameUnits = append(frameUnits, &WorkUnit{
+					Frame: frame,
+					Tile:  &pb.Tile{X: uint32(x), Y: uint32(y), Size: uint32(network.Tiling.Size)},
+				})
 			}
 		}
+		rand.Shuffle(len(frameUnits), func(a, b int) { frameUnits[a], frameUnits[b] = frameUnits[b], frameUnits[a] })
+		unitList = append(unitList, frameUnits...)
+	}
+	go func() {
+		defer close(units)
 
-		rand.Shuffle(len(tileList), func(a, b int) { tileList[a], tileList[b] = tileList[b], tileList[a] })
-
-		for _, tile := range tileList {
-			tiles <- tile
+		for _, unit := range unitList {
+			units <- unit
 		}
 	}()
 
 	wg := &sync.WaitGroup{}
-	pixels := make(chan *pb.Pixel)
+	results := make(chan *pb.Pixels)
 	startedAt := time.Now()
 
-	// Read from the pixels channel and append them to the buffer, to later
-	// draw them.
-	buffer := make([]*pb.Pixel, 0, height*width)
+	// Read from the results channel and append the pixels to the buffer of
+	// their frame, to later draw and save them.
+	buffers := make(map[uint32][]*pb.Pixel)
+	buffersMu := &sync.RWMutex{}
+	done := make(chan struct{})
 	go func() {
-		for pixel := range pixels {
-			buffer = append(buffer, pixel)
+		defer close(done)
+		for result := range results {
+			buffersMu.Lock()
+			buffers[result.Frame] = append(buffers[result.Frame], result.Pixels...)
+			buffersMu.Unlock()
 		}
 	}()
+	getFramePixels := func(frame uint32) []*pb.Pixel {
+		buffersMu.RLock()
+		defer buffersMu.RUnlock()
+		return buffers[frame]
+	}
 
 	// Keep track of which tiles are being generated.
 	generating := make(map[string]*TileInProgress)
 	generatingMu := &sync.RWMutex{}
-	setGenerating := func(key string, tile *pb.Tile, startedAt time.Time, color rl.Color) {
+	setGenerating := func(key string, unit *WorkUnit, startedAt time.Time, color rl.Color) {
 		generatingMu.Lock()
 		defer generatingMu.Unlock()
+		tile := unit.Tile
+		if tile == nil {
+			size := width
+			if height > size {
+				size = height
+			}
+			tile = &pb.Tile{Size: uint32(size)}
+		}
 		generating[key] = &TileInProgress{
 			Tile:       tile,
+			Frame:      unit.Frame,
 			StartedAt:  startedAt,
 			Color:      color,
 			WorkerName: key,
@@ -252,12 +325,8 @@ func main() {
 				wg.Done()
 			}()
 
-			for tile := range tiles {
-				if err := worker.Client.Send(&pb.Job{
-					Request: &pb.Job_Tile{
-						Tile: tile,
-					},
-				}); err != nil {
+			for unit := range units {
+				if err := worker.Client.Send(unit.Job(animated)); err != nil {
 					if err == io.EOF {
 						return
 					}
@@ -266,7 +335,7 @@ func main() {
 				}
 				sentAt := time.Now()
 
-				setGenerating(worker.Name, tile, sentAt, worker.TileInProgressColor)
+				setGenerating(worker.Name, unit, sentAt, worker.TileInProgressColor)
 
 				// Wait for the result...
 				recv, err := worker.Client.Recv()
@@ -277,11 +346,9 @@ func main() {
 					log.Println(err)
 					return
 				}
-				log.Printf("%q processed tile in %v", worker.Name, time.Since(sentAt))
+				log.Printf("%q processed tile of frame %d in %v", worker.Name, recv.Frame, time.Since(sentAt))
 				atomic.AddInt32(&worker.CompletedTiles, 1)
-				for _, pixel := range recv.Pixels {
-					pixels <- pixel
-				}
+				results <- recv
 			}
 		}(worker)
 	}
@@ -293,27 +360,52 @@ func main() {
 	go func() {
 		wg.Wait()
 
-		close(pixels)
+		close(results)
+		<-done
 		log.Println("completed in", time.Since(startedAt))
 		for _, worker := range workers {
-			percentage := float64(worker.CompletedTiles) / float64(len(tileList)) * 100
+			percentage := float64(worker.CompletedTiles) / float64(len(unitList)) * 100
 			log.Printf("%q completed %d tiles, that's %.2f%%", worker.Name, worker.CompletedTiles, percentage)
 		}
+
+		if *outputDir == "" {
+			return
+		}
+		for frame := firstFrame; frame <= lastFrame; frame++ {
+			path := filepath.Join(*outputDir, fmt.Sprintf("frame_%04d.png", frame))
+			if err := writeFrame(path, getFramePixels(frame), int(width), int(height)); err != nil {
+				log.Printf("could not write frame %d: %s", frame, err)
+				continue
+			}
+			log.Println("wrote", path)
+		}
 	}()
 
 	rl.InitWindow(width, height, "Distracer!")
 	defer rl.CloseWindow()
 	rl.SetTargetFPS(144)
 
+	// The arrow keys flip through the frames of animated scenes.
+	shownFrame := firstFrame
 	for !rl.WindowShouldClose() {
+		if rl.IsKeyPressed(rl.KeyRight) && shownFrame < lastFrame {
+			shownFrame++
+		}
+		if rl.IsKeyPressed(rl.KeyLeft) && shownFrame > firstFrame {
+			shownFrame--
+		}
+
 		rl.BeginDrawing()
 		rl.ClearBackground(rl.Black)
 
-		for _, p := range buffer {
+		for _, p := range getFramePixels(shownFrame) {
 			rl.DrawPixel(int32(p.X), int32(p.Y), pixelToRlColor(p.Color))
 		}
 
 		for _, elem := range getTilesBeingGenerated() {
+			if elem.Frame != shownFrame {
+				continue
+			}
 			sizeDiv2 := int32(elem.Tile.Size / 2)
 			centerX := int32(elem.Tile.X) + sizeDiv2
 			centerY := int32(elem.Tile.Y) + sizeDiv2
@@ -321,10 +413,104 @@ func main() {
 			rl.DrawText(fmt.Sprintf("%s", elem.WorkerName), centerX-10, centerY-5, 10, rl.Black)
 		}
 
+		if animated {
+			rl.DrawText(fmt.Sprintf("frame %d", shownFrame), 5, 5, 10, rl.White)
+		}
+
 		rl.EndDrawing()
 	}
 }
 
+// hashAssets replaces every asset read from a file, that is any map with a
+// path, by a reference to the SHA-256 of its data, and keeps the data in
+// assets by hash.
+func hashAssets(node interface{}, assets map[string][]byte) error {
+	switch node := node.(type) {
+	case map[interface{}]interface{}:
+		if path, ok := node["path"].(string); ok {
+			data, err := ioutil.ReadFile(path)
+			if err != nil {
+				return err
+			}
+			sum := sha256.Sum256(data)
+			hash := hex.EncodeToString(sum[:])
+			assets[hash] = data
+			delete(node, "path")
+			node["hash"] = hash
+			log.Printf("hashed asset %q, %d bytes", path, len(data))
+			return nil
+		}
+		for _, value := range node {
+			if err := hashAssets(value, assets); err != nil {
+				return err
+			}
+		}
+	case []interface{}:
+		for _, value := range node {
+			if err := hashAssets(value, assets); err != nil {
+				return err
+			}
+		}
+	}
+	return nil
+}
+
+// uploadAssets uploads the assets the worker does not have yet and returns
+// how many were uploaded.
+func uploadAssets(ctx context.Context, client pb.WorkerClient, assets map[string][]byte) (int, error) {
+	list, err := client.ListAssets(ctx, &pb.ListAssetsRequest{})
+	if err != nil {
+		return 0, err
+	}
+	stored := make(map[string]bool, len(list.Hashes))
+	for _, hash := range list.Hashes {
+		stored[hash] = true
+	}
+
+	missing := make([]string, 0, len(assets))
+	for hash := range assets {
+		if !stored[hash] {
+			missing = append(missing, hash)
+		}
+	}
+	if len(missing) == 0 {
+		return 0, nil
+	}
+
+	upload, err := client.UploadAssets(ctx)
+	if err != nil {
+		return 0, err
+	}
+	for _, hash := range missing {
+		if err := upload.Send(&pb.Asset{Hash: hash, Data: assets[hash]}); err != nil {
+			return 0, err
+		}
+	}
+	if _, err := upload.CloseAndRecv(); err != nil {
+		return 0, err
+	}
+	return len(missing), nil
+}
+
+// writeFrame writes the pixels of a frame to a PNG file.
+func writeFrame(path string, pixels []*pb.Pixel, width, height int) error {
+	img := image.NewRGBA(image.Rect(0, 0, width, height))
+	for _, p := range pixels {
+		c := pixelToRlColor(p.Color)
+		img.Set(int(p.X), int(p.Y), color.RGBA{R: c.R, G: c.G, B: c.B, A: c.A})
+	}
+
+	file, err := os.Create(path)
+	if err != nil {
+		return err
+	}
+	if err := png.Encode(file, img); err != nil {
+		file.Close()
+		return err
+	}
+	return file.Close()
+}
+
 func pixelToRlColor(p uint32) rl.Color {
 	return rl.Color{
 		R: uint8(p >> 16),
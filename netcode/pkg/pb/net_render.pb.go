@@ -81,9 +81,59 @@ func (m *Tile) GetSize() uint32 {
 	return 0
 }
 
+// Frame is a single frame of an animated scene. When tile is not set the
+// whole frame is rendered, otherwise only that tile of it.
+type Frame struct {
+	Number               uint32   `protobuf:"varint,1,opt,name=number,proto3" json:"number,omitempty"`
+	Tile                 *Tile    `protobuf:"bytes,2,opt,name=tile,proto3" json:"tile,omitempty"`
+	XXX_NoUnkeyedLiteral struct{} `json:"-"`
+	XXX_unrecognized     []byte   `json:"-"`
+	XXX_sizecache        int32    `json:"-"`
+}
+
+func (m *Frame) Reset()         { *m = Frame{} }
+func (m *Frame) String() string { return proto.CompactTextString(m) }
+func (*Frame) ProtoMessage()    {}
+func (*Frame) Descriptor() ([]byte, []int) {
+	return fileDescriptor_9ea114fcbf489839, []int{1}
+}
+
+func (m *Frame) XXX_Unmarshal(b []byte) error {
+	return xxx_messageInfo_Frame.Unmarshal(m, b)
+}
+func (m *Frame) XXX_Marshal(b []byte, deterministic bool) ([]byte, error) {
+	return xxx_messageInfo_Frame.Marshal(b, m, deterministic)
+}
+func (m *Frame) XXX_Merge(src proto.Message) {
+	xxx_messageInfo_Frame.Merge(m, src)
+}
+func (m *Frame) XXX_Size() int {
+	return xxx_messageInfo_Frame.Size(m)
+}
+func (m *Frame) XXX_DiscardUnknown() {
+	xxx_messageInfo_Frame.DiscardUnknown(m)
+}
+
+var xxx_messageInfo_Frame proto.InternalMessageInfo
+
+func (m *Frame) GetNumber() uint32 {
+	if m != nil {
+		return m.Number
+	}
+	return 0
+}
+
+func (m *Frame) GetTile() *Tile {
+	if m != nil {
+		return m.Tile
+	}
+	return nil
+}
+
 // Pixels is the result of a single job processed by a worker.
 type Pixels struct {
 	Pixels               []*Pixel `protobuf:"bytes,1,rep,name=pixels,proto3" json:"pixels,omitempty"`
+	Frame                uint32   `protobuf:"varint,2,opt,name=frame,proto3" json:"frame,omitempty"`
 	XXX_NoUnkeyedLiteral struct{} `json:"-"`
 	XXX_unrecognized     []byte   `json:"-"`
 	XXX_sizecache        int32    `json:"-"`
@@ -93,7 +143,7 @@ func (m *Pixels) Reset()         { *m = Pixels{} }
 func (m *Pixels) String() string { return proto.CompactTextString(m) }
 func (*Pixels) ProtoMessage()    {}
 func (*Pixels) Descriptor() ([]byte, []int) {
-	return fileDescriptor_9ea114fcbf489839, []int{1}
+	return fileDescriptor_9ea114fcbf489839, []int{2}
 }
 
 func (m *Pixels) XXX_Unmarshal(b []byte) error {
@@ -121,6 +171,13 @@ func (m *Pixels) GetPixels() []*Pixel {
 	return nil
 }
 
+func (m *Pixels) GetFrame() uint32 {
+	if m != nil {
+		return m.Frame
+	}
+	return 0
+}
+
 // Pixel represents a single pixel within the canvas.
 type Pixel struct {
 	X                    uint32   `protobuf:"varint,1,opt,name=x,proto3" json:"x,omitempty"`
@@ -135,7 +192,7 @@ func (m *Pixel) Reset()         { *m = Pixel{} }
 func (m *Pixel) String() string { return proto.CompactTextString(m) }
 func (*Pixel) ProtoMessage()    {}
 func (*Pixel) Descriptor() ([]byte, []int) {
-	return fileDescriptor_9ea114fcbf489839, []int{2}
+	return fileDescriptor_9ea114fcbf489839, []int{3}
 }
 
 func (m *Pixel) XXX_Unmarshal(b []byte) error {
@@ -179,11 +236,15 @@ func (m *Pixel) GetColor() uint32 {
 
 // Job is the stream of messgaes for the server to consume. Scene will be sent
 // once, on the first message. The rest of the messages will all be of type
-// Tile, asking for a tile to be rendered.
+// Tile, asking for a tile of the first frame to be rendered, or of type Frame,
+// asking for a frame (or a tile of it) of an animated scene to be rendered.
+// Textures and models are only decoded once per stream, so a whole animation
+// should be sent through a single stream.
 type Job struct {
 	// Types that are valid to be assigned to Request:
 	//	*Job_Scene
 	//	*Job_Tile
+	//	*Job_Frame
 	Request              isJob_Request `protobuf_oneof:"request"`
 	XXX_NoUnkeyedLiteral struct{}      `json:"-"`
 	XXX_unrecognized     []byte        `json:"-"`
@@ -194,7 +255,7 @@ func (m *Job) Reset()         { *m = Job{} }
 func (m *Job) String() string { return proto.CompactTextString(m) }
 func (*Job) ProtoMessage()    {}
 func (*Job) Descriptor() ([]byte, []int) {
-	return fileDescriptor_9ea114fcbf489839, []int{3}
+	return fileDescriptor_9ea114fcbf489839, []int{4}
 }
 
 func (m *Job) XXX_Unmarshal(b []byte) error {
@@ -227,10 +288,16 @@ type Job_Tile struct {
 	Tile *Tile `protobuf:"bytes,2,opt,name=tile,proto3,oneof"`
 }
 
+type Job_Frame struct {
+	Frame *Frame `protobuf:"bytes,3,opt,name=frame,proto3,oneof"`
+}
+
 func (*Job_Scene) isJob_Request() {}
 
 func (*Job_Tile) isJob_Request() {}
 
+func (*Job_Frame) isJob_Request() {}
+
 func (m *Job) GetRequest() isJob_Request {
 	if m != nil {
 		return m.Request
@@ -252,43 +319,184 @@ func (m *Job) GetTile() *Tile {
 	return nil
 }
 
+func (m *Job) GetFrame() *Frame {
+	if x, ok := m.GetRequest().(*Job_Frame); ok {
+		return x.Frame
+	}
+	return nil
+}
+
 // XXX_OneofWrappers is for the internal use of the proto package.
 func (*Job) XXX_OneofWrappers() []interface{} {
 	return []interface{}{
 		(*Job_Scene)(nil),
 		(*Job_Tile)(nil),
+		(*Job_Frame)(nil),
+	}
+}
+
+// Asset is a texture or model blob, addressed by the hex encoded SHA-256 of
+// its data. Scenes refer to uploaded assets with `hash: <hash>` instead of
+// inlining them as base64.
+type Asset struct {
+	Hash                 string   `protobuf:"bytes,1,opt,name=hash,proto3" json:"hash,omitempty"`
+	Data                 []byte   `protobuf:"bytes,2,opt,name=data,proto3" json:"data,omitempty"`
+	XXX_NoUnkeyedLiteral struct{} `json:"-"`
+	XXX_unrecognized     []byte   `json:"-"`
+	XXX_sizecache        int32    `json:"-"`
+}
+
+func (m *Asset) Reset()         { *m = Asset{} }
+func (m *Asset) String() string { return proto.CompactTextString(m) }
+func (*Asset) ProtoMessage()    {}
+func (*Asset) Descriptor() ([]byte, []int) {
+	return fileDescriptor_9ea114fcbf489839, []int{5}
+}
+
+func (m *Asset) XXX_Unmarshal(b []byte) error {
+	return xxx_messageInfo_Asset.Unmarshal(m, b)
+}
+func (m *Asset) XXX_Marshal(b []byte, deterministic bool) ([]byte, error) {
+	return xxx_messageInfo_Asset.Marshal(b, m, deterministic)
+}
+func (m *Asset) XXX_Merge(src proto.Message) {
+	xxx_messageInfo_Asset.Merge(m, src)
+}
+func (m *Asset) XXX_Size() int {
+	return xxx_messageInfo_Asset.Size(m)
+}
+func (m *Asset) XXX_DiscardUnknown() {
+	xxx_messageInfo_Asset.DiscardUnknown(m)
+}
+
+var xxx_messageInfo_Asset proto.InternalMessageInfo
+
+func (m *Asset) GetHash() string {
+	if m != nil {
+		return m.Hash
 	}
+	return ""
+}
+
+func (m *Asset) GetData() []byte {
+	if m != nil {
+		return m.Data
+	}
+	return nil
+}
+
+type ListAssetsRequest struct {
+	XXX_NoUnkeyedLiteral struct{} `json:"-"`
+	XXX_unrecognized     []byte   `json:"-"`
+	XXX_sizecache        int32    `json:"-"`
+}
+
+func (m *ListAssetsRequest) Reset()         { *m = ListAssetsRequest{} }
+func (m *ListAssetsRequest) String() string { return proto.CompactTextString(m) }
+func (*ListAssetsRequest) ProtoMessage()    {}
+func (*ListAssetsRequest) Descriptor() ([]byte, []int) {
+	return fileDescriptor_9ea114fcbf489839, []int{6}
+}
+
+func (m *ListAssetsRequest) XXX_Unmarshal(b []byte) error {
+	return xxx_messageInfo_ListAssetsRequest.Unmarshal(m, b)
+}
+func (m *ListAssetsRequest) XXX_Marshal(b []byte, deterministic bool) ([]byte, error) {
+	return xxx_messageInfo_ListAssetsRequest.Marshal(b, m, deterministic)
+}
+func (m *ListAssetsRequest) XXX_Merge(src proto.Message) {
+	xxx_messageInfo_ListAssetsRequest.Merge(m, src)
+}
+func (m *ListAssetsRequest) XXX_Size() int {
+	return xxx_messageInfo_ListAssetsRequest.Size(m)
+}
+func (m *ListAssetsRequest) XXX_DiscardUnknown() {
+	xxx_messageInfo_ListAssetsRequest.DiscardUnknown(m)
+}
+
+var xxx_messageInfo_ListAssetsRequest proto.InternalMessageInfo
+
+// AssetList is a list of asset hashes.
+type AssetList struct {
+	Hashes               []string `protobuf:"bytes,1,rep,name=hashes,proto3" json:"hashes,omitempty"`
+	XXX_NoUnkeyedLiteral struct{} `json:"-"`
+	XXX_unrecognized     []byte   `json:"-"`
+	XXX_sizecache        int32    `json:"-"`
+}
+
+func (m *AssetList) Reset()         { *m = AssetList{} }
+func (m *AssetList) String() string { return proto.CompactTextString(m) }
+func (*AssetList) ProtoMessage()    {}
+func (*AssetList) Descriptor() ([]byte, []int) {
+	return fileDescriptor_9ea114fcbf489839, []int{7}
+}
+
+func (m *AssetList) XXX_Unmarshal(b []byte) error {
+	return xxx_messageInfo_AssetList.Unmarshal(m, b)
+}
+func (m *AssetList) XXX_Marshal(b []byte, deterministic bool) ([]byte, error) {
+	return xxx_messageInfo_AssetList.Marshal(b, m, deterministic)
+}
+func (m *AssetList) XXX_Merge(src proto.Message) {
+	xxx_messageInfo_AssetList.Merge(m, src)
+}
+func (m *AssetList) XXX_Size() int {
+	return xxx_messageInfo_AssetList.Size(m)
+}
+func (m *AssetList) XXX_DiscardUnknown() {
+	xxx_messageInfo_AssetList.DiscardUnknown(m)
+}
+
+var xxx_messageInfo_AssetList proto.InternalMessageInfo
+
+func (m *AssetList) GetHashes() []string {
+	if m != nil {
+		return m.Hashes
+	}
+	return nil
 }
 
 func init() {
 	proto.RegisterType((*Tile)(nil), "net_render.Tile")
+	proto.RegisterType((*Frame)(nil), "net_render.Frame")
 	proto.RegisterType((*Pixels)(nil), "net_render.Pixels")
 	proto.RegisterType((*Pixel)(nil), "net_render.Pixel")
 	proto.RegisterType((*Job)(nil), "net_render.Job")
+	proto.RegisterType((*Asset)(nil), "net_render.Asset")
+	proto.RegisterType((*ListAssetsRequest)(nil), "net_render.ListAssetsRequest")
+	proto.RegisterType((*AssetList)(nil), "net_render.AssetList")
 }
 
 func init() { proto.RegisterFile("proto/net_render.proto", fileDescriptor_9ea114fcbf489839) }
 
 var fileDescriptor_9ea114fcbf489839 = []byte{
-	// 277 bytes of a gzipped FileDescriptorProto
-	0x1f, 0x8b, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x02, 0xff, 0x7c, 0x90, 0xcf, 0x4b, 0xfb, 0x40,
-	0x10, 0xc5, 0xbb, 0xdf, 0xfc, 0x28, 0x9d, 0x7e, 0x45, 0x5d, 0xa4, 0x04, 0x4f, 0x25, 0x07, 0x89,
-	0x22, 0x89, 0xa6, 0x20, 0x88, 0xb7, 0x9e, 0x4a, 0x4f, 0x65, 0x11, 0x04, 0x2f, 0x62, 0x92, 0xa1,
-	0x86, 0xc6, 0x4c, 0xdc, 0xdd, 0x42, 0xea, 0x5f, 0x2f, 0x99, 0x08, 0x06, 0x04, 0x6f, 0xf3, 0x3e,
-	0xfb, 0x66, 0xdf, 0xcc, 0xc0, 0xac, 0xd1, 0x64, 0x29, 0xa9, 0xd1, 0xbe, 0x68, 0xac, 0x0b, 0xd4,
-	0x31, 0x03, 0x09, 0x3f, 0x24, 0xbc, 0x03, 0xf7, 0xb1, 0xac, 0x50, 0xfe, 0x07, 0xd1, 0x06, 0x62,
-	0x2e, 0xa2, 0x23, 0x25, 0xda, 0x4e, 0x1d, 0x82, 0x7f, 0xbd, 0x3a, 0x48, 0x09, 0xae, 0x29, 0x3f,
-	0x31, 0x70, 0x18, 0x70, 0x1d, 0x2e, 0xc0, 0xdf, 0x94, 0x2d, 0x56, 0x46, 0x5e, 0x82, 0xdf, 0x70,
-	0x15, 0x88, 0xb9, 0x13, 0x4d, 0xd3, 0xd3, 0x78, 0x10, 0xc8, 0x1e, 0xf5, 0x6d, 0x08, 0xef, 0xc1,
-	0x63, 0xf0, 0x67, 0xda, 0x19, 0x78, 0x39, 0x55, 0xa4, 0x39, 0x6e, 0xac, 0x7a, 0x11, 0x6e, 0xc0,
-	0x59, 0x53, 0x26, 0x67, 0xe0, 0x99, 0x1c, 0x6b, 0xe4, 0xe6, 0xc9, 0x6a, 0xa4, 0x7a, 0x29, 0x2f,
-	0xc0, 0xb5, 0x65, 0x85, 0xfc, 0xcb, 0x34, 0x3d, 0x19, 0x8e, 0xd0, 0xad, 0xb7, 0x1a, 0x29, 0x7e,
-	0x5f, 0x4e, 0x60, 0xac, 0xf1, 0x63, 0x8f, 0xc6, 0xa6, 0x0f, 0xe0, 0x3f, 0x91, 0xde, 0xa1, 0x96,
-	0xb7, 0xe0, 0x2b, 0xf6, 0xca, 0xe3, 0x61, 0xe3, 0x9a, 0xb2, 0x73, 0xf9, 0x6b, 0x19, 0x13, 0x89,
-	0x1b, 0xb1, 0xbc, 0x7e, 0xbe, 0xda, 0x96, 0xf6, 0x6d, 0x9f, 0xc5, 0x39, 0xbd, 0x27, 0x05, 0xd5,
-	0x64, 0x6b, 0x22, 0x9b, 0x58, 0xfd, 0x9a, 0xa3, 0xee, 0x4e, 0x9e, 0x53, 0x81, 0x49, 0xb3, 0xdb,
-	0x26, 0x4d, 0x96, 0xf9, 0x7c, 0xf7, 0xc5, 0x57, 0x00, 0x00, 0x00, 0xff, 0xff, 0xeb, 0xdb, 0x2d,
-	0xc0, 0x91, 0x01, 0x00, 0x00,
+	// 405 bytes of a gzipped FileDescriptorProto
+	0x1f, 0x8b, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x02, 0xff, 0x7d, 0x52, 0xdf, 0x4b, 0x02, 0x41,
+	0x10, 0xee, 0xd2, 0x3b, 0x71, 0x32, 0xca, 0xad, 0x44, 0x84, 0x20, 0xae, 0x08, 0x8b, 0xf0, 0xca,
+	0x20, 0xa8, 0xb7, 0x84, 0xa2, 0xa2, 0x87, 0x58, 0x8a, 0xa0, 0x97, 0xb8, 0x1f, 0x9b, 0x1e, 0xea,
+	0xed, 0xb5, 0xbb, 0x82, 0xd6, 0x9f, 0xd6, 0x3f, 0xd7, 0xee, 0xdc, 0x81, 0x17, 0x92, 0x6f, 0xf3,
+	0xcd, 0x7c, 0xdf, 0xcc, 0x37, 0xb3, 0x0b, 0x8d, 0x54, 0x70, 0xc5, 0xbd, 0x84, 0xa9, 0x77, 0xc1,
+	0x92, 0x88, 0x89, 0x0e, 0x26, 0x08, 0xcc, 0x33, 0xee, 0x05, 0x94, 0x9f, 0xe3, 0x11, 0x23, 0x35,
+	0xb0, 0xa6, 0x4d, 0x6b, 0xcf, 0x6a, 0xaf, 0x53, 0x6b, 0x6a, 0xd0, 0xac, 0xb9, 0x9a, 0xa1, 0x19,
+	0x21, 0x50, 0x96, 0xf1, 0x17, 0x6b, 0x96, 0x30, 0x81, 0xb1, 0x7b, 0x03, 0xf6, 0xad, 0xf0, 0xc7,
+	0x8c, 0x34, 0xc0, 0x49, 0x26, 0xe3, 0x80, 0x89, 0x5c, 0x9d, 0x23, 0x72, 0x00, 0x65, 0xa5, 0x1b,
+	0x63, 0x97, 0xb5, 0xee, 0x66, 0xa7, 0xe0, 0xc2, 0x0c, 0xa4, 0x58, 0x75, 0xef, 0xc1, 0x79, 0x8a,
+	0xa7, 0x6c, 0x24, 0xc9, 0x11, 0x38, 0x29, 0x46, 0xba, 0x4f, 0x49, 0x2b, 0xea, 0x45, 0x05, 0x72,
+	0x68, 0x4e, 0x20, 0xdb, 0x60, 0x7f, 0x98, 0xd9, 0xb9, 0xc3, 0x0c, 0xb8, 0x97, 0x60, 0x23, 0x6d,
+	0xe9, 0x2a, 0x5a, 0x1a, 0xf2, 0x11, 0x17, 0xb8, 0x4b, 0x85, 0x66, 0xc0, 0xfd, 0x86, 0xd2, 0x03,
+	0x0f, 0xf4, 0x2a, 0xb6, 0x0c, 0x59, 0xc2, 0x50, 0x5c, 0xbd, 0x5b, 0xa1, 0x19, 0x24, 0x87, 0xcb,
+	0x57, 0xd1, 0x44, 0xac, 0xeb, 0x15, 0x72, 0x5f, 0x25, 0x24, 0xfe, 0xd9, 0x00, 0x8f, 0x65, 0x5a,
+	0x22, 0xa3, 0x57, 0x85, 0x8a, 0x60, 0x9f, 0x13, 0x26, 0x95, 0xeb, 0x81, 0x7d, 0x2d, 0x25, 0x53,
+	0xe6, 0xcc, 0x03, 0x5f, 0x0e, 0xb2, 0xe9, 0x14, 0x63, 0x93, 0x8b, 0x7c, 0xe5, 0xe3, 0xe8, 0x1a,
+	0xc5, 0xd8, 0xdd, 0x82, 0xfa, 0x63, 0x2c, 0x15, 0x8a, 0x24, 0xcd, 0xbb, 0xec, 0x43, 0x15, 0x13,
+	0xa6, 0x62, 0xde, 0xc4, 0xa8, 0x59, 0x76, 0xcb, 0x2a, 0xcd, 0x51, 0xf7, 0xc7, 0x02, 0xe7, 0x95,
+	0x8b, 0xa1, 0x7e, 0x9e, 0x33, 0x70, 0x28, 0x3a, 0x23, 0x1b, 0x45, 0x9b, 0xfa, 0x0c, 0x2d, 0xb2,
+	0x70, 0x79, 0xd9, 0xb6, 0x4e, 0x2d, 0xd2, 0x03, 0x98, 0xcf, 0x25, 0xbb, 0x45, 0xd6, 0x82, 0x9f,
+	0xd6, 0x4e, 0xb1, 0x3c, 0x77, 0x76, 0x05, 0xb5, 0x97, 0x74, 0xc4, 0xfd, 0x28, 0xef, 0x52, 0x5f,
+	0xa0, 0xfd, 0xa3, 0x6c, 0x5b, 0xbd, 0x93, 0xb7, 0xe3, 0x7e, 0xac, 0x06, 0x93, 0xa0, 0x13, 0xf2,
+	0xb1, 0x17, 0xf1, 0x84, 0xab, 0x84, 0x73, 0xe5, 0x29, 0xe1, 0x87, 0x4c, 0x98, 0x6f, 0x1e, 0xf2,
+	0x88, 0x79, 0xe9, 0xb0, 0xef, 0xa5, 0x41, 0xe0, 0xe0, 0x5f, 0x3f, 0xff, 0x05, 0x05, 0xd7, 0xf8,
+	0xce, 0x05, 0x03, 0x00, 0x00,
 }
 
 // Reference imports to suppress errors if they are not otherwise used.
@@ -304,6 +512,12 @@ const _ = grpc.SupportPackageIsVersion4
 // For semantics around ctx use and closing/ending streaming RPCs, please refer to https://godoc.org/google.golang.org/grpc#ClientConn.NewStream.
 type WorkerClient interface {
 	Render(ctx context.Context, opts ...grpc.CallOption) (Worker_RenderClient, error)
+	// ListAssets returns the hashes of every asset the worker has cached, so
+	// that only the missing ones need to be uploaded.
+	ListAssets(ctx context.Context, in *ListAssetsRequest, opts ...grpc.CallOption) (*AssetList, error)
+	// UploadAssets stores assets in the worker's cache and returns the hashes
+	// of the stored assets.
+	UploadAssets(ctx context.Context, opts ...grpc.CallOption) (Worker_UploadAssetsClient, error)
 }
 
 type workerClient struct {
@@ -345,9 +559,58 @@ func (x *workerRenderClient) Recv() (*Pixels, error) {
 	return m, nil
 }
 
+func (c *workerClient) ListAssets(ctx context.Context, in *ListAssetsRequest, opts ...grpc.CallOption) (*AssetList, error) {
+	out := new(AssetList)
+	err := c.cc.Invoke(ctx, "/net_render.Worker/ListAssets", in, out, opts...)
+	if err != nil {
+		return nil, err
+	}
+	return out, nil
+}
+
+func (c *workerClient) UploadAssets(ctx context.Context, opts ...grpc.CallOption) (Worker_UploadAssetsClient, error) {
+	stream, err := c.cc.NewStream(ctx, &_Worker_serviceDesc.Streams[1], "/net_render.Worker/UploadAssets", opts...)
+	if err != nil {
+		return nil, err
+	}
+	x := &workerUploadAssetsClient{stream}
+	return x, nil
+}
+
+type Worker_UploadAssetsClient interface {
+	Send(*Asset) error
+	CloseAndRecv() (*AssetList, error)
+	grpc.ClientStream
+}
+
+type workerUploadAssetsClient struct {
+	grpc.ClientStream
+}
+
+func (x *workerUploadAssetsClient) Send(m *Asset) error {
+	return x.ClientStream.SendMsg(m)
+}
+
+func (x *workerUploadAssetsClient) CloseAndRecv() (*AssetList, error) {
+	if err := x.ClientStream.CloseSend(); err != nil {
+		return nil, err
+	}
+	m := new(AssetList)
+	if err := x.ClientStream.RecvMsg(m); err != nil {
+		return nil, err
+	}
+	return m, nil
+}
+
 // WorkerServer is the server API for Worker service.
 type WorkerServer interface {
 	Render(Worker_RenderServer) error
+	// ListAssets returns the hashes of every asset the worker has cached, so
+	// that only the missing ones need to be uploaded.
+	ListAssets(context.Context, *ListAssetsRequest) (*AssetList, error)
+	// UploadAssets stores assets in the worker's cache and returns the hashes
+	// of the stored assets.
+	UploadAssets(Worker_UploadAssetsServer) error
 }
 
 // UnimplementedWorkerServer can be embedded to have forward compatible implementations.
@@ -357,6 +620,12 @@ type UnimplementedWorkerServer struct {
 func (*UnimplementedWorkerServer) Render(srv Worker_RenderServer) error {
 	return status.Errorf(codes.Unimplemented, "method Render not implemented")
 }
+func (*UnimplementedWorkerServer) ListAssets(ctx context.Context, req *ListAssetsRequest) (*AssetList, error) {
+	return nil, status.Errorf(codes.Unimplemented, "method ListAssets not implemented")
+}
+func (*UnimplementedWorkerServer) UploadAssets(srv Worker_UploadAssetsServer) error {
+	return status.Errorf(codes.Unimplemented, "method UploadAssets not implemented")
+}
 
 func RegisterWorkerServer(s *grpc.Server, srv WorkerServer) {
 	s.RegisterService(&_Worker_serviceDesc, srv)
@@ -388,10 +657,59 @@ func (x *workerRenderServer) Recv() (*Job, error) {
 	return m, nil
 }
 
+func _Worker_ListAssets_Handler(srv interface{}, ctx context.Context, dec func(interface{}) error, interceptor grpc.UnaryServerInterceptor) (interface{}, error) {
+	in := new(ListAssetsRequest)
+	if err := dec(in); err != nil {
+		return nil, err
+	}
+	if interceptor == nil {
+		return srv.(WorkerServer).ListAssets(ctx, in)
+	}
+	info := &grpc.UnaryServerInfo{
+		Server:     srv,
+		FullMethod: "/net_render.Worker/ListAssets",
+	}
+	handler := func(ctx context.Context, req interface{}) (interface{}, error) {
+		return srv.(WorkerServer).ListAssets(ctx, req.(*ListAssetsRequest))
+	}
+	return interceptor(ctx, in, info, handler)
+}
+
+func _Worker_UploadAssets_Handler(srv interface{}, stream grpc.ServerStream) error {
+	return srv.(WorkerServer).UploadAssets(&workerUploadAssetsServer{stream})
+}
+
+type Worker_UploadAssetsServer interface {
+	SendAndClose(*AssetList) error
+	Recv() (*Asset, error)
+	grpc.ServerStream
+}
+
+type workerUploadAssetsServer struct {
+	grpc.ServerStream
+}
+
+func (x *workerUploadAssetsServer) SendAndClose(m *AssetList) error {
+	return x.ServerStream.SendMsg(m)
+}
+
+func (x *workerUploadAssetsServer) Recv() (*Asset, error) {
+	m := new(Asset)
+	if err := x.ServerStream.RecvMsg(m); err != nil {
+		return nil, err
+	}
+	return m, nil
+}
+
 var _Worker_serviceDesc = grpc.ServiceDesc{
 	ServiceName: "net_render.Worker",
 	HandlerType: (*WorkerServer)(nil),
-	Methods:     []grpc.MethodDesc{},
+	Methods: []grpc.MethodDesc{
+		{
+			MethodName: "ListAssets",
+			Handler:    _Worker_ListAssets_Handler,
+		},
+	},
 	Streams: []grpc.StreamDesc{
 		{
 			StreamName:    "Render",
@@ -399,6 +717,11 @@ var _Worker_serviceDesc = grpc.ServiceDesc{
 			ServerStreams: true,
 			ClientStreams: true,
 		},
+		{
+			StreamName:    "UploadAssets",
+			Handler:       _Worker_UploadAssets_Handler,
+			ClientStreams: true,
+		},
 	},
 	Metadata: "proto/net_render.proto",
 }
@@ -5,6 +5,9 @@ use futures::{Stream, StreamExt};
 use log::*;
 use pretty_env_logger;
 use rayon::prelude::*;
+use rstracer::tracer::asset_store::AssetStore;
+use rstracer::tracer::camera::Camera;
+use rstracer::tracer::world::World;
 use rstracer::tracer::*;
 use std::error::Error;
 use std::net::{Ipv4Addr, SocketAddr, SocketAddrV4};
@@ -18,10 +21,12 @@ pub mod net_render {
 
 use net_render::job::Request as JobRequest;
 use net_render::worker_server::{Worker, WorkerServer};
-use net_render::{Job, Pixel, Pixels};
+use net_render::{Asset, AssetList, Job, ListAssetsRequest, Pixel, Pixels, Tile};
 
 #[derive(Debug)]
-pub struct WorkerService {}
+pub struct WorkerService {
+    store: AssetStore,
+}
 
 #[tonic::async_trait]
 impl Worker for WorkerService {
@@ -46,52 +51,135 @@ impl Worker for WorkerService {
         }?;
         info!("Received scene successfully");
 
-        let (world, camera, _) = match scene_parser::from_reader(scene.as_bytes()) {
-            Ok((world, camera, rendering_spec)) => Ok((world, camera, rendering_spec)),
+        let animation = match scene_parser::Animation::from_reader(scene.as_bytes()) {
+            Ok(animation) => Ok(animation.with_store(self.store.clone())),
             Err(err) => Err(Status::new(
                 Code::InvalidArgument,
                 format!("invalid rendering spec: {}", err),
             )),
         }?;
+        let first_frame = animation.frames().0;
+        let (world, camera, _) = animation
+            .frame(first_frame)
+            .map_err(|err| invalid_frame(first_frame, err))?;
+        let mut current = (first_frame, world, camera);
         info!("Parsed scene successfully");
 
         let output = async_stream::try_stream! {
             while let Some(job) = stream.next().await {
-                let tile = match job?.request.unwrap() {
-                    JobRequest::Tile(tile) => Ok(tile),
+                let (number, tile) = match job?.request.unwrap() {
+                    JobRequest::Tile(tile) => Ok((first_frame, Some(tile))),
+                    JobRequest::Frame(frame) => Ok((frame.number, frame.tile)),
                     _ => Err(Status::new(
                         Code::InvalidArgument,
-                        "consecutive messages must be tiles!".to_string(),
+                        "consecutive messages must be tiles or frames!".to_string(),
                     )),
                 }?;
-                info!("Rendering tile: {:?}", tile);
 
-                let mut pixels: Vec<(u32, u32)> = vec![];
-                for x in tile.x..(tile.x + tile.size) {
-                    for y in tile.y..(tile.y + tile.size) {
-                        pixels.push((x, y));
-                    }
+                // Textures and models are cached by the animation, so only the scene itself
+                // is rebuilt when the frame changes.
+                if current.0 != number {
+                    let (world, camera, _) = animation
+                        .frame(number)
+                        .map_err(|err| invalid_frame(number, err))?;
+                    current = (number, world, camera);
+                    info!("Built frame {}", number);
                 }
+                let (_, world, camera) = &current;
+
+                let pixels = match tile {
+                    Some(tile) => {
+                        info!("Rendering tile of frame {}: {:?}", number, tile);
+                        tile_pixels(&tile)
+                    }
+                    None => {
+                        info!("Rendering frame {}", number);
+                        let (width, height) = (camera.h_size as u32, camera.v_size as u32);
+                        (0..width * height).map(|i| (i % width, i / width)).collect()
+                    }
+                };
 
-                let pixels = pixels
-                    .into_par_iter()
-                    .with_max_len(1)
-                    .map(|(x, y)| {
-                        Pixel {
-                            x,
-                            y,
-                            color: tup_to_u32_color(camera.render_pixel(&world, x, y)),
-                        }
-                    }).collect::<Vec<Pixel>>();
+                let pixels = render_pixels(world, camera, pixels);
 
-                info!("Rendered tile successfully");
+                info!("Rendered job successfully");
 
-                yield Pixels{pixels}
+                yield Pixels{pixels, frame: number}
             }
         };
 
         Ok(Response::new(Box::pin(output) as Self::RenderStream))
     }
+
+    async fn list_assets(
+        &self,
+        _: Request<ListAssetsRequest>,
+    ) -> Result<Response<AssetList>, Status> {
+        match self.store.hashes() {
+            Ok(hashes) => Ok(Response::new(AssetList { hashes })),
+            Err(err) => Err(Status::new(
+                Code::Internal,
+                format!("could not list assets: {}", err),
+            )),
+        }
+    }
+
+    async fn upload_assets(
+        &self,
+        request: Request<Streaming<Asset>>,
+    ) -> Result<Response<AssetList>, Status> {
+        let mut stream = request.into_inner();
+
+        let mut hashes = vec![];
+        while let Some(asset) = stream.next().await {
+            let asset = asset?;
+            if AssetStore::hash(&asset.data) != asset.hash {
+                return Err(Status::new(
+                    Code::InvalidArgument,
+                    format!("asset data does not match hash '{}'", asset.hash),
+                ));
+            }
+
+            let hash = self.store.put(&asset.data).map_err(|err| {
+                Status::new(
+                    Code::Internal,
+                    format!("could not store asset '{}': {}", asset.hash, err),
+                )
+            })?;
+            info!("Stored asset {} ({} bytes)", hash, asset.data.len());
+            hashes.push(hash);
+        }
+
+        Ok(Response::new(AssetList { hashes }))
+    }
+}
+
+fn invalid_frame(number: u32, err: Box<dyn Error>) -> Status {
+    Status::new(
+        Code::InvalidArgument,
+        format!("invalid frame {}: {}", number, err),
+    )
+}
+
+fn tile_pixels(tile: &Tile) -> Vec<(u32, u32)> {
+    let mut pixels: Vec<(u32, u32)> = vec![];
+    for x in tile.x..(tile.x + tile.size) {
+        for y in tile.y..(tile.y + tile.size) {
+            pixels.push((x, y));
+        }
+    }
+    pixels
+}
+
+fn render_pixels(world: &World, camera: &Camera, pixels: Vec<(u32, u32)>) -> Vec<Pixel> {
+    pixels
+        .into_par_iter()
+        .with_max_len(1)
+        .map(|(x, y)| Pixel {
+            x,
+            y,
+            color: tup_to_u32_color(camera.render_pixel(world, x, y)),
+        })
+        .collect::<Vec<Pixel>>()
 }
 
 fn tup_to_u32_color(t: tuple::Tup) -> u32 {
@@ -123,6 +211,13 @@ async fn main() -> Result<(), Box<dyn Error>> {
                 .default_value("11811")
                 .help("Port on which to start serving"),
         )
+        .arg(
+            Arg::with_name("assets")
+                .short("a")
+                .long("assets")
+                .default_value("assets")
+                .help("Directory in which uploaded assets are cached"),
+        )
         .get_matches();
 
     let port: u16 = matches.value_of("port").unwrap().parse()?;
@@ -130,7 +225,13 @@ async fn main() -> Result<(), Box<dyn Error>> {
 
     let socket_v4 = SocketAddrV4::new(Ipv4Addr::new(0, 0, 0, 0), port);
 
-    let svc = WorkerServer::new(WorkerService {});
+    let store = AssetStore::new(matches.value_of("assets").unwrap())?;
+    info!(
+        "Using asset cache {:?}",
+        matches.value_of("assets").unwrap()
+    );
+
+    let svc = WorkerServer::new(WorkerService { store });
 
     info!("Serving on {}", socket_v4);
     Server::builder()
@@ -0,0 +1,192 @@
+use clap::{App, Arg};
+use image::{Rgb, RgbImage};
+use log::*;
+use rayon::prelude::*;
+use rstracer::tracer::aov::{Aovs, PassImages};
+use rstracer::tracer::camera::Camera;
+use rstracer::tracer::tuple::Tup;
+use rstracer::tracer::world::World;
+use rstracer::tracer::*;
+use std::error::Error;
+use std::time::Instant;
+
+fn main() -> Result<(), Box<dyn Error>> {
+    pretty_env_logger::formatted_builder()
+        .filter(None, log::LevelFilter::Info)
+        .init();
+
+    let matches = App::new("Tracer Renderer")
+        .version("0.1.0")
+        .arg(
+            Arg::with_name("scene")
+                .required(true)
+                .index(1)
+                .help("Scene file to render"),
+        )
+        .arg(
+            Arg::with_name("frames")
+                .short("f")
+                .long("frames")
+                .takes_value(true)
+                .help("Frames to render, either a single one or a range like 1..240"),
+        )
+        .arg(
+            Arg::with_name("output")
+                .short("o")
+                .long("output")
+                .default_value("frame_####.png")
+                .help(
+                    "Output image, '#' characters are replaced with the frame number. Passes are \
+                     written next to it, or as layers when writing an .exr file",
+                ),
+        )
+        .get_matches();
+
+    let scene_path = matches.value_of("scene").unwrap();
+    let animation = scene_parser::Animation::from_reader(std::fs::File::open(scene_path)?)?;
+    info!("Read scene {}", scene_path);
+
+    let (first, last) = match matches.value_of("frames") {
+        Some(frames) => parse_frames(frames)?,
+        None => animation.frames(),
+    };
+    let output = matches.value_of("output").unwrap();
+
+    for frame in first..=last {
+        let started_at = Instant::now();
+        let (world, camera, rendering) = animation.frame(frame)?;
+
+        let path = output_path(output, frame, first != last);
+        if rendering.passes.is_empty()
+            && rendering.denoise.is_none()
+            && rendering.postprocess.is_empty()
+            && !path.ends_with(".exr")
+        {
+            render(&world, &camera).save(&path)?;
+        } else {
+            let (mut beauty, aovs) = render_with_aovs(&world, &camera);
+            if let Some(denoiser) = &rendering.denoise {
+                beauty = denoiser.apply(camera.h_size as u32, &beauty, &aovs);
+            }
+            beauty = postprocess::apply(&rendering.postprocess, camera.h_size as u32, beauty);
+
+            let mut passes = PassImages::new(
+                &rendering.passes,
+                camera.h_size as u32,
+                camera.v_size as u32,
+            );
+            for (i, aovs) in aovs.iter().enumerate() {
+                passes.set(i as u32 % passes.width, i as u32 / passes.width, aovs);
+            }
+            save_with_passes(&camera, &beauty, &passes, &path)?;
+        }
+        info!(
+            "Rendered frame {} to {} in {:?}",
+            frame,
+            path,
+            started_at.elapsed()
+        );
+    }
+
+    Ok(())
+}
+
+fn render(world: &World, camera: &Camera) -> RgbImage {
+    let (width, height) = (camera.h_size as u32, camera.v_size as u32);
+
+    let pixels = (0..width * height)
+        .into_par_iter()
+        .map(|i| {
+            let (x, y) = (i % width, i / width);
+            camera.render_pixel(world, x, y)
+        })
+        .collect::<Vec<Tup>>();
+
+    let c = |c: f32| -> u8 { (c * 255.0).clamp(0.0, 255.0) as u8 };
+    RgbImage::from_fn(width, height, |x, y| {
+        let p = &pixels[(y * width + x) as usize];
+        Rgb([c(p.x), c(p.y), c(p.z)])
+    })
+}
+
+/// Renders the image before tone mapping, along with the passes of every pixel.
+fn render_with_aovs(world: &World, camera: &Camera) -> (Vec<Tup>, Vec<Aovs>) {
+    let (width, height) = (camera.h_size as u32, camera.v_size as u32);
+
+    (0..width * height)
+        .into_par_iter()
+        .map(|i| {
+            let (x, y) = (i % width, i / width);
+            camera.render_pixel_with_aovs(world, x, y)
+        })
+        .unzip()
+}
+
+/// Writes a linear EXR with the passes as layers, or tone mapped PNGs with every pass in
+/// its own file, e.g. `frame_0001.depth.png`.
+fn save_with_passes(
+    camera: &Camera,
+    beauty: &[Tup],
+    passes: &PassImages,
+    path: &str,
+) -> Result<(), Box<dyn Error>> {
+    if path.ends_with(".exr") {
+        return passes.write_exr(beauty, path);
+    }
+
+    let c = |c: f32| -> u8 { (c * 255.0).clamp(0.0, 255.0) as u8 };
+    RgbImage::from_fn(passes.width, passes.height, |x, y| {
+        let p = camera.tone_map(beauty[(y * passes.width + x) as usize].clone());
+        Rgb([c(p.x), c(p.y), c(p.z)])
+    })
+    .save(path)?;
+
+    let (stem, extension) = match path.rfind('.') {
+        Some(dot) => (&path[..dot], &path[dot..]),
+        None => (path, ""),
+    };
+    for (pass, _) in passes.passes.iter() {
+        if let Some(image) = passes.preview(*pass) {
+            image.save(format!("{}.{}{}", stem, pass.name(), extension))?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Parses either a single frame ("12") or an inclusive range of frames ("1..240").
+fn parse_frames(frames: &str) -> Result<(u32, u32), Box<dyn Error>> {
+    match frames.split("..").collect::<Vec<&str>>().as_slice() {
+        [frame] => {
+            let frame = frame.trim().parse()?;
+            Ok((frame, frame))
+        }
+        [first, last] => {
+            let (first, last) = (first.trim().parse()?, last.trim().parse()?);
+            if first > last {
+                return Err(format!("invalid frame range '{}'", frames).into());
+            }
+            Ok((first, last))
+        }
+        _ => Err(format!("invalid frame range '{}'", frames).into()),
+    }
+}
+
+/// Replaces the run of '#' characters in the output path with the zero padded frame number. If
+/// there is none and a sequence is being rendered, the number is added before the extension.
+fn output_path(output: &str, frame: u32, sequence: bool) -> String {
+    match (output.find('#'), output.rfind('#')) {
+        (Some(start), Some(end)) => format!(
+            "{}{:0width$}{}",
+            &output[..start],
+            frame,
+            &output[end + 1..],
+            width = end + 1 - start
+        ),
+        _ if sequence => match output.rfind('.') {
+            Some(dot) => format!("{}_{:04}{}", &output[..dot], frame, &output[dot..]),
+            None => format!("{}_{:04}", output, frame),
+        },
+        _ => output.to_string(),
+    }
+}
@@ -0,0 +1,315 @@
+use serde::Deserialize;
+use serde_yaml::{Mapping, Value};
+
+/// How values are interpolated between two keyframes.
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq)]
+pub enum Interpolation {
+    Linear,
+    /// Cubic bezier with automatic handles: smooth through the keyframes, easing in and out of
+    /// the first and last ones.
+    Bezier,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct Key {
+    pub frame: f32,
+    pub value: Value,
+}
+
+/// A keyframed value somewhere in the scene file. The path is a dot separated list of mapping
+/// keys and sequence indices, e.g. `camera.from` or `objects.2.transform.0.RotateY`. Tracks
+/// interpolate linearly unless told otherwise. Numbers are always interpolated as floats, values
+/// that have to be whole numbers, like sample counts or colours written as 0 to 255, set `round`.
+#[derive(Debug, Deserialize, Clone)]
+pub struct Track {
+    pub path: String,
+    pub interpolation: Option<Interpolation>,
+    #[serde(default)]
+    pub round: bool,
+    pub keys: Vec<Key>,
+}
+
+impl Track {
+    /// Sets the value of the track at the given frame in the scene.
+    pub fn apply(&self, scene: &mut Value, frame: f32) -> Result<(), String> {
+        let value = self.value_at(frame)?;
+        let target = lookup(scene, &self.path)?;
+        *target = value;
+        Ok(())
+    }
+
+    pub fn value_at(&self, frame: f32) -> Result<Value, String> {
+        let mut keys = self.keys.iter().collect::<Vec<&Key>>();
+        keys.sort_by(|a, b| {
+            a.frame
+                .partial_cmp(&b.frame)
+                .unwrap_or(std::cmp::Ordering::Less)
+        });
+
+        let frames = keys.iter().map(|k| k.frame).collect::<Vec<f32>>();
+        let values = keys.iter().map(|k| &k.value).collect::<Vec<&Value>>();
+
+        let interpolation = self.interpolation.unwrap_or(Interpolation::Linear);
+        blend(&values, &frames, frame, interpolation, self.round)
+            .map_err(|err| format!("track '{}': {}", self.path, err))
+    }
+}
+
+/// Finds the value at the given path, creating the last mapping entry if it does not exist yet
+/// so that values left to their defaults can be animated too.
+fn lookup<'a>(scene: &'a mut Value, path: &str) -> Result<&'a mut Value, String> {
+    path.split('.')
+        .try_fold(scene, |value, segment| match value {
+            Value::Mapping(mapping) => {
+                let key = Value::String(segment.to_string());
+                if !mapping.contains_key(&key) {
+                    mapping.insert(key.clone(), Value::Null);
+                }
+                Ok(mapping.get_mut(&key).unwrap())
+            }
+            Value::Sequence(sequence) => {
+                let index: usize = segment
+                    .parse()
+                    .map_err(|_| format!("'{}' in '{}' is not a sequence index", segment, path))?;
+                let len = sequence.len();
+                sequence.get_mut(index).ok_or_else(|| {
+                    format!(
+                        "index {} in '{}' is out of bounds, there are {} elements",
+                        index, path, len
+                    )
+                })
+            }
+            _ => Err(format!("'{}' in '{}' does not exist", segment, path)),
+        })
+}
+
+/// Interpolates values that share the same structure. Numbers are interpolated, sequences and
+/// mappings element by element, anything else holds the value of the previous keyframe.
+/// Numbers are rounded to integers when `round` is set.
+fn blend(
+    values: &[&Value],
+    frames: &[f32],
+    frame: f32,
+    interpolation: Interpolation,
+    round: bool,
+) -> Result<Value, String> {
+    if values.is_empty() {
+        return Err("no keyframes".to_string());
+    }
+
+    match values[0] {
+        Value::Number(_) => {
+            let numbers = values
+                .iter()
+                .map(|v| v.as_f64().ok_or("keyframes have different types"))
+                .collect::<Result<Vec<f64>, &str>>()?;
+            let value = interpolate(&numbers, frames, frame, interpolation);
+
+            if round {
+                Ok(Value::from(value.round() as i64))
+            } else {
+                Ok(Value::from(value))
+            }
+        }
+        Value::Sequence(first) => {
+            let sequences = values
+                .iter()
+                .map(|v| match v {
+                    Value::Sequence(s) if s.len() == first.len() => Ok(s),
+                    _ => Err("keyframes have sequences of different lengths"),
+                })
+                .collect::<Result<Vec<&Vec<Value>>, &str>>()?;
+
+            (0..first.len())
+                .map(|i| {
+                    let elements = sequences.iter().map(|s| &s[i]).collect::<Vec<&Value>>();
+                    blend(&elements, frames, frame, interpolation, round)
+                })
+                .collect::<Result<Vec<Value>, String>>()
+                .map(Value::Sequence)
+        }
+        Value::Mapping(first) => {
+            let mut mapping = Mapping::new();
+            for (key, _) in first.iter() {
+                let elements = values
+                    .iter()
+                    .map(|v| v.get(key).ok_or("keyframes have different keys"))
+                    .collect::<Result<Vec<&Value>, &str>>()?;
+                let value = blend(&elements, frames, frame, interpolation, round)?;
+                mapping.insert(key.clone(), value);
+            }
+            Ok(Value::Mapping(mapping))
+        }
+        _ => {
+            let previous = frames.iter().rposition(|f| *f <= frame).unwrap_or(0);
+            Ok(values[previous].clone())
+        }
+    }
+}
+
+fn interpolate(values: &[f64], frames: &[f32], frame: f32, interpolation: Interpolation) -> f64 {
+    let last = values.len() - 1;
+    if frame <= frames[0] {
+        return values[0];
+    }
+    if frame >= frames[last] {
+        return values[last];
+    }
+
+    let next = frames.iter().position(|f| *f > frame).unwrap_or(last);
+    let i = next - 1;
+    let (f0, f1) = (frames[i] as f64, frames[next] as f64);
+    let (v0, v1) = (values[i], values[next]);
+    let s = (frame as f64 - f0) / (f1 - f0);
+
+    match interpolation {
+        Interpolation::Linear => v0 + (v1 - v0) * s,
+        Interpolation::Bezier => {
+            // Catmull-Rom slopes, flat at the ends of the track.
+            let slope = |k: usize| {
+                if k == 0 || k == last {
+                    0.0
+                } else {
+                    (values[k + 1] - values[k - 1]) / (frames[k + 1] - frames[k - 1]) as f64
+                }
+            };
+            let handle = (f1 - f0) / 3.0;
+            let c0 = v0 + slope(i) * handle;
+            let c1 = v1 - slope(next) * handle;
+
+            let r = 1.0 - s;
+            r.powi(3) * v0 + 3.0 * r.powi(2) * s * c0 + 3.0 * r * s.powi(2) * c1 + s.powi(3) * v1
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn track(yaml: &str) -> Track {
+        serde_yaml::from_str(yaml).unwrap()
+    }
+
+    #[test]
+    fn linear_interpolation_of_numbers_and_sequences() {
+        let t = track(
+            "
+path: camera.from
+keys:
+  - frame: 1
+    value: [0, 0, -5]
+  - frame: 11
+    value: [10, 0, 5]
+",
+        );
+
+        assert_eq!(
+            t.value_at(6.0).unwrap(),
+            serde_yaml::from_str::<Value>("[5.0, 0.0, 0.0]").unwrap()
+        );
+        assert_eq!(
+            t.value_at(0.0).unwrap(),
+            serde_yaml::from_str::<Value>("[0.0, 0.0, -5.0]").unwrap()
+        );
+        assert_eq!(
+            t.value_at(20.0).unwrap(),
+            serde_yaml::from_str::<Value>("[10.0, 0.0, 5.0]").unwrap()
+        );
+
+        let t = track("{path: x, keys: [{frame: 0, value: 0}, {frame: 4, value: 1}]}");
+        assert_eq!(t.value_at(1.0).unwrap(), Value::from(0.25));
+
+        let t =
+            track("{path: x, round: true, keys: [{frame: 0, value: 0}, {frame: 4, value: 10}]}");
+        assert_eq!(t.value_at(1.0).unwrap(), Value::from(3));
+    }
+
+    #[test]
+    fn bezier_interpolation_eases_in_and_out() {
+        let t = track(
+            "
+path: x
+interpolation: Bezier
+keys:
+  - frame: 0
+    value: 0
+  - frame: 10
+    value: 10
+",
+        );
+        let at = |f: f32| t.value_at(f).unwrap().as_f64().unwrap();
+
+        assert!((at(5.0) - 5.0).abs() < 10e-6);
+        assert!(at(1.0) < 1.0);
+        assert!(at(9.0) > 9.0);
+    }
+
+    #[test]
+    fn non_numeric_values_step() {
+        let t = track(
+            "
+path: materials.ball
+keys:
+  - frame: 0
+    value: {color: red, ambient: 0.0}
+  - frame: 10
+    value: {color: blue, ambient: 1}
+",
+        );
+
+        let v = t.value_at(4.0).unwrap();
+        assert_eq!(v.get("color").unwrap().as_str(), Some("red"));
+        assert_eq!(v.get("ambient").unwrap().as_f64(), Some(0.4));
+
+        let v = t.value_at(10.0).unwrap();
+        assert_eq!(v.get("color").unwrap().as_str(), Some("blue"));
+    }
+
+    #[test]
+    fn applying_tracks_to_a_scene() {
+        let mut scene: Value = serde_yaml::from_str(
+            "
+objects:
+  - shape: Sphere
+    transform:
+      - RotateY: 0
+materials:
+  glass: {}
+",
+        )
+        .unwrap();
+
+        track(
+            "
+path: objects.0.transform.0.RotateY
+keys: [{frame: 0, value: 0}, {frame: 10, value: 90}]
+",
+        )
+        .apply(&mut scene, 5.0)
+        .unwrap();
+        track(
+            "
+path: materials.glass.transparency
+keys: [{frame: 0, value: 0.0}, {frame: 10, value: 1.0}]
+",
+        )
+        .apply(&mut scene, 5.0)
+        .unwrap();
+
+        assert_eq!(
+            scene["objects"][0]["transform"][0]["RotateY"].as_f64(),
+            Some(45.0)
+        );
+        assert_eq!(
+            scene["materials"]["glass"]["transparency"].as_f64(),
+            Some(0.5)
+        );
+
+        assert!(
+            track("{path: objects.3.transform, keys: [{frame: 0, value: 1}]}")
+                .apply(&mut scene, 0.0)
+                .is_err()
+        );
+    }
+}
@@ -0,0 +1,235 @@
+use super::tuple::{color, Tup};
+use exr::prelude::{
+    AnyChannel, AnyChannels, Encoding, FlatSamples, Image, Layer, LayerAttributes, SmallVec,
+    WritableImage,
+};
+use image::{Rgb, RgbImage};
+use serde::Deserialize;
+use std::error::Error;
+
+/// Arbitrary output variables, extra images rendered next to the final one so that shots can be
+/// adjusted in compositing without rendering them again.
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq)]
+pub enum Pass {
+    /// Distance from the camera to the first surface hit.
+    Depth,
+    /// World space normal of the first surface hit.
+    Normal,
+    /// Surface colour before any lighting.
+    Albedo,
+    ObjectId,
+    MaterialId,
+    /// Light reaching the surface straight from the lights.
+    Direct,
+    /// Light reaching the surface through reflections and refractions.
+    Indirect,
+    Reflection,
+    Refraction,
+}
+
+impl Pass {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Pass::Depth => "depth",
+            Pass::Normal => "normal",
+            Pass::Albedo => "albedo",
+            Pass::ObjectId => "object_id",
+            Pass::MaterialId => "material_id",
+            Pass::Direct => "direct",
+            Pass::Indirect => "indirect",
+            Pass::Reflection => "reflection",
+            Pass::Refraction => "refraction",
+        }
+    }
+}
+
+/// Values of every pass for a camera ray, taken at the first surface it hits. Rays that hit
+/// nothing have an infinite depth and zero ids.
+#[derive(Debug, Clone)]
+pub struct Aovs {
+    pub depth: f32,
+    pub normal: Tup,
+    pub albedo: Tup,
+    pub object_id: u32,
+    pub material_id: u32,
+    pub direct: Tup,
+    pub reflection: Tup,
+    pub refraction: Tup,
+}
+
+impl Aovs {
+    pub fn background(background_color: Tup) -> Self {
+        Aovs {
+            depth: f32::INFINITY,
+            normal: color(0.0, 0.0, 0.0),
+            albedo: color(0.0, 0.0, 0.0),
+            object_id: 0,
+            material_id: 0,
+            direct: background_color,
+            reflection: color(0.0, 0.0, 0.0),
+            refraction: color(0.0, 0.0, 0.0),
+        }
+    }
+
+    /// Value of a pass. Single channel passes are repeated in every channel.
+    pub fn get(&self, pass: Pass) -> Tup {
+        match pass {
+            Pass::Depth => color(self.depth, self.depth, self.depth),
+            Pass::Normal => self.normal.clone(),
+            Pass::Albedo => self.albedo.clone(),
+            Pass::ObjectId => {
+                let id = self.object_id as f32;
+                color(id, id, id)
+            }
+            Pass::MaterialId => {
+                let id = self.material_id as f32;
+                color(id, id, id)
+            }
+            Pass::Direct => self.direct.clone(),
+            Pass::Indirect => &self.reflection + &self.refraction,
+            Pass::Reflection => self.reflection.clone(),
+            Pass::Refraction => self.refraction.clone(),
+        }
+    }
+
+    /// Averages the samples of a pixel. Depth and ids can't be blended, so those are taken from
+    /// the first sample.
+    pub fn average(samples: &[Aovs]) -> Aovs {
+        let n = samples.len() as f32;
+        let avg = |f: &dyn Fn(&Aovs) -> &Tup| samples.iter().map(|s| f(s) / n).sum::<Tup>();
+
+        Aovs {
+            depth: samples[0].depth,
+            normal: avg(&|s| &s.normal),
+            albedo: avg(&|s| &s.albedo),
+            object_id: samples[0].object_id,
+            material_id: samples[0].material_id,
+            direct: avg(&|s| &s.direct),
+            reflection: avg(&|s| &s.reflection),
+            refraction: avg(&|s| &s.refraction),
+        }
+    }
+}
+
+/// Floating point images of a set of passes.
+pub struct PassImages {
+    pub width: u32,
+    pub height: u32,
+    pub passes: Vec<(Pass, Vec<Tup>)>,
+}
+
+impl PassImages {
+    pub fn new(passes: &[Pass], width: u32, height: u32) -> Self {
+        let size = (width * height) as usize;
+        PassImages {
+            width,
+            height,
+            passes: passes
+                .iter()
+                .map(|pass| (*pass, vec![color(0.0, 0.0, 0.0); size]))
+                .collect(),
+        }
+    }
+
+    pub fn set(&mut self, x: u32, y: u32, aovs: &Aovs) {
+        let i = (y * self.width + x) as usize;
+        for (pass, pixels) in self.passes.iter_mut() {
+            pixels[i] = aovs.get(*pass);
+        }
+    }
+
+    /// Writes the final (linear) image and every pass as layers of a single EXR file, the
+    /// channels of the passes are named like `depth.R`.
+    pub fn write_exr(&self, beauty: &[Tup], path: &str) -> Result<(), Box<dyn Error>> {
+        let size = (self.width as usize, self.height as usize);
+
+        let mut channels: Vec<AnyChannel<FlatSamples>> = vec![];
+        let mut add = |prefix: &str, pixels: &[Tup]| {
+            for (name, channel) in [("R", 0), ("G", 1), ("B", 2)].iter() {
+                let samples = pixels
+                    .iter()
+                    .map(|p| match channel {
+                        0 => p.x,
+                        1 => p.y,
+                        _ => p.z,
+                    })
+                    .collect();
+                channels.push(AnyChannel::new(
+                    format!("{}{}", prefix, name).as_str(),
+                    FlatSamples::F32(samples),
+                ));
+            }
+        };
+
+        add("", beauty);
+        for (pass, pixels) in self.passes.iter() {
+            add(&format!("{}.", pass.name()), pixels);
+        }
+
+        let layer = Layer::new(
+            size,
+            LayerAttributes::default(),
+            Encoding::FAST_LOSSLESS,
+            AnyChannels::sort(SmallVec::from_vec(channels)),
+        );
+        Image::from_layer(layer).write().to_file(path)?;
+        Ok(())
+    }
+
+    /// Converts a pass to an 8 bit image for previewing. Depth is scaled to the farthest
+    /// surface, normals are mapped from [-1, 1] and ids get a colour each.
+    pub fn preview(&self, pass: Pass) -> Option<RgbImage> {
+        let (_, pixels) = self.passes.iter().find(|(p, _)| *p == pass)?;
+
+        let far = pixels
+            .iter()
+            .map(|p| p.x)
+            .filter(|d| d.is_finite())
+            .fold(0.0, f32::max);
+
+        let c = |c: f32| -> u8 { (c * 255.0).clamp(0.0, 255.0) as u8 };
+        Some(RgbImage::from_fn(self.width, self.height, |x, y| {
+            let p = &pixels[(y * self.width + x) as usize];
+            match pass {
+                Pass::Depth if p.x.is_finite() && far > 0.0 => {
+                    let d = c(p.x / far);
+                    Rgb([d, d, d])
+                }
+                Pass::Depth => Rgb([255, 255, 255]),
+                Pass::Normal => Rgb([c(p.x * 0.5 + 0.5), c(p.y * 0.5 + 0.5), c(p.z * 0.5 + 0.5)]),
+                Pass::ObjectId | Pass::MaterialId => id_color(p.x as u32),
+                _ => Rgb([c(p.x), c(p.y), c(p.z)]),
+            }
+        }))
+    }
+}
+
+/// A distinct colour for every id, black for zero.
+fn id_color(id: u32) -> Rgb<u8> {
+    if id == 0 {
+        return Rgb([0, 0, 0]);
+    }
+    let h = id.wrapping_mul(0x9E37_79B9);
+    Rgb([(h >> 24) as u8, (h >> 16) as u8, (h >> 8) as u8])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn averaging_samples() {
+        let mut a = Aovs::background(color(0.0, 0.0, 0.0));
+        a.depth = 2.0;
+        a.object_id = 3;
+        a.albedo = color(1.0, 0.0, 0.0);
+        let mut b = Aovs::background(color(1.0, 1.0, 1.0));
+        b.albedo = color(0.0, 0.0, 1.0);
+
+        let avg = Aovs::average(&[a, b]);
+        assert_eq!(avg.depth, 2.0);
+        assert_eq!(avg.object_id, 3);
+        assert_eq!(avg.albedo, color(0.5, 0.0, 0.5));
+        assert_eq!(avg.get(Pass::Direct), color(0.5, 0.5, 0.5));
+    }
+}
@@ -0,0 +1,137 @@
+use sha2::{Digest, Sha256};
+use std::error::Error;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Numbers the temporary files of uploads, so that concurrent uploads of the same blob never
+/// write to the same file.
+static UPLOADS: AtomicUsize = AtomicUsize::new(0);
+
+/// Content addressed store for texture and model blobs, kept on disk so that workers only need
+/// to receive each asset once. Every blob is stored in a file named after the hex encoded
+/// SHA-256 of its contents.
+#[derive(Debug, Clone)]
+pub struct AssetStore {
+    dir: PathBuf,
+}
+
+impl AssetStore {
+    pub fn new(dir: impl AsRef<Path>) -> io::Result<Self> {
+        fs::create_dir_all(dir.as_ref())?;
+        Ok(AssetStore {
+            dir: dir.as_ref().to_path_buf(),
+        })
+    }
+
+    pub fn hash(data: &[u8]) -> String {
+        format!("{:x}", Sha256::digest(data))
+    }
+
+    /// Hashes of every asset in the store.
+    pub fn hashes(&self) -> io::Result<Vec<String>> {
+        let mut hashes = vec![];
+        for entry in fs::read_dir(&self.dir)? {
+            if let Some(name) = entry?.file_name().to_str() {
+                if is_hash(name) {
+                    hashes.push(name.to_string());
+                }
+            }
+        }
+        Ok(hashes)
+    }
+
+    pub fn contains(&self, hash: &str) -> bool {
+        is_hash(hash) && self.dir.join(hash).is_file()
+    }
+
+    /// Stores the blob and returns its hash. The blob is written to a temporary file first so
+    /// that a failed upload never leaves a truncated asset behind.
+    pub fn put(&self, data: &[u8]) -> io::Result<String> {
+        let hash = Self::hash(data);
+        if !self.contains(&hash) {
+            let upload = UPLOADS.fetch_add(1, Ordering::Relaxed);
+            let partial = self
+                .dir
+                .join(format!("{}.{}-{}.partial", hash, process::id(), upload));
+            fs::write(&partial, data)?;
+            fs::rename(&partial, self.dir.join(&hash))?;
+        }
+        Ok(hash)
+    }
+
+    pub fn get(&self, hash: &str) -> Result<Vec<u8>, Box<dyn Error>> {
+        if !is_hash(hash) {
+            return Err(format!("'{}' is not a valid asset hash", hash).into());
+        }
+        match fs::read(self.dir.join(hash)) {
+            Ok(data) => Ok(data),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {
+                Err(format!("asset '{}' is not in the store", hash).into())
+            }
+            Err(err) => Err(err.into()),
+        }
+    }
+}
+
+/// Only lowercase hex SHA-256 hashes are accepted, which also keeps paths inside the store.
+fn is_hash(s: &str) -> bool {
+    s.len() == 64 && s.chars().all(|c| matches!(c, '0'..='9' | 'a'..='f'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ops::Deref;
+
+    /// Store in a temporary directory that is removed once the test is done with it.
+    struct TempStore(AssetStore);
+
+    impl Deref for TempStore {
+        type Target = AssetStore;
+
+        fn deref(&self) -> &AssetStore {
+            &self.0
+        }
+    }
+
+    impl Drop for TempStore {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0.dir);
+        }
+    }
+
+    fn store(name: &str) -> TempStore {
+        let dir = std::env::temp_dir().join(format!("tracer-{}-{}", name, process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        TempStore(AssetStore::new(dir).unwrap())
+    }
+
+    #[test]
+    fn storing_and_reading_assets() {
+        let s = store("assets");
+        let hash = s.put(b"hello").unwrap();
+
+        assert_eq!(
+            hash,
+            "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824"
+        );
+        assert!(s.contains(&hash));
+        assert_eq!(s.get(&hash).unwrap(), b"hello");
+        assert_eq!(s.hashes().unwrap(), vec![hash.clone()]);
+
+        assert_eq!(s.put(b"hello").unwrap(), hash);
+        assert_eq!(fs::read_dir(&s.dir).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn invalid_and_missing_hashes() {
+        let s = store("missing");
+
+        assert!(!s.contains("../secret"));
+        assert!(s.get("../secret").is_err());
+        assert!(s.get(&AssetStore::hash(b"missing")).is_err());
+    }
+}
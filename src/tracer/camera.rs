@@ -1,16 +1,37 @@
 use std::sync::mpsc::Sender;
 
+use super::aov::Aovs;
 use super::canvas::Pixel;
 use super::matrix::{identity, Mat};
+use super::motion::Motion;
 use super::ray::Ray;
+use super::spectrum::{weight, WAVELENGTHS};
+use super::tonemap::ToneMapping;
 
-use super::tuple::{point, Tup, color};
+use super::tuple::{color, point, vector, Tup};
 use super::world::World;
 
 use rand::seq::SliceRandom;
-use rand::thread_rng;
+use rand::{thread_rng, Rng};
 use rayon::prelude::*;
 
+/// How the camera maps pixels on the canvas to rays in the scene.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Projection {
+    Perspective,
+    /// Parallel rays, `width` is the size of the canvas in world units.
+    Orthographic {
+        width: f32,
+    },
+    /// Equidistant fisheye covering `fov` radians inside the inscribed circle.
+    Fisheye,
+    /// Full 360° latitude/longitude panorama.
+    Equirectangular,
+    /// Six 90° faces laid out in a 3x2 grid: +x, -x, +y on the top row and -y, -z, +z on the
+    /// bottom one.
+    CubeMap,
+}
+
 #[derive(Debug)]
 pub struct Camera {
     pub aspect_ratio: f32,
@@ -22,10 +43,16 @@ pub struct Camera {
     pub pixel_size: f32,
     pub antialias: u32,
     pub reflection_limit: u32,
-    pub gamma: f32,
+    pub tone_mapping: ToneMapping,
+    pub projection: Projection,
+    /// Interval during which the shutter is open, rays are spread evenly over it.
+    pub shutter: (f32, f32),
+    /// Traces every sample at its own wavelength, so that dispersive materials split light.
+    pub spectral: bool,
 
     transform: Mat,
     transform_inverse: Mat,
+    motion: Option<Motion>,
 }
 
 impl Camera {
@@ -51,7 +78,11 @@ impl Camera {
             transform_inverse: identity(),
             antialias: aa,
             reflection_limit: max_depth,
-            gamma,
+            tone_mapping: ToneMapping::gamma(gamma),
+            projection: Projection::Perspective,
+            shutter: (0.0, 0.0),
+            spectral: false,
+            motion: None,
         }
     }
 
@@ -60,54 +91,246 @@ impl Camera {
         self.transform_inverse = transform.inverse();
     }
 
-    fn ray(&self, x: f32, y: f32, xoff: f32, yoff: f32) -> Ray {
-        let x_off = (x + xoff) * self.pixel_size;
-        let y_off = (y + yoff) * self.pixel_size;
+    /// Makes the camera follow the given motion while the shutter is open, overriding the static
+    /// transform.
+    pub fn set_motion(&mut self, motion: Motion) {
+        self.motion = Some(motion);
+    }
 
-        let world_x = self.half_width - x_off;
-        let world_y = self.half_height - y_off;
+    /// Changes the projection, framing the canvas for it: by the field of view for perspective
+    /// cameras and by the width for orthographic ones.
+    pub fn set_projection(&mut self, projection: Projection) {
+        let half = match projection {
+            Projection::Orthographic { width } => width / 2.0,
+            _ => (self.fov / 2.0).tan(),
+        };
+        let (half_width, half_height) = if self.aspect_ratio >= 1.0 {
+            (half, half / self.aspect_ratio)
+        } else {
+            (half * self.aspect_ratio, half)
+        };
+        self.half_width = half_width;
+        self.half_height = half_height;
+        self.pixel_size = (half_width * 2.0) / self.h_size;
+        self.projection = projection;
+    }
 
-        let pixel = &self.transform_inverse * &point(world_x, world_y, -1.0);
-        let origin = &self.transform_inverse * &point(0.0, 0.0, 0.0);
-        let direction = (&pixel - &origin).normalize();
+    /// Returns the ray going through the given point of the canvas, or None if the projection
+    /// does not cover that point (e.g. the corners of a fisheye render).
+    fn ray(&self, x: f32, y: f32, xoff: f32, yoff: f32) -> Option<Ray> {
+        let (open, close) = self.shutter;
+        let time = if close > open {
+            thread_rng().gen_range(open, close)
+        } else {
+            open
+        };
 
-        Ray { origin, direction }
-    }
+        let (origin, direction) = match &self.projection {
+            Projection::Perspective => {
+                let x_off = (x + xoff) * self.pixel_size;
+                let y_off = (y + yoff) * self.pixel_size;
+
+                let world_x = self.half_width - x_off;
+                let world_y = self.half_height - y_off;
+
+                (point(0.0, 0.0, 0.0), vector(world_x, world_y, -1.0))
+            }
+            Projection::Orthographic { .. } => {
+                let x_off = (x + xoff) * self.pixel_size;
+                let y_off = (y + yoff) * self.pixel_size;
+
+                let world_x = self.half_width - x_off;
+                let world_y = self.half_height - y_off;
+
+                (point(world_x, world_y, 0.0), vector(0.0, 0.0, -1.0))
+            }
+            Projection::Fisheye => {
+                let radius = self.h_size.min(self.v_size) / 2.0;
+                let nx = (self.h_size / 2.0 - (x + xoff)) / radius;
+                let ny = (self.v_size / 2.0 - (y + yoff)) / radius;
+
+                let r = (nx.powi(2) + ny.powi(2)).sqrt();
+                if r > 1.0 {
+                    return None;
+                }
+
+                let theta = r * self.fov / 2.0;
+                let phi = ny.atan2(nx);
+                (
+                    point(0.0, 0.0, 0.0),
+                    vector(
+                        theta.sin() * phi.cos(),
+                        theta.sin() * phi.sin(),
+                        -theta.cos(),
+                    ),
+                )
+            }
+            Projection::Equirectangular => {
+                let u = (x + xoff) / self.h_size;
+                let v = (y + yoff) / self.v_size;
+
+                let longitude = (0.5 - u) * 2.0 * std::f32::consts::PI;
+                let latitude = (0.5 - v) * std::f32::consts::PI;
+                (
+                    point(0.0, 0.0, 0.0),
+                    vector(
+                        latitude.cos() * longitude.sin(),
+                        latitude.sin(),
+                        -latitude.cos() * longitude.cos(),
+                    ),
+                )
+            }
+            Projection::CubeMap => {
+                let face_size = (self.h_size / 3.0).min(self.v_size / 2.0);
+                let (col, row) = ((x / face_size) as u32, (y / face_size) as u32);
+                if col > 2 || row > 1 {
+                    return None;
+                }
 
-    fn gamma_correct(&self, c: Tup) -> Tup {
-        let comp = |c: f32| {
-            c.powf(1./self.gamma)
+                // Coordinates within the face, from -1 to 1, +u left and +v up, as the camera's +x
+                // is on the left of the image for the other projections too.
+                let u = 1.0 - ((x + xoff) - col as f32 * face_size) / face_size * 2.0;
+                let v = 1.0 - ((y + yoff) - row as f32 * face_size) / face_size * 2.0;
+
+                let direction = match (row, col) {
+                    (0, 0) => vector(1.0, v, u),
+                    (0, 1) => vector(-1.0, v, -u),
+                    (0, 2) => vector(u, 1.0, v),
+                    (1, 0) => vector(u, -1.0, -v),
+                    (1, 1) => vector(u, v, -1.0),
+                    _ => vector(-u, v, 1.0),
+                };
+                (point(0.0, 0.0, 0.0), direction)
+            }
+        };
+
+        let moved_inverse;
+        let transform_inverse = match &self.motion {
+            Some(motion) => {
+                moved_inverse = motion.inverse_at(time);
+                &moved_inverse
+            }
+            None => &self.transform_inverse,
         };
-        color(comp(c.x), comp(c.y), comp(c.z))
+
+        let origin = transform_inverse * &origin;
+        let direction = (transform_inverse * &direction).normalize();
+
+        Some(Ray {
+            origin,
+            direction,
+            time,
+            wavelength: None,
+        })
     }
 
-    pub fn render_pixel(&self, w: &World, x: u32, y: u32) -> Tup {
-        self.gamma_correct(match self.antialias {
-            0 | 1 => w.color_at(
-                &self.ray(x as f32, y as f32, 0.5, 0.5),
-                self.reflection_limit,
-            ),
+    /// Turns the light reaching a pixel into its display value.
+    pub fn tone_map(&self, c: Tup) -> Tup {
+        self.tone_mapping.apply(&c)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn color_at(
+        &self,
+        w: &World,
+        x: u32,
+        y: u32,
+        xoff: f32,
+        yoff: f32,
+        wavelength: Option<f32>,
+    ) -> Tup {
+        match self.ray(x as f32, y as f32, xoff, yoff) {
+            Some(mut ray) => {
+                ray.wavelength = wavelength;
+                let c = w.color_at(&ray, self.reflection_limit);
+                match wavelength {
+                    Some(wavelength) => &c * &weight(wavelength),
+                    None => c,
+                }
+            }
+            None => color(0.0, 0.0, 0.0),
+        }
+    }
+
+    /// Wavelength of the i-th of n samples of a pixel in spectral mode. Every sample gets its own
+    /// slice of the visible range and a random wavelength within it.
+    fn wavelength(&self, i: usize, n: usize) -> Option<f32> {
+        if !self.spectral {
+            return None;
+        }
+        let (low, high) = WAVELENGTHS;
+        Some(low + (high - low) * (i as f32 + thread_rng().gen::<f32>()) / n as f32)
+    }
+
+    /// Offsets within a pixel of the rays cast for it.
+    fn sample_offsets(&self) -> Vec<(f32, f32)> {
+        match self.antialias {
+            0 | 1 => vec![(0.5, 0.5)],
             aa => {
-                let mut p: Vec<Tup> = vec![];
+                let mut offsets = vec![];
                 let step = 1.0 / aa as f32;
-                let points = aa.pow(2);
 
                 for xoff in 0..aa {
                     let xoff: f32 = (xoff as f32 * step) + step / aa as f32;
                     for yoff in 0..aa {
                         let yoff: f32 = (yoff as f32 * step) + step / aa as f32;
-
-                        let color = w.color_at(
-                            &self.ray(x as f32, y as f32, xoff, yoff),
-                            self.reflection_limit,
-                        );
-                        p.push(color / points as f32);
+                        offsets.push((xoff, yoff));
                     }
                 }
 
-                p.into_iter().sum()
+                offsets
             }
-        })
+        }
+    }
+
+    pub fn render_pixel(&self, w: &World, x: u32, y: u32) -> Tup {
+        let offsets = self.sample_offsets();
+        let n = offsets.len();
+
+        self.tone_map(
+            offsets
+                .into_iter()
+                .enumerate()
+                .map(|(i, (xoff, yoff))| {
+                    self.color_at(w, x, y, xoff, yoff, self.wavelength(i, n)) / n as f32
+                })
+                .sum(),
+        )
+    }
+
+    /// Renders a pixel along with its passes. The colour is returned before tone mapping.
+    pub fn render_pixel_with_aovs(&self, w: &World, x: u32, y: u32) -> (Tup, Aovs) {
+        let offsets = self.sample_offsets();
+        let n = offsets.len();
+        let samples = offsets
+            .into_iter()
+            .enumerate()
+            .map(
+                |(i, (xoff, yoff))| match self.ray(x as f32, y as f32, xoff, yoff) {
+                    Some(mut ray) => {
+                        ray.wavelength = self.wavelength(i, n);
+                        let (c, mut aovs) = w.color_and_aovs_at(&ray, self.reflection_limit);
+                        match ray.wavelength {
+                            Some(wavelength) => {
+                                let weight = weight(wavelength);
+                                aovs.direct = &aovs.direct * &weight;
+                                aovs.reflection = &aovs.reflection * &weight;
+                                aovs.refraction = &aovs.refraction * &weight;
+                                (&c * &weight, aovs)
+                            }
+                            None => (c, aovs),
+                        }
+                    }
+                    None => (color(0.0, 0.0, 0.0), Aovs::background(color(0.0, 0.0, 0.0))),
+                },
+            )
+            .collect::<Vec<(Tup, Aovs)>>();
+
+        let points = samples.len() as f32;
+        let color = samples.iter().map(|(c, _)| c / points).sum();
+        let aovs = samples.into_iter().map(|(_, a)| a).collect::<Vec<Aovs>>();
+
+        (color, Aovs::average(&aovs))
     }
 
     pub fn render(&self, w: World, tx: Sender<Pixel>, shuffle: bool, tile_size: u32) {
@@ -151,6 +374,7 @@ impl Camera {
 
 #[cfg(test)]
 mod tests {
+    use super::super::motion::Transform;
     use super::super::transformations::{rotate_y, translation};
     use super::*;
 
@@ -169,7 +393,7 @@ mod tests {
     #[test]
     fn ray_through_center_of_canvas() {
         let c = Camera::new(201.0, 101.0, std::f32::consts::PI / 2.0, 1, 8, 1.);
-        let r = c.ray(100.0, 50.0, 0.5, 0.5);
+        let r = c.ray(100.0, 50.0, 0.5, 0.5).unwrap();
 
         assert_eq!(point(0.0, 0.0, 0.0), r.origin);
 
@@ -181,7 +405,7 @@ mod tests {
     #[test]
     fn ray_through_corner_of_canvas() {
         let c = Camera::new(201.0, 101.0, std::f32::consts::PI / 2.0, 1, 8, 1.);
-        let r = c.ray(0.0, 0.0, 0.5, 0.5);
+        let r = c.ray(0.0, 0.0, 0.5, 0.5).unwrap();
 
         assert_eq!(point(0.0, 0.0, 0.0), r.origin);
 
@@ -194,7 +418,7 @@ mod tests {
     fn ray_when_camera_is_transformed() {
         let mut c = Camera::new(201.0, 101.0, std::f32::consts::PI / 2.0, 1, 8, 1.);
         c.set_transform(&rotate_y(std::f32::consts::PI / 4.0) * &translation(0.0, -2.0, 5.0));
-        let r = c.ray(100.0, 50.0, 0.5, 0.5);
+        let r = c.ray(100.0, 50.0, 0.5, 0.5).unwrap();
 
         assert_eq!(point(0.0, 2.0, -5.0), r.origin);
 
@@ -203,4 +427,108 @@ mod tests {
         assert!(r.direction.y <= 0.001);
         assert!((r.direction.z - -p).abs() <= 0.001);
     }
+
+    #[test]
+    fn orthographic_rays_are_parallel() {
+        let mut c = Camera::new(200.0, 100.0, std::f32::consts::PI / 2.0, 1, 8, 1.);
+        c.set_projection(Projection::Orthographic { width: 10.0 });
+        assert!((c.pixel_size - 0.05).abs() < 10e-7);
+
+        let center = c.ray(100.0, 50.0, 0.0, 0.0).unwrap();
+        let corner = c.ray(0.0, 0.0, 0.0, 0.0).unwrap();
+
+        assert_eq!(center.origin, point(0.0, 0.0, 0.0));
+        assert_eq!(corner.origin, point(5.0, 2.5, 0.0));
+        assert_eq!(center.direction, vector(0.0, 0.0, -1.0));
+        assert_eq!(corner.direction, vector(0.0, 0.0, -1.0));
+
+        c.set_projection(Projection::Perspective);
+        assert!((c.pixel_size - 0.01).abs() < 10e-7);
+    }
+
+    #[test]
+    fn fisheye_rays() {
+        let mut c = Camera::new(200.0, 100.0, std::f32::consts::PI, 1, 8, 1.);
+        c.set_projection(Projection::Fisheye);
+
+        let center = c.ray(100.0, 50.0, 0.0, 0.0).unwrap();
+        assert!(center.direction.cmp_epsilon(0.0, 0.0, -1.0, 0.0));
+
+        // The edge of the circle is 90° off the view direction with a 180° fov.
+        let edge = c.ray(100.0, 0.0, 0.0, 0.0).unwrap();
+        assert!(edge.direction.x.abs() < 10e-5);
+        assert!((edge.direction.y - 1.0).abs() < 10e-5);
+        assert!(edge.direction.z.abs() < 10e-5);
+
+        assert!(c.ray(0.0, 0.0, 0.0, 0.0).is_none());
+    }
+
+    #[test]
+    fn equirectangular_rays() {
+        let mut c = Camera::new(400.0, 200.0, std::f32::consts::PI / 2.0, 1, 8, 1.);
+        c.set_projection(Projection::Equirectangular);
+
+        let forward = c.ray(200.0, 100.0, 0.0, 0.0).unwrap();
+        assert!(forward.direction.cmp_epsilon(0.0, 0.0, -1.0, 0.0));
+
+        let up = c.ray(200.0, 0.0, 0.0, 0.0).unwrap();
+        assert!((up.direction.y - 1.0).abs() < 10e-5);
+
+        let behind = c.ray(0.0, 100.0, 0.0, 0.0).unwrap();
+        assert!((behind.direction.z - 1.0).abs() < 10e-5);
+    }
+
+    #[test]
+    fn cube_map_faces() {
+        let mut c = Camera::new(300.0, 200.0, std::f32::consts::PI / 2.0, 1, 8, 1.);
+        c.set_projection(Projection::CubeMap);
+
+        let face_center = |col: f32, row: f32| {
+            c.ray(col * 100.0 + 50.0, row * 100.0 + 50.0, 0.0, 0.0)
+                .unwrap()
+                .direction
+        };
+
+        assert_eq!(face_center(0.0, 0.0), vector(1.0, 0.0, 0.0));
+        assert_eq!(face_center(1.0, 0.0), vector(-1.0, 0.0, 0.0));
+        assert_eq!(face_center(2.0, 0.0), vector(0.0, 1.0, 0.0));
+        assert_eq!(face_center(0.0, 1.0), vector(0.0, -1.0, 0.0));
+        assert_eq!(face_center(1.0, 1.0), vector(0.0, 0.0, -1.0));
+        assert_eq!(face_center(2.0, 1.0), vector(0.0, 0.0, 1.0));
+
+        // The face looking ahead matches a perspective camera of the same size, off centre too.
+        let perspective = Camera::new(100.0, 100.0, std::f32::consts::PI / 2.0, 1, 8, 1.);
+        let ahead = c.ray(125.0, 140.0, 0.5, 0.5).unwrap().direction;
+        let expected = perspective.ray(25.0, 40.0, 0.5, 0.5).unwrap().direction;
+        assert!((&ahead - &expected).magnitude() < 10e-5);
+    }
+
+    #[test]
+    fn rays_are_spread_over_the_shutter_interval() {
+        let mut c = Camera::new(201.0, 101.0, std::f32::consts::PI / 2.0, 1, 8, 1.);
+        assert_eq!(c.ray(100.0, 50.0, 0.5, 0.5).unwrap().time, 0.0);
+
+        c.shutter = (0.25, 0.75);
+        for _ in 0..100 {
+            let time = c.ray(100.0, 50.0, 0.5, 0.5).unwrap().time;
+            assert!((0.25..0.75).contains(&time));
+        }
+    }
+
+    #[test]
+    fn ray_when_camera_moves() {
+        let mut c = Camera::new(201.0, 101.0, std::f32::consts::PI / 2.0, 1, 8, 1.);
+        c.shutter = (1.0, 1.0);
+        c.set_motion(
+            Motion::new(vec![
+                (0.0, vec![Transform::Translation(0.0, 0.0, 0.0)]),
+                (2.0, vec![Transform::Translation(0.0, -4.0, 0.0)]),
+            ])
+            .unwrap(),
+        );
+        let r = c.ray(100.0, 50.0, 0.5, 0.5).unwrap();
+
+        assert_eq!(r.origin, point(0.0, 2.0, 0.0));
+        assert_eq!(r.time, 1.0);
+    }
 }
@@ -0,0 +1,63 @@
+use super::matrix::Mat;
+use super::objects::Curve;
+use super::tuple::point;
+use std::error::Error;
+use std::io::prelude::*;
+use std::io::{BufReader, Read};
+
+/// Reads curves from a text file with one curve on each line: the four control points, three
+/// numbers each, followed by the width at the root and at the tip. A single width keeps the curve
+/// the same width all along. Empty lines and lines starting with `#` are skipped.
+pub fn parse_curves(r: impl Read, transform: Mat) -> Result<Vec<Curve>, Box<dyn Error>> {
+    let reader = BufReader::new(r);
+    let mut curves = vec![];
+
+    for (number, line) in reader.lines().enumerate() {
+        let line = line?;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let values = line
+            .split_whitespace()
+            .map(|v| v.parse::<f32>())
+            .collect::<Result<Vec<f32>, _>>()?;
+        let widths = match values.len() {
+            13 => (values[12], values[12]),
+            14 => (values[12], values[13]),
+            n => {
+                return Err(format!(
+                    "line {} of the curves file has {} numbers instead of 13 or 14",
+                    number + 1,
+                    n
+                )
+                .into())
+            }
+        };
+        let control = |i: usize| point(values[i * 3], values[i * 3 + 1], values[i * 3 + 2]);
+        let points = [control(0), control(1), control(2), control(3)];
+        curves.push(Curve::new(transform.clone(), points, widths));
+    }
+
+    Ok(curves)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::matrix::identity;
+    use super::*;
+
+    #[test]
+    fn reading_curves() {
+        let file = "# grass\n\
+                    0 0 0  0 1 0  0 2 0  0 3 0  0.2 0.1\n\
+                    \n\
+                    1 0 0  1 1 0  1 2 0  1 3 0  0.1\n";
+        let curves = parse_curves(file.as_bytes(), identity()).unwrap();
+        assert_eq!(curves.len(), 2);
+
+        assert!(parse_curves("0 0 0 0 1 0 0 2 0 0.1".as_bytes(), identity()).is_err());
+        assert!(parse_curves("0 0 0 0 1 0 0 2 0 0 3 0 a".as_bytes(), identity()).is_err());
+    }
+}
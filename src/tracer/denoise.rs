@@ -0,0 +1,157 @@
+use super::aov::Aovs;
+use super::tuple::{color, dot, Tup};
+use rayon::prelude::*;
+use serde::Deserialize;
+
+/// Cross bilateral filter that smooths the noise of stochastic sampling (soft shadows, rough
+/// surfaces...) while keeping edges. Neighbouring pixels are only blended in when the first
+/// surfaces they hit look alike: similar normals, albedo and depth. It works on the linear
+/// image, before tone mapping.
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct Denoiser {
+    /// Half the size of the filter window, in pixels.
+    pub radius: u32,
+    pub sigma_spatial: f32,
+    pub sigma_color: f32,
+    pub sigma_normal: f32,
+    pub sigma_albedo: f32,
+    /// Relative to the depth of the pixel being filtered.
+    pub sigma_depth: f32,
+}
+
+impl Default for Denoiser {
+    fn default() -> Self {
+        Denoiser {
+            radius: 3,
+            sigma_spatial: 2.0,
+            sigma_color: 0.5,
+            sigma_normal: 0.3,
+            sigma_albedo: 0.1,
+            sigma_depth: 0.05,
+        }
+    }
+}
+
+impl Denoiser {
+    /// Filters an image given in rows of `width` pixels, guided by the passes of every pixel.
+    pub fn apply(&self, width: u32, image: &[Tup], guides: &[Aovs]) -> Vec<Tup> {
+        let width = width as i64;
+        let height = image.len() as i64 / width;
+        let radius = self.radius as i64;
+
+        (0..image.len())
+            .into_par_iter()
+            .map(|i| {
+                let (x, y) = (i as i64 % width, i as i64 / width);
+                let (center, guide) = (&image[i], &guides[i]);
+
+                let mut total = color(0.0, 0.0, 0.0);
+                let mut total_weight = 0.0;
+
+                for ny in (y - radius).max(0)..=(y + radius).min(height - 1) {
+                    for nx in (x - radius).max(0)..=(x + radius).min(width - 1) {
+                        let j = (ny * width + nx) as usize;
+                        let distance = ((nx - x).pow(2) + (ny - y).pow(2)) as f32;
+
+                        let weight = gaussian(distance, self.sigma_spatial)
+                            * gaussian(distance_squared(center, &image[j]), self.sigma_color)
+                            * self.guide_weight(guide, &guides[j]);
+
+                        total = total + &image[j] * weight;
+                        total_weight += weight;
+                    }
+                }
+
+                if total_weight > 0.0 {
+                    total / total_weight
+                } else {
+                    center.clone()
+                }
+            })
+            .collect()
+    }
+
+    fn guide_weight(&self, a: &Aovs, b: &Aovs) -> f32 {
+        let depth = match (a.depth.is_finite(), b.depth.is_finite()) {
+            (true, true) => {
+                let difference = (a.depth - b.depth) / a.depth.max(10e-5);
+                gaussian(difference.powi(2), self.sigma_depth)
+            }
+            (false, false) => 1.0,
+            _ => 0.0,
+        };
+
+        let normal = gaussian(
+            (1.0 - dot(&a.normal, &b.normal)).max(0.0),
+            self.sigma_normal,
+        );
+        let albedo = gaussian(distance_squared(&a.albedo, &b.albedo), self.sigma_albedo);
+
+        depth * normal * albedo
+    }
+}
+
+fn gaussian(distance_squared: f32, sigma: f32) -> f32 {
+    (-distance_squared / (2.0 * sigma * sigma)).exp()
+}
+
+fn distance_squared(a: &Tup, b: &Tup) -> f32 {
+    (a.x - b.x).powi(2) + (a.y - b.y).powi(2) + (a.z - b.z).powi(2)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tuple::vector;
+    use super::*;
+    use rand::Rng;
+
+    fn guide(albedo: Tup) -> Aovs {
+        let mut aovs = Aovs::background(color(0.0, 0.0, 0.0));
+        aovs.depth = 5.0;
+        aovs.normal = vector(0.0, 0.0, -1.0);
+        aovs.albedo = albedo;
+        aovs
+    }
+
+    #[test]
+    fn denoising_reduces_noise() {
+        let mut rng = rand::thread_rng();
+        let image = (0..32 * 32)
+            .map(|_| {
+                let v = 0.5 + rng.gen_range(-0.1, 0.1);
+                color(v, v, v)
+            })
+            .collect::<Vec<Tup>>();
+        let guides = vec![guide(color(1.0, 1.0, 1.0)); 32 * 32];
+
+        let variance = |image: &[Tup]| {
+            image.iter().map(|c| (c.x - 0.5).powi(2)).sum::<f32>() / image.len() as f32
+        };
+        let denoised = Denoiser::default().apply(32, &image, &guides);
+
+        assert!(variance(&denoised) < variance(&image) / 4.0);
+    }
+
+    #[test]
+    fn denoising_keeps_albedo_edges() {
+        // Left half red, right half blue, with matching albedo.
+        let red = color(1.0, 0.0, 0.0);
+        let blue = color(0.0, 0.0, 1.0);
+        let (image, guides): (Vec<Tup>, Vec<Aovs>) = (0..16 * 16)
+            .map(|i| {
+                let c = if i % 16 < 8 {
+                    red.clone()
+                } else {
+                    blue.clone()
+                };
+                (c.clone(), guide(c))
+            })
+            .unzip();
+
+        let denoised = Denoiser::default().apply(16, &image, &guides);
+
+        assert!(distance_squared(&denoised[7], &red) < 10e-5);
+        assert!(distance_squared(&denoised[8], &blue) < 10e-5);
+    }
+}
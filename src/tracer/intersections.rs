@@ -37,6 +37,8 @@ pub struct Computations<'a> {
     pub under_point: Tup,
     pub n1: f32,
     pub n2: f32,
+    pub time: f32,
+    pub wavelength: Option<f32>,
 }
 
 impl<'a> Computations<'a> {
@@ -77,7 +79,7 @@ impl<'a> Intersection<'a> {
         let eye = -&r.direction;
 
         let (inside, normal) = {
-            let normal = self.object.normal(&point, self.uv);
+            let normal = self.object.normal(&point, self.uv, r.time);
             if dot(&normal, &eye) < 0.0 {
                 (true, -&normal)
             } else {
@@ -90,7 +92,7 @@ impl<'a> Intersection<'a> {
         let under_point = &point - &(&normal * 10e-5);
 
         let (n1, n2) = match xs {
-            Some(xs) => self.calculate_refractions(xs),
+            Some(xs) => self.calculate_refractions(xs, r.wavelength),
             None => (1.0, 1.0),
         };
 
@@ -106,10 +108,12 @@ impl<'a> Intersection<'a> {
             inside,
             n1,
             n2,
+            time: r.time,
+            wavelength: r.wavelength,
         }
     }
 
-    fn calculate_refractions(&self, xs: &[Intersection]) -> (f32, f32) {
+    fn calculate_refractions(&self, xs: &[Intersection], wavelength: Option<f32>) -> (f32, f32) {
         let mut n1: f32 = 1.0;
         let mut n2: f32 = 1.0;
 
@@ -127,7 +131,11 @@ impl<'a> Intersection<'a> {
                 if containers.is_empty() {
                     n1 = 1.0;
                 } else {
-                    n1 = containers.last().unwrap().material.refractive_index;
+                    n1 = containers
+                        .last()
+                        .unwrap()
+                        .material
+                        .refractive_index_at(wavelength);
                 }
             }
 
@@ -141,7 +149,11 @@ impl<'a> Intersection<'a> {
                 if containers.is_empty() {
                     n2 = 1.0;
                 } else {
-                    n2 = containers.last().unwrap().material.refractive_index;
+                    n2 = containers
+                        .last()
+                        .unwrap()
+                        .material
+                        .refractive_index_at(wavelength);
                 }
                 break;
             }
@@ -168,6 +180,8 @@ mod tests {
             geometry: Geometry::Sphere(Sphere::default()),
             material: Material::new(),
             normal_map: None,
+            motion: None,
+            id: 0,
         }
     }
 
@@ -179,6 +193,8 @@ mod tests {
             geometry: Geometry::Sphere(Sphere::default()),
             material,
             normal_map: None,
+            motion: None,
+            id: 0,
         }
     }
 
@@ -250,6 +266,8 @@ mod tests {
         let r = Ray {
             origin: point(0.0, 0.0, -5.0),
             direction: vector(0.0, 0.0, 1.0),
+            time: 0.0,
+            wavelength: None,
         };
         let s = &make_sphere();
         let i = Intersection::new(4.0, s, None);
@@ -276,6 +294,8 @@ mod tests {
         let r = Ray {
             origin: point(0.0, 0.0, -5.0),
             direction: vector(0.0, 0.0, 1.0),
+            time: 0.0,
+            wavelength: None,
         };
         let s = &make_sphere();
         let i = Intersection::new(4.0, s, None);
@@ -289,6 +309,8 @@ mod tests {
         let r = Ray {
             origin: point(0.0, 0.0, 0.0),
             direction: vector(0.0, 0.0, 1.0),
+            time: 0.0,
+            wavelength: None,
         };
         let s = &make_sphere();
         let i = Intersection::new(1.0, s, None);
@@ -314,11 +336,15 @@ mod tests {
         let r = Ray {
             origin: point(0.0, 0.0, -5.0),
             direction: vector(0.0, 0.0, 1.0),
+            time: 0.0,
+            wavelength: None,
         };
         let s = &Object {
             geometry: Geometry::Sphere(Sphere::new(translation(0.0, 0.0, 1.0))),
             material: Material::new(),
             normal_map: None,
+            motion: None,
+            id: 0,
         };
         let i = Intersection::new(5.0, s, None);
         let c = i.computations(&r, None);
@@ -332,11 +358,15 @@ mod tests {
         let r = Ray {
             origin: point(0.0, 0.0, -5.0),
             direction: vector(0.0, 0.0, 1.0),
+            time: 0.0,
+            wavelength: None,
         };
         let s = &Object {
             geometry: Geometry::Sphere(Sphere::new(translation(0.0, 0.0, 1.0))),
             material: Material::new(),
             normal_map: None,
+            motion: None,
+            id: 0,
         };
         let i = Intersection::new(5.0, s, None);
         let c = i.computations(&r, None);
@@ -351,11 +381,15 @@ mod tests {
             geometry: Geometry::Plane(Plane::default()),
             material: Material::new(),
             normal_map: None,
+            motion: None,
+            id: 0,
         };
         let p = 2.0f32.sqrt() / 2.0;
         let r = Ray {
             origin: point(0.0, 1.0, -1.0),
             direction: vector(0.0, -p, p),
+            time: 0.0,
+            wavelength: None,
         };
         let i = Intersection::new(p, &plane, None);
         let c = i.computations(&r, None);
@@ -373,6 +407,8 @@ mod tests {
                 geometry: Geometry::Sphere(s),
                 material: Material::new(),
                 normal_map: None,
+                motion: None,
+                id: 0,
             };
             s.material.refractive_index = 1.5;
             s
@@ -383,6 +419,8 @@ mod tests {
                 geometry: Geometry::Sphere(s),
                 material: Material::new(),
                 normal_map: None,
+                motion: None,
+                id: 0,
             };
             s.material.refractive_index = 2.0;
             s
@@ -393,6 +431,8 @@ mod tests {
                 geometry: Geometry::Sphere(s),
                 material: Material::new(),
                 normal_map: None,
+                motion: None,
+                id: 0,
             };
             s.material.refractive_index = 2.5;
             s
@@ -401,6 +441,8 @@ mod tests {
         let r = Ray {
             origin: point(0.0, 0.0, -4.0),
             direction: vector(0.0, 0.0, 1.0),
+            time: 0.0,
+            wavelength: None,
         };
         let xs: Intersections = vec![
             Intersection::new(2.0, &a, None),
@@ -440,6 +482,8 @@ mod tests {
             geometry: Geometry::Sphere(Sphere::default()),
             material,
             normal_map: None,
+            motion: None,
+            id: 0,
         }
     }
 
@@ -450,6 +494,8 @@ mod tests {
         let ray = Ray {
             origin: point(0.0, 0.0, p),
             direction: vector(0.0, 1.0, 0.0),
+            time: 0.0,
+            wavelength: None,
         };
         let xs: Intersections = vec![
             Intersection::new(-p, &shape, None),
@@ -466,6 +512,8 @@ mod tests {
         let ray = Ray {
             origin: point(0.0, 0.0, 0.0),
             direction: vector(0.0, 1.0, 0.0),
+            time: 0.0,
+            wavelength: None,
         };
         let xs: Intersections = vec![
             Intersection::new(-1., &shape, None),
@@ -483,6 +531,8 @@ mod tests {
         let ray = Ray {
             origin: point(0.0, 0.99, -2.0),
             direction: vector(0.0, 0.0, 1.0),
+            time: 0.0,
+            wavelength: None,
         };
         let xs: Intersections = vec![Intersection::new(1.8589, &shape, None)];
         let comps = xs[0].computations(&ray, Some(&xs));
@@ -1,9 +1,12 @@
-use super::tuple::Tup;
+use super::objects::Object;
+use super::tuple::{dot, Tup};
+use rand::Rng;
 
 #[derive(Debug)]
 pub enum Light {
     Point(PointLight),
     Area(AreaLight),
+    Object(ObjectLight),
 }
 
 #[derive(Debug)]
@@ -22,11 +25,67 @@ pub struct AreaLight {
     pub uvec: Tup,
     pub usteps: u32,
     pub samples: u32,
+    pub shape: AreaShape,
+}
+
+/// Outline of an area light inside the parallelogram its u and v sizes span.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AreaShape {
+    Rect,
+    /// Ellipse touching the middle of each side, with a hole in the middle when the inner radius,
+    /// a share of the outer one, is above zero.
+    Disk {
+        inner: f32,
+    },
 }
 
 impl AreaLight {
     pub fn point_on(&self, u: u32, v: u32, off_u: f32, off_v: f32) -> Tup {
-        &self.corner + &(&self.uvec * (u as f32 * off_u)) + (&self.vvec * (v as f32 * off_v))
+        let (u, v) = (u as f32 * off_u, v as f32 * off_v);
+        match self.shape {
+            AreaShape::Rect => &self.corner + &(&self.uvec * u) + (&self.vvec * v),
+            AreaShape::Disk { inner } => {
+                // Cells of the grid become rings and sectors of the same area.
+                let (s, t) = (
+                    (u / self.usteps as f32).clamp(0.0, 1.0),
+                    v / self.vsteps as f32,
+                );
+                let r = (inner * inner + s * (1.0 - inner * inner)).sqrt();
+                let angle = 2.0 * std::f32::consts::PI * t;
+                let u_radius = &self.uvec * (self.usteps as f32 / 2.0);
+                let v_radius = &self.vvec * (self.vsteps as f32 / 2.0);
+                &self.position + &(&u_radius * (r * angle.cos())) + (&v_radius * (r * angle.sin()))
+            }
+        }
+    }
+}
+
+/// Light given off by an emissive object, shining from random points on its surface. The
+/// position is the centre of the object.
+#[derive(Debug)]
+pub struct ObjectLight {
+    pub position: Tup,
+    pub color: Tup,
+    /// Shapes making up the object, all the triangles of a model.
+    pub parts: Vec<Object>,
+    pub samples: u32,
+}
+
+impl ObjectLight {
+    /// Random point just off the part of the surface that faces `towards`, so that the object
+    /// doesn't shadow its own light. Points facing away would always be hidden by the object
+    /// itself. After a few tries any point will do, for parts that face away altogether.
+    pub fn point_on(&self, towards: &Tup) -> Tup {
+        let mut rng = rand::thread_rng();
+        let mut tries = 0;
+        loop {
+            let part = &self.parts[rng.gen_range(0, self.parts.len())];
+            let (p, normal) = part.sample_surface();
+            tries += 1;
+            if dot(&(towards - &p), &normal) > 0.0 || tries == 16 {
+                return &p + &(normal * 10e-4);
+            }
+        }
     }
 }
 
@@ -45,6 +104,7 @@ impl Light {
         u_steps: u32,
         v_size: Tup,
         v_steps: u32,
+        shape: AreaShape,
     ) -> Self {
         let corner = &position - &(&u_size / 2.0 + &v_size / 2.0);
         Light::Area(AreaLight {
@@ -56,13 +116,42 @@ impl Light {
             vsteps: v_steps,
             usteps: u_steps,
             samples: v_steps * u_steps,
+            shape,
         })
     }
 
+    /// Random point the light shines on `towards` from, one of its cells for area lights and the
+    /// side facing it for objects.
+    pub fn sample_point(&self, towards: &Tup) -> Tup {
+        match self {
+            Light::Point(light) => light.position.clone(),
+            Light::Area(light) => {
+                let mut rng = rand::thread_rng();
+                light.point_on(
+                    1,
+                    1,
+                    rng.gen::<f32>() * light.usteps as f32,
+                    rng.gen::<f32>() * light.vsteps as f32,
+                )
+            }
+            Light::Object(light) => light.point_on(towards),
+        }
+    }
+
+    /// Number of points to sample on the light to tell how much of it reaches a point.
+    pub fn samples(&self) -> u32 {
+        match self {
+            Light::Point(_) => 1,
+            Light::Area(light) => light.samples.max(1),
+            Light::Object(light) => light.samples.max(1),
+        }
+    }
+
     pub fn color(&self) -> &Tup {
         match &self {
             Light::Point(light) => &light.color,
             Light::Area(light) => &light.color,
+            Light::Object(light) => &light.color,
         }
     }
 
@@ -70,6 +159,7 @@ impl Light {
         match &self {
             Light::Point(light) => &light.position,
             Light::Area(light) => &light.position,
+            Light::Object(light) => &light.position,
         }
     }
 }
@@ -0,0 +1,261 @@
+use super::light::Light;
+use super::tuple::{dot, point, vector, Tup};
+use rand::Rng;
+
+/// Bounding volume hierarchy over the lights of a scene, used to pick a few of them at random at
+/// every hit instead of casting a shadow ray towards each one. Lights are picked in proportion to
+/// an estimate of what they give the point: their power over the squared distance to their
+/// bounds, or nothing when all of them are below the surface. Lights here don't fade with
+/// distance, but the ones nearby are the likelier to reach the point and so are favoured.
+#[derive(Debug)]
+pub struct LightTree {
+    nodes: Vec<Node>,
+    /// Combined colour of all the lights.
+    color: Tup,
+    /// Lights picked, and shadow rays cast, at every hit.
+    pub shadow_rays: u32,
+}
+
+#[derive(Debug)]
+struct Node {
+    min: Tup,
+    max: Tup,
+    power: f32,
+    kind: Kind,
+}
+
+#[derive(Debug)]
+enum Kind {
+    /// Index of the light in the world.
+    Leaf(usize),
+    /// Indices of the children in the tree.
+    Inner(usize, usize),
+}
+
+impl LightTree {
+    pub fn new(lights: &[Light], shadow_rays: u32) -> Self {
+        let mut tree = LightTree {
+            nodes: vec![],
+            color: lights.iter().map(|l| l.color().clone()).sum(),
+            shadow_rays,
+        };
+        if !lights.is_empty() {
+            let mut indices = (0..lights.len()).collect::<Vec<usize>>();
+            tree.build(lights, &mut indices);
+        }
+        tree
+    }
+
+    pub fn color(&self) -> &Tup {
+        &self.color
+    }
+
+    /// Adds the subtree over the given lights, splitting them at the median along the longest
+    /// side of their bounds, and returns the index of its root.
+    fn build(&mut self, lights: &[Light], indices: &mut [usize]) -> usize {
+        let positions = indices
+            .iter()
+            .flat_map(|i| extent(&lights[*i]))
+            .collect::<Vec<Tup>>();
+        let min = positions.iter().fold(
+            point(f32::INFINITY, f32::INFINITY, f32::INFINITY),
+            |m, p| point(m.x.min(p.x), m.y.min(p.y), m.z.min(p.z)),
+        );
+        let max = positions.iter().fold(
+            point(f32::NEG_INFINITY, f32::NEG_INFINITY, f32::NEG_INFINITY),
+            |m, p| point(m.x.max(p.x), m.y.max(p.y), m.z.max(p.z)),
+        );
+        let power = indices.iter().map(|i| power(&lights[*i])).sum();
+
+        let kind = if indices.len() == 1 {
+            Kind::Leaf(indices[0])
+        } else {
+            let extent = &max - &min;
+            let axis = |p: &Tup| {
+                if extent.x >= extent.y && extent.x >= extent.z {
+                    p.x
+                } else if extent.y >= extent.z {
+                    p.y
+                } else {
+                    p.z
+                }
+            };
+            indices.sort_by(|a, b| {
+                axis(lights[*a].position())
+                    .partial_cmp(&axis(lights[*b].position()))
+                    .unwrap_or(std::cmp::Ordering::Less)
+            });
+
+            let (left, right) = indices.split_at_mut(indices.len() / 2);
+            Kind::Inner(self.build(lights, left), self.build(lights, right))
+        };
+
+        self.nodes.push(Node {
+            min,
+            max,
+            power,
+            kind,
+        });
+        self.nodes.len() - 1
+    }
+
+    /// Picks a light for a point on a surface with the given normal, or for a point lit from all
+    /// around when there is no normal, like one on a fibre. Returns its index and the probability
+    /// it was picked with, or nothing when no light can reach the point.
+    pub fn sample(
+        &self,
+        p: &Tup,
+        normal: Option<&Tup>,
+        rng: &mut impl Rng,
+    ) -> Option<(usize, f32)> {
+        let mut node = self.nodes.last()?;
+        let mut probability = 1.0;
+        if node.importance(p, normal) <= 0.0 {
+            return None;
+        }
+
+        loop {
+            match node.kind {
+                Kind::Leaf(light) => return Some((light, probability)),
+                Kind::Inner(left, right) => {
+                    let (left, right) = (&self.nodes[left], &self.nodes[right]);
+                    let (a, b) = (left.importance(p, normal), right.importance(p, normal));
+                    if a + b <= 0.0 {
+                        return None;
+                    }
+
+                    if rng.gen::<f32>() * (a + b) < a {
+                        probability *= a / (a + b);
+                        node = left;
+                    } else {
+                        probability *= b / (a + b);
+                        node = right;
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl Node {
+    /// Power of the lights in the node over the squared distance to its bounds, taken as at least
+    /// half their diagonal so points among the lights don't blow it up, or zero when they are all
+    /// below the surface.
+    fn importance(&self, p: &Tup, normal: Option<&Tup>) -> f32 {
+        let outside = |p: f32, min: f32, max: f32| (min - p).max(p - max).max(0.0);
+        let d = vector(
+            outside(p.x, self.min.x, self.max.x),
+            outside(p.y, self.min.y, self.max.y),
+            outside(p.z, self.min.z, self.max.z),
+        );
+        let r = (&self.max - &self.min).magnitude() / 2.0;
+        let power = self.power / dot(&d, &d).max(r * r).max(10e-4);
+
+        let normal = match normal {
+            Some(normal) => normal,
+            None => return power,
+        };
+        let above = (0..8).any(|i| {
+            let corner = point(
+                if i & 1 == 0 { self.min.x } else { self.max.x },
+                if i & 2 == 0 { self.min.y } else { self.max.y },
+                if i & 4 == 0 { self.min.z } else { self.max.z },
+            );
+            dot(&(&corner - p), normal) > 0.0
+        });
+
+        if above {
+            power
+        } else {
+            0.0
+        }
+    }
+}
+
+/// Points the bounds of a light have to include.
+fn extent(light: &Light) -> Vec<Tup> {
+    match light {
+        Light::Point(light) => vec![light.position.clone()],
+        Light::Area(light) => {
+            let u = &light.uvec * light.usteps as f32;
+            let v = &light.vvec * light.vsteps as f32;
+            vec![
+                light.corner.clone(),
+                &light.corner + &u,
+                &light.corner + &v,
+                &(&light.corner + &u) + &v,
+            ]
+        }
+        Light::Object(light) => light
+            .parts
+            .iter()
+            .filter_map(|part| part.bounds())
+            .flat_map(|(min, max)| vec![min, max])
+            .chain(std::iter::once(light.position.clone()))
+            .collect(),
+    }
+}
+
+/// Brightness of a light, the average of its channels.
+fn power(light: &Light) -> f32 {
+    let c = light.color();
+    ((c.x + c.y + c.z) / 3.0).max(0.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tuple::color;
+    use super::*;
+
+    #[test]
+    fn lights_are_picked_by_power() {
+        let lights = vec![
+            Light::new_point(point(-5.0, 5.0, 0.0), color(1.0, 1.0, 1.0)),
+            Light::new_point(point(0.0, 5.0, -5.0), color(3.0, 3.0, 3.0)),
+            Light::new_point(point(4.0, -5.0, 0.0), color(100.0, 100.0, 100.0)),
+            Light::new_point(point(5.0, -5.0, 0.0), color(100.0, 100.0, 100.0)),
+        ];
+        let tree = LightTree::new(&lights, 1);
+        assert_eq!(tree.color(), &color(204.0, 204.0, 204.0));
+
+        let mut rng = rand::thread_rng();
+        let (p, up) = (point(0.0, 0.0, 0.0), vector(0.0, 1.0, 0.0));
+        let mut picked = [0; 4];
+        for _ in 0..4000 {
+            let (light, probability) = tree.sample(&p, Some(&up), &mut rng).unwrap();
+            picked[light] += 1;
+            assert!((probability - [0.25, 0.75][light]).abs() < 10e-5);
+        }
+
+        // The lights below the surface are never picked.
+        assert!(picked[1] > 2 * picked[0]);
+
+        assert!(tree
+            .sample(&point(0.0, 10.0, 0.0), Some(&up), &mut rng)
+            .is_none());
+
+        // Without a normal every light can be picked.
+        assert!(tree
+            .sample(&point(0.0, 10.0, 0.0), None, &mut rng)
+            .is_some());
+    }
+
+    #[test]
+    fn nearer_lights_are_picked_more_often() {
+        let lights = vec![
+            Light::new_point(point(0.0, 1.0, 0.0), color(1.0, 1.0, 1.0)),
+            Light::new_point(point(0.0, 10.0, 0.0), color(1.0, 1.0, 1.0)),
+        ];
+        let tree = LightTree::new(&lights, 1);
+
+        let mut rng = rand::thread_rng();
+        let (p, up) = (point(0.0, 0.0, 0.0), vector(0.0, 1.0, 0.0));
+        let mut picked = [0; 2];
+        for _ in 0..1000 {
+            let (light, probability) = tree.sample(&p, Some(&up), &mut rng).unwrap();
+            picked[light] += 1;
+            assert!((probability - [100.0 / 101.0, 1.0 / 101.0][light]).abs() < 10e-5);
+        }
+        assert!(picked[0] > 10 * picked[1]);
+    }
+}
@@ -1,7 +1,10 @@
 use super::light::*;
+use super::medium::Medium;
 use super::objects::Object;
 use super::patterns::Pattern;
-use super::tuple::{color, dot, Tup};
+use super::spectrum::{Dispersion, REFERENCE_WAVELENGTH};
+use super::tuple::{basis, color, dot, Tup};
+use rand::Rng;
 
 #[derive(Debug, Clone)]
 pub struct Material {
@@ -11,10 +14,53 @@ pub struct Material {
     pub specular: f32,
     pub shininess: f32,
     pub reflectiveness: f32,
+    /// Spreads reflections and refractions, from 0 for a mirror or clear glass to 1.
+    pub roughness: f32,
     pub pattern: Option<Pattern>,
     pub transparency: f32,
     pub refractive_index: f32,
+    /// Makes the refractive index depend on the wavelength, replacing `refractive_index`.
+    pub dispersion: Option<Dispersion>,
+    /// Colour light takes on after travelling `absorption_distance` inside the object, white for
+    /// clear materials.
+    pub absorption: Tup,
+    pub absorption_distance: f32,
     pub light_through: bool,
+    /// Light scattering under the surface, which replaces the diffuse term of the lighting.
+    pub subsurface: Option<Subsurface>,
+    /// Fills the object with a medium, the object itself is then only its boundary and is not
+    /// shaded.
+    pub medium: Option<Medium>,
+    /// Light given off by the surface, added to the shading whether or not it is lit.
+    pub emission: Option<Emission>,
+    /// Shades the surface as fibres running along the object's tangent, for hair and fur on
+    /// curves.
+    pub hair: bool,
+    /// Identifies the material in the scene, zero when it was not given one.
+    pub id: u32,
+}
+
+/// Translucent material like wax, skin or marble, rendered with random walks under the surface.
+#[derive(Debug, Clone)]
+pub struct Subsurface {
+    /// Share of the light kept every time it scatters inside the object, per channel.
+    pub color: Tup,
+    /// Average distance light travels inside the object before scattering.
+    pub mean_free_path: f32,
+    /// Random walks averaged at every hit.
+    pub samples: u32,
+}
+
+/// Light given off by a glowing surface.
+#[derive(Debug, Clone)]
+pub struct Emission {
+    pub color: Tup,
+    pub strength: f32,
+    /// Varies the emitted colour over the surface, replacing `color`.
+    pub pattern: Option<Pattern>,
+    /// Also lights up the objects around it, from `samples` random points on its surface.
+    pub light: bool,
+    pub samples: u32,
 }
 
 impl Material {
@@ -26,13 +72,90 @@ impl Material {
             specular: 0.9,
             shininess: 200.0,
             reflectiveness: 0.0,
+            roughness: 0.0,
             pattern: None,
             transparency: 0.0,
             refractive_index: 1.0,
+            dispersion: None,
+            absorption: color(1.0, 1.0, 1.0),
+            absorption_distance: 1.0,
             light_through: false,
+            subsurface: None,
+            medium: None,
+            emission: None,
+            hair: false,
+            id: 0,
+        }
+    }
+
+    /// Colour of the surface at the given point, before any lighting.
+    pub fn color_at(&self, o: &Object, p: &Tup, time: f32) -> Tup {
+        match &self.pattern {
+            Some(c) => c.at_object(o, p, time),
+            None => self.color.clone(), // TODO: no need to clone this...
         }
     }
 
+    /// Light given off at the given point, black for materials that don't glow.
+    pub fn emission_at(&self, o: &Object, p: &Tup, time: f32) -> Tup {
+        match &self.emission {
+            Some(emission) => {
+                let c = match &emission.pattern {
+                    Some(pattern) => pattern.at_object(o, p, time),
+                    None => emission.color.clone(),
+                };
+                c * emission.strength
+            }
+            None => color(0.0, 0.0, 0.0),
+        }
+    }
+
+    /// Refractive index for light of the given wavelength, in nanometres. Dispersive materials
+    /// use their index at the reference wavelength when rendering in RGB.
+    pub fn refractive_index_at(&self, wavelength: Option<f32>) -> f32 {
+        match &self.dispersion {
+            Some(dispersion) => {
+                dispersion.refractive_index(wavelength.unwrap_or(REFERENCE_WAVELENGTH))
+            }
+            None => self.refractive_index,
+        }
+    }
+
+    /// Normal of a microfacet drawn from the GGX distribution around the surface normal, which is
+    /// the normal itself for smooth materials.
+    pub fn microfacet_normal(&self, normal: &Tup) -> Tup {
+        if self.roughness <= 0.0 {
+            return normal.clone();
+        }
+
+        let mut rng = rand::thread_rng();
+        let alpha = self.roughness * self.roughness;
+        let u: f32 = rng.gen();
+        let theta = (alpha * (u / (1.0 - u)).sqrt()).atan();
+        let phi = 2.0 * std::f32::consts::PI * rng.gen::<f32>();
+
+        let (tangent, bitangent) = basis(normal);
+        (&(&(&tangent * (theta.sin() * phi.cos())) + &(&bitangent * (theta.sin() * phi.sin())))
+            + &(normal * theta.cos()))
+            .normalize()
+    }
+
+    /// Share of the light left in each channel after travelling the distance inside the object
+    /// (Beer-Lambert law).
+    pub fn absorb(&self, distance: f32) -> Tup {
+        let d = distance / self.absorption_distance;
+        color(
+            self.absorption.x.powf(d),
+            self.absorption.y.powf(d),
+            self.absorption.z.powf(d),
+        )
+    }
+
+    /// Phong lighting of the point. `visible` holds, for each light, points on it along with the
+    /// share of their light that reaches the point, which is shaded from each of them in turn.
+    /// `ambient_occlusion` is the share of the ambient light that reaches it, 1 when nothing is
+    /// in the way.
+    #[allow(clippy::too_many_arguments)]
     pub fn lighting(
         &self,
         o: &Object,
@@ -40,47 +163,102 @@ impl Material {
         p: Tup,
         eye: Tup,
         normal: Tup,
-        shadow_color: Tup,
+        visible: &[Vec<(Tup, Tup)>],
+        ambient_occlusion: f32,
+        time: f32,
     ) -> Tup {
-        let object_color = match &self.pattern {
-            Some(c) => c.at_object(o, &p),
-            None => self.color.clone(), // TODO: no need to clone this...
-        };
+        let object_color = self.color_at(o, &p, time);
+        let tangent = if self.hair { o.tangent(&p, time) } else { None };
 
         l.iter()
-            .map(|l| {
-                let effective_color = &object_color * l.color();
-                let ambient = &effective_color * self.ambient;
-
-                let light = (l.position() - &p).normalize();
-                let light_normal_dot = dot(&light, &normal);
-
-                let (diffuse, specular) = if light_normal_dot < 0.0 {
-                    let diffuse = color(0.0, 0.0, 0.0);
-                    let specular = color(0.0, 0.0, 0.0);
-                    (diffuse, specular)
-                } else {
-                    let diffuse = &effective_color * (self.diffuse * light_normal_dot);
-                    let reflect = &(-&light).reflect(&normal);
-                    let reflect_dot_eye = dot(&reflect, &eye);
-
-                    if reflect_dot_eye <= 0.0 {
-                        let specular = color(0.0, 0.0, 0.0);
-                        (diffuse, specular)
-                    } else {
-                        let factor = reflect_dot_eye.powf(self.shininess);
-                        let specular = l.color() * (self.specular * factor);
-                        (diffuse, specular)
-                    }
-                };
-
-                let diffuse = &diffuse * &shadow_color;
-                let specular = &specular * &shadow_color;
-
-                ambient + diffuse + specular
+            .zip(visible)
+            .map(|(l, samples)| {
+                let ambient = &(&object_color * l.color()) * (self.ambient * ambient_occlusion);
+                let direct = samples
+                    .iter()
+                    .map(|(from, seen)| {
+                        let direct = self.direct_lighting(
+                            &object_color,
+                            l,
+                            from,
+                            &p,
+                            &eye,
+                            &normal,
+                            tangent.as_ref(),
+                        );
+                        &direct * seen
+                    })
+                    .sum::<Tup>();
+                ambient + direct / samples.len().max(1) as f32
             })
             .sum()
     }
+
+    /// Diffuse and specular light a single light gives the point, shining from `from` on the
+    /// light, not counting shadows. With a tangent the point is lit as a thin fibre along it
+    /// rather than a surface.
+    #[allow(clippy::too_many_arguments)]
+    pub fn direct_lighting(
+        &self,
+        object_color: &Tup,
+        l: &Light,
+        from: &Tup,
+        p: &Tup,
+        eye: &Tup,
+        normal: &Tup,
+        tangent: Option<&Tup>,
+    ) -> Tup {
+        let effective_color = object_color * l.color();
+
+        let light = (from - p).normalize();
+        if let Some(tangent) = tangent {
+            return self.fibre_lighting(&effective_color, l, &light, eye, tangent);
+        }
+        let light_normal_dot = dot(&light, normal);
+
+        let (diffuse, specular) = if light_normal_dot < 0.0 {
+            let diffuse = color(0.0, 0.0, 0.0);
+            let specular = color(0.0, 0.0, 0.0);
+            (diffuse, specular)
+        } else {
+            let diffuse = match self.subsurface {
+                Some(_) => color(0.0, 0.0, 0.0),
+                None => &effective_color * (self.diffuse * light_normal_dot),
+            };
+            let reflect = &(-&light).reflect(normal);
+            let reflect_dot_eye = dot(reflect, eye);
+
+            if reflect_dot_eye <= 0.0 {
+                let specular = color(0.0, 0.0, 0.0);
+                (diffuse, specular)
+            } else {
+                let factor = reflect_dot_eye.powf(self.shininess);
+                let specular = l.color() * (self.specular * factor);
+                (diffuse, specular)
+            }
+        };
+
+        diffuse + specular
+    }
+
+    /// Kajiya-Kay shading, where the fibre reflects light in a cone around its tangent.
+    fn fibre_lighting(
+        &self,
+        effective_color: &Tup,
+        l: &Light,
+        light: &Tup,
+        eye: &Tup,
+        tangent: &Tup,
+    ) -> Tup {
+        let (tangent_light, tangent_eye) = (dot(tangent, light), dot(tangent, eye));
+        let sin_light = (1.0 - tangent_light * tangent_light).max(0.0).sqrt();
+        let sin_eye = (1.0 - tangent_eye * tangent_eye).max(0.0).sqrt();
+
+        let diffuse = effective_color * (self.diffuse * sin_light);
+        let highlight = (sin_light * sin_eye - tangent_light * tangent_eye).max(0.0);
+        let specular = l.color() * (self.specular * highlight.powf(self.shininess));
+        diffuse + specular
+    }
 }
 
 impl Default for Material {
@@ -99,7 +277,7 @@ mod tests {
     use super::super::material::Material;
     use super::super::objects::{Geometry, Object, Sphere};
     use super::super::patterns::Pattern;
-    use super::super::tuple::{color, point, vector};
+    use super::super::tuple::{color, dot, point, vector};
 
     #[test]
     fn eye_between_light_and_surface() {
@@ -114,12 +292,16 @@ mod tests {
                 geometry: Geometry::Sphere(Sphere::default()),
                 material: Material::new(),
                 normal_map: None,
+                motion: None,
+                id: 0,
             },
             &vec![light],
             pos,
             eyev,
             normalv,
-            color(1., 1., 1.),
+            &[vec![(point(0.0, 0.0, -10.0), color(1., 1., 1.))]],
+            1.0,
+            0.0,
         );
 
         assert_eq!((1.9 - result.x).abs() <= std::f32::EPSILON, true);
@@ -141,12 +323,16 @@ mod tests {
                 geometry: Geometry::Sphere(Sphere::default()),
                 material: Material::new(),
                 normal_map: None,
+                motion: None,
+                id: 0,
             },
             &vec![light],
             pos,
             eyev,
             normalv,
-            color(1., 1., 1.),
+            &[vec![(point(0.0, 0.0, -10.0), color(1., 1., 1.))]],
+            1.0,
+            0.0,
         );
 
         assert_eq!((1.0 - result.x).abs() <= std::f32::EPSILON, true);
@@ -169,12 +355,16 @@ mod tests {
                 geometry: Geometry::Sphere(Sphere::default()),
                 material: Material::new(),
                 normal_map: None,
+                motion: None,
+                id: 0,
             },
             &vec![light],
             pos,
             eyev,
             normalv,
-            color(1., 1., 1.),
+            &[vec![(point(0.0, 10.0, -10.0), color(1., 1., 1.))]],
+            1.0,
+            0.0,
         );
 
         let r = 0.1 + p * 0.9;
@@ -198,12 +388,16 @@ mod tests {
                 geometry: Geometry::Sphere(Sphere::default()),
                 material: Material::new(),
                 normal_map: None,
+                motion: None,
+                id: 0,
             },
             &vec![light],
             pos,
             eyev,
             normalv,
-            color(1., 1., 1.),
+            &[vec![(point(0.0, 10.0, -10.0), color(1., 1., 1.))]],
+            1.0,
+            0.0,
         );
 
         let r = 0.1 + 0.9 * p + 0.9;
@@ -225,12 +419,16 @@ mod tests {
                 geometry: Geometry::Sphere(Sphere::default()),
                 material: Material::new(),
                 normal_map: None,
+                motion: None,
+                id: 0,
             },
             &vec![light],
             pos,
             eyev,
             normalv,
-            color(1., 1., 1.),
+            &[vec![(point(0.0, 0.0, 10.0), color(1., 1., 1.))]],
+            1.0,
+            0.0,
         );
 
         assert_eq!((0.1 - result.x).abs() <= std::f32::EPSILON, true);
@@ -251,12 +449,16 @@ mod tests {
                 geometry: Geometry::Sphere(Sphere::default()),
                 material: Material::new(),
                 normal_map: None,
+                motion: None,
+                id: 0,
             },
             &vec![light],
             pos,
             eyev,
             normalv,
-            color(0., 0., 0.),
+            &[vec![(point(0.0, 0.0, -10.0), color(0., 0., 0.))]],
+            1.0,
+            0.0,
         );
 
         assert_eq!((0.1 - result.x).abs() <= std::f32::EPSILON, true);
@@ -285,24 +487,32 @@ mod tests {
                 geometry: Geometry::Sphere(Sphere::default()),
                 material: Material::new(),
                 normal_map: None,
+                motion: None,
+                id: 0,
             },
             light,
             point(0.9, 0.0, 0.0),
             eyev.clone(),
             normalv.clone(),
-            color(1., 1., 1.),
+            &[vec![(point(0.0, 0.0, -10.0), color(1., 1., 1.))]],
+            1.0,
+            0.0,
         );
         let c2 = mat.lighting(
             &Object {
                 geometry: Geometry::Sphere(Sphere::default()),
                 material: Material::new(),
                 normal_map: None,
+                motion: None,
+                id: 0,
             },
             light,
             point(1.0, 0.0, 0.0),
             eyev,
             normalv,
-            color(1., 1., 1.),
+            &[vec![(point(0.0, 0.0, -10.0), color(1., 1., 1.))]],
+            1.0,
+            0.0,
         );
 
         assert_eq!((1.0 - c2.x).abs() <= std::f32::EPSILON, false);
@@ -313,4 +523,82 @@ mod tests {
         assert_eq!((0.0 - c1.y).abs() <= std::f32::EPSILON, false);
         assert_eq!((0.0 - c1.z).abs() <= std::f32::EPSILON, false);
     }
+
+    #[test]
+    fn absorption_over_distance() {
+        let mut mat = Material::new();
+        assert_eq!(mat.absorb(10.0), color(1.0, 1.0, 1.0));
+
+        mat.absorption = color(1.0, 0.5, 0.25);
+        mat.absorption_distance = 2.0;
+        assert_eq!(mat.absorb(2.0), color(1.0, 0.5, 0.25));
+        assert_eq!(mat.absorb(4.0), color(1.0, 0.25, 0.0625));
+    }
+
+    #[test]
+    fn microfacet_normals_spread_with_roughness() {
+        let normal = vector(0.0, 1.0, 0.0);
+        let mut mat = Material::new();
+        assert_eq!(mat.microfacet_normal(&normal), normal);
+
+        let spread = |mat: &Material| {
+            (0..1000)
+                .map(|_| {
+                    let m = mat.microfacet_normal(&normal);
+                    assert!((m.magnitude() - 1.0).abs() < 10e-5);
+                    assert!(dot(&m, &normal) > 0.0);
+                    1.0 - dot(&m, &normal)
+                })
+                .sum::<f32>()
+        };
+        mat.roughness = 0.2;
+        let satin = spread(&mat);
+        mat.roughness = 0.8;
+        assert!(spread(&mat) > satin);
+    }
+
+    #[test]
+    fn fibres_light_around_their_tangent() {
+        let mat = Material::new();
+        let light = Light::new_point(point(0.0, 0.0, -10.0), color(1.0, 1.0, 1.0));
+        let (p, white) = (point(0.0, 0.0, 0.0), color(1.0, 1.0, 1.0));
+        let normal = vector(0.0, 0.0, -1.0);
+
+        // Across the light the fibre is lit fully, even from behind its normal.
+        let across = vector(1.0, 0.0, 0.0);
+        let eye = vector(0.0, 0.0, -1.0);
+        let lit = mat.direct_lighting(
+            &white,
+            &light,
+            light.position(),
+            &p,
+            &eye,
+            &normal,
+            Some(&across),
+        );
+        assert!(lit.cmp_epsilon(1.8, 1.8, 1.8, 0.0));
+        let behind = mat.direct_lighting(
+            &white,
+            &light,
+            light.position(),
+            &p,
+            &eye,
+            &-&normal,
+            Some(&across),
+        );
+        assert_eq!(behind, lit);
+
+        // Pointing at the light it gets none.
+        let towards = vector(0.0, 0.0, -1.0);
+        let dark = mat.direct_lighting(
+            &white,
+            &light,
+            light.position(),
+            &p,
+            &eye,
+            &normal,
+            Some(&towards),
+        );
+        assert!(dark.cmp_epsilon(0.0, 0.0, 0.0, 0.0));
+    }
 }
@@ -0,0 +1,104 @@
+use super::tuple::Tup;
+
+/// Homogeneous participating medium, like smoke or murky water, that fills the inside of an
+/// object. Light travelling through it is scattered out of the way and lights shining into it
+/// are scattered towards the camera.
+#[derive(Debug, Clone)]
+pub struct Medium {
+    /// Fraction of the light that is scattered or absorbed per unit of distance.
+    pub density: f32,
+    /// Share of the light that is scattered rather than absorbed, per channel.
+    pub color: Tup,
+    /// Henyey-Greenstein asymmetry, from -1 (back scattering) to 1 (forward scattering).
+    pub anisotropy: f32,
+    /// Samples taken along every ray that goes through the medium.
+    pub steps: u32,
+}
+
+impl Medium {
+    /// Henyey-Greenstein phase function, scaled so that isotropic media give 1. `cos` is the
+    /// cosine of the angle between the direction of the ray and the direction to the light.
+    pub fn phase(&self, cos: f32) -> f32 {
+        let g = self.anisotropy.clamp(-0.99, 0.99);
+        (1.0 - g * g) / (1.0 + g * g - 2.0 * g * cos).powf(1.5)
+    }
+}
+
+/// Medium filling the whole scene. Its density falls off exponentially above the given height,
+/// which makes for ground fog and atmospheric haze.
+#[derive(Debug, Clone)]
+pub struct Fog {
+    pub medium: Medium,
+    pub height: f32,
+    /// How fast the density falls off above the height, zero for the same density everywhere.
+    pub falloff: f32,
+    /// Rays that hit nothing only go through this much fog before reaching the background.
+    pub distance: f32,
+}
+
+impl Fog {
+    pub fn density_at(&self, p: &Tup) -> f32 {
+        self.medium.density * (-self.falloff * (p.y - self.height).max(0.0)).exp()
+    }
+
+    /// Integral of the density along a ray between two of its points, exact when the density
+    /// is the same everywhere.
+    pub fn optical_depth(&self, from: &Tup, to: &Tup) -> f32 {
+        let distance = (to - from).magnitude();
+        if self.falloff == 0.0 {
+            return self.medium.density * distance;
+        }
+
+        let steps = 8;
+        (0..steps)
+            .map(|i| {
+                let s = (i as f32 + 0.5) / steps as f32;
+                self.density_at(&(from + &(&(to - from) * s)))
+            })
+            .sum::<f32>()
+            * distance
+            / steps as f32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tuple::{color, point};
+    use super::*;
+
+    fn medium(anisotropy: f32) -> Medium {
+        Medium {
+            density: 0.5,
+            color: color(1.0, 1.0, 1.0),
+            anisotropy,
+            steps: 8,
+        }
+    }
+
+    #[test]
+    fn phase_functions() {
+        assert!((medium(0.0).phase(0.3) - 1.0).abs() < 10e-5);
+
+        let forward = medium(0.6);
+        assert!(forward.phase(1.0) > forward.phase(0.0));
+        assert!(forward.phase(0.0) > forward.phase(-1.0));
+    }
+
+    #[test]
+    fn fog_thins_out_above_its_height() {
+        let fog = Fog {
+            medium: medium(0.0),
+            height: 1.0,
+            falloff: 1.0,
+            distance: 100.0,
+        };
+
+        assert_eq!(fog.density_at(&point(0.0, -5.0, 0.0)), 0.5);
+        assert!((fog.density_at(&point(0.0, 2.0, 0.0)) - 0.5 / 1f32.exp()).abs() < 10e-5);
+
+        let low = fog.optical_depth(&point(0.0, 0.0, 0.0), &point(4.0, 0.0, 0.0));
+        let high = fog.optical_depth(&point(0.0, 3.0, 0.0), &point(4.0, 3.0, 0.0));
+        assert!((low - 2.0).abs() < 10e-5);
+        assert!(high < low);
+    }
+}
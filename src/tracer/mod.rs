@@ -1,14 +1,28 @@
+pub mod animation;
+pub mod aov;
+pub mod asset_store;
 pub mod camera;
 pub mod canvas;
+pub mod curve_parser;
+pub mod denoise;
 pub mod intersections;
 pub mod light;
+pub mod light_tree;
 pub mod material;
 pub mod matrix;
+pub mod medium;
+pub mod motion;
 pub mod obj_parser;
 pub mod objects;
+pub mod occlusion;
 pub mod patterns;
+pub mod photon_map;
+pub mod postprocess;
 pub mod ray;
 pub mod scene_parser;
+pub mod sdf;
+pub mod spectrum;
+pub mod tonemap;
 pub mod transformations;
 pub mod tuple;
 pub mod world;
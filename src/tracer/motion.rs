@@ -0,0 +1,260 @@
+use super::matrix::{identity, Mat};
+use super::transformations::*;
+use super::tuple::Tup;
+
+/// A single transformation with its parameters kept around, so that it can be interpolated
+/// between keyframes.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Transform {
+    Translation(f32, f32, f32),
+    Scaling(f32, f32, f32),
+    RotateX(f32),
+    RotateY(f32),
+    RotateZ(f32),
+    Shearing(f32, f32, f32, f32, f32, f32),
+    View(Tup, Tup, Tup),
+}
+
+impl Transform {
+    pub fn matrix(&self) -> Mat {
+        match self {
+            Transform::Translation(x, y, z) => translation(*x, *y, *z),
+            Transform::Scaling(x, y, z) => scaling(*x, *y, *z),
+            Transform::RotateX(rad) => rotate_x(*rad),
+            Transform::RotateY(rad) => rotate_y(*rad),
+            Transform::RotateZ(rad) => rotate_z(*rad),
+            Transform::Shearing(xy, xz, yx, yz, zx, zy) => shearing(*xy, *xz, *yx, *yz, *zx, *zy),
+            Transform::View(from, to, up) => view(from.clone(), to.clone(), up.clone()),
+        }
+    }
+
+    /// Inverse of the matrix, built from the undone transformation where there is one rather than
+    /// by inverting the matrix.
+    pub fn inverse_matrix(&self) -> Mat {
+        match self {
+            Transform::Translation(x, y, z) => translation(-*x, -*y, -*z),
+            Transform::Scaling(x, y, z) => scaling(1.0 / *x, 1.0 / *y, 1.0 / *z),
+            Transform::RotateX(rad) => rotate_x(-*rad),
+            Transform::RotateY(rad) => rotate_y(-*rad),
+            Transform::RotateZ(rad) => rotate_z(-*rad),
+            Transform::Shearing(..) | Transform::View(..) => self.matrix().inverse(),
+        }
+    }
+
+    /// Interpolates the parameters of two transformations of the same kind, returns None if the
+    /// kinds differ.
+    pub fn lerp(&self, other: &Transform, t: f32) -> Option<Transform> {
+        let l = |a: f32, b: f32| a + (b - a) * t;
+        let lt = |a: &Tup, b: &Tup| a + &((b - a) * t);
+
+        match (self, other) {
+            (Transform::Translation(x1, y1, z1), Transform::Translation(x2, y2, z2)) => Some(
+                Transform::Translation(l(*x1, *x2), l(*y1, *y2), l(*z1, *z2)),
+            ),
+            (Transform::Scaling(x1, y1, z1), Transform::Scaling(x2, y2, z2)) => {
+                Some(Transform::Scaling(l(*x1, *x2), l(*y1, *y2), l(*z1, *z2)))
+            }
+            (Transform::RotateX(a), Transform::RotateX(b)) => Some(Transform::RotateX(l(*a, *b))),
+            (Transform::RotateY(a), Transform::RotateY(b)) => Some(Transform::RotateY(l(*a, *b))),
+            (Transform::RotateZ(a), Transform::RotateZ(b)) => Some(Transform::RotateZ(l(*a, *b))),
+            (
+                Transform::Shearing(xy1, xz1, yx1, yz1, zx1, zy1),
+                Transform::Shearing(xy2, xz2, yx2, yz2, zx2, zy2),
+            ) => Some(Transform::Shearing(
+                l(*xy1, *xy2),
+                l(*xz1, *xz2),
+                l(*yx1, *yx2),
+                l(*yz1, *yz2),
+                l(*zx1, *zx2),
+                l(*zy1, *zy2),
+            )),
+            (Transform::View(from1, to1, up1), Transform::View(from2, to2, up2)) => Some(
+                Transform::View(lt(from1, from2), lt(to1, to2), lt(up1, up2)),
+            ),
+            _ => None,
+        }
+    }
+}
+
+/// Combines a list of transformations into a single matrix, applied in the same order as the
+/// scene file lists them.
+pub fn combine(transforms: &[Transform]) -> Mat {
+    transforms
+        .iter()
+        .fold(identity(), |m, transform| m * transform.matrix())
+}
+
+/// Inverse of `combine`, undoing the transformations in reverse order.
+pub fn combine_inverse(transforms: &[Transform]) -> Mat {
+    transforms
+        .iter()
+        .rev()
+        .fold(identity(), |m, transform| m * transform.inverse_matrix())
+}
+
+/// A transformation that changes over time, defined by keyframes. Keyframes are interpolated
+/// linearly, parameter by parameter, so every keyframe must be made of the same kinds of
+/// transformations in the same order.
+#[derive(Debug, Clone)]
+pub struct Motion {
+    keys: Vec<(f32, Vec<Transform>)>,
+}
+
+impl Motion {
+    pub fn new(mut keys: Vec<(f32, Vec<Transform>)>) -> Result<Self, String> {
+        if keys.is_empty() {
+            return Err("motion needs at least one keyframe".to_string());
+        }
+
+        if let Some((time, _)) = keys.iter().find(|(time, _)| !time.is_finite()) {
+            return Err(format!("keyframe time {} is not a finite number", time));
+        }
+        keys.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+        let (first_time, first) = &keys[0];
+        for (time, transforms) in keys.iter().skip(1) {
+            let compatible = transforms.len() == first.len()
+                && transforms
+                    .iter()
+                    .zip(first.iter())
+                    .all(|(a, b)| a.lerp(b, 0.0).is_some());
+            if !compatible {
+                return Err(format!(
+                    "keyframe at time {} does not have the same transformations as the one at time {}",
+                    time, first_time
+                ));
+            }
+        }
+
+        Ok(Motion { keys })
+    }
+
+    /// The transformation matrix at the given time. Times outside the keyframes are clamped to
+    /// the first or last keyframe.
+    pub fn at(&self, time: f32) -> Mat {
+        combine(&self.transforms_at(time))
+    }
+
+    /// Inverse of the transformation matrix at the given time, without inverting a matrix.
+    pub fn inverse_at(&self, time: f32) -> Mat {
+        combine_inverse(&self.transforms_at(time))
+    }
+
+    fn transforms_at(&self, time: f32) -> Vec<Transform> {
+        let last = self.keys.len() - 1;
+
+        if time <= self.keys[0].0 {
+            return self.keys[0].1.clone();
+        }
+        if time >= self.keys[last].0 {
+            return self.keys[last].1.clone();
+        }
+
+        let next = self
+            .keys
+            .iter()
+            .position(|(t, _)| *t > time)
+            .unwrap_or(last);
+        let (t0, a) = &self.keys[next - 1];
+        let (t1, b) = &self.keys[next];
+        let factor = (time - t0) / (t1 - t0);
+
+        a.iter()
+            .zip(b.iter())
+            .filter_map(|(a, b)| a.lerp(b, factor))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tuple::{point, vector};
+    use super::*;
+
+    #[test]
+    fn combine_matches_matrix_multiplication() {
+        let transforms = vec![
+            Transform::Translation(1.0, 2.0, 3.0),
+            Transform::RotateY(1.0),
+            Transform::Scaling(2.0, 2.0, 2.0),
+        ];
+        assert_eq!(
+            combine(&transforms),
+            translation(1.0, 2.0, 3.0) * rotate_y(1.0) * scaling(2.0, 2.0, 2.0)
+        );
+    }
+
+    #[test]
+    fn motion_interpolates_between_keyframes() {
+        let motion = Motion::new(vec![
+            (0.0, vec![Transform::Translation(0.0, 0.0, 0.0)]),
+            (1.0, vec![Transform::Translation(2.0, 0.0, 0.0)]),
+            (2.0, vec![Transform::Translation(2.0, 4.0, 0.0)]),
+        ])
+        .unwrap();
+
+        let p = point(0.0, 0.0, 0.0);
+        assert_eq!(&motion.at(-1.0) * &p, point(0.0, 0.0, 0.0));
+        assert_eq!(&motion.at(0.5) * &p, point(1.0, 0.0, 0.0));
+        assert_eq!(&motion.at(1.5) * &p, point(2.0, 2.0, 0.0));
+        assert_eq!(&motion.at(3.0) * &p, point(2.0, 4.0, 0.0));
+    }
+
+    #[test]
+    fn motion_interpolates_rotation_angles() {
+        let motion = Motion::new(vec![
+            (0.0, vec![Transform::RotateY(0.0)]),
+            (1.0, vec![Transform::RotateY(std::f32::consts::PI)]),
+        ])
+        .unwrap();
+
+        let v = &motion.at(0.5) * &vector(0.0, 0.0, 1.0);
+        assert!((v.x - 1.0).abs() < 10e-5);
+        assert!(v.z.abs() < 10e-5);
+    }
+
+    #[test]
+    fn motion_inverse_undoes_the_motion() {
+        let motion = Motion::new(vec![
+            (
+                0.0,
+                vec![
+                    Transform::Translation(1.0, 0.0, 0.0),
+                    Transform::RotateY(0.0),
+                    Transform::Scaling(1.0, 1.0, 1.0),
+                ],
+            ),
+            (
+                1.0,
+                vec![
+                    Transform::Translation(1.0, 2.0, 3.0),
+                    Transform::RotateY(2.0),
+                    Transform::Scaling(2.0, 0.5, 4.0),
+                ],
+            ),
+        ])
+        .unwrap();
+
+        for &time in &[-1.0, 0.3, 0.7, 2.0] {
+            let p = point(1.0, -2.0, 3.0);
+            let back = &motion.inverse_at(time) * &(&motion.at(time) * &p);
+            assert!((&back - &p).magnitude() < 10e-5);
+        }
+    }
+
+    #[test]
+    fn motion_keyframes_must_match() {
+        assert!(Motion::new(vec![]).is_err());
+        assert!(Motion::new(vec![(f32::NAN, vec![Transform::RotateY(0.0)])]).is_err());
+        assert!(Motion::new(vec![
+            (0.0, vec![Transform::RotateY(0.0)]),
+            (1.0, vec![Transform::RotateX(1.0)]),
+        ])
+        .is_err());
+        assert!(Motion::new(vec![
+            (0.0, vec![Transform::RotateY(0.0)]),
+            (1.0, vec![Transform::RotateY(0.0), Transform::RotateX(1.0)]),
+        ])
+        .is_err());
+    }
+}
@@ -1,135 +1,633 @@
 use super::matrix::Mat;
 use super::objects::Tri;
-use super::tuple::{point, vector, Tup};
+use super::tuple::{cross, dot, point, vector, Tup};
+use std::collections::{HashMap, HashSet};
 use std::error::Error;
 use std::io::prelude::*;
 use std::io::{BufReader, Read};
 
-enum Face {
-    Face {
-        // Face indices
-        x: usize,
-        y: usize,
-        z: usize,
-    },
-    FaceWithNormal {
-        // Face indices
-        x: usize,
-        y: usize,
-        z: usize,
-        // Normal indices
-        x_n: usize,
-        y_n: usize,
-        z_n: usize,
-    },
+/// Corner of a face, with the indices of its vertex, texture coordinates and normal counting from
+/// zero.
+#[derive(Debug, Clone)]
+struct Corner {
+    vertex: usize,
+    uv: Option<usize>,
+    normal: Option<usize>,
 }
 
+/// The contents of an OBJ file, before it is turned into triangles. Kept around so that a model
+/// can be placed in a scene several times without reading it again.
+#[derive(Debug)]
+pub struct Mesh {
+    vertices: Vec<Tup>,
+    uvs: Vec<(f32, f32)>,
+    normals: Vec<Tup>,
+    /// Polygons with their corners in order, anticlockwise seen from the outside.
+    faces: Vec<Vec<Corner>>,
+}
+
+/// Subdivision levels a mesh can be smoothed by, as every level makes four times as many faces.
+pub const MAX_SUBDIVISION_LEVELS: u32 = 6;
+
 pub fn parse_obj(r: impl Read, transform: Mat, smooth: bool) -> Result<Vec<Tri>, Box<dyn Error>> {
-    let reader = BufReader::new(r);
-
-    let mut vertices: Vec<Tup> = Vec::new();
-    let mut normals: Vec<Tup> = Vec::new();
-    let mut faces: Vec<Face> = Vec::new();
-
-    for line in reader.lines().into_iter() {
-        let line: String = line?;
-        let split = line.trim().split(' ').collect::<Vec<&str>>();
-        match split.as_slice() {
-            &["v", x, y, z] => {
-                vertices.push(point(x.parse()?, y.parse()?, z.parse()?));
-            }
-            &["vn", x, y, z] => {
-                normals.push(vector(x.parse()?, y.parse()?, z.parse()?));
-            }
-            &["f", a, b, c] => {
-                let spl_a = a.trim().split('/').collect::<Vec<&str>>();
-                let spl_b = b.trim().split('/').collect::<Vec<&str>>();
-                let spl_c = c.trim().split('/').collect::<Vec<&str>>();
-
-                match (smooth, spl_a.as_slice(), spl_b.as_slice(), spl_c.as_slice()) {
-                    // All have vertex, texture and normal indices
-                    (true, &[avi, ati, ani], &[bvi, bti, bni], &[cvi, cti, cni]) => {
-                        faces.push(Face::FaceWithNormal {
-                            x: avi.parse()?,
-                            y: bvi.parse()?,
-                            z: cvi.parse()?,
-                            x_n: ani.parse()?,
-                            y_n: bni.parse()?,
-                            z_n: cni.parse()?,
-                        });
-                    }
+    Ok(Mesh::read(r)?.tris(transform, smooth))
+}
+
+/// Index in a face, which counts from one, as an index counting from zero.
+fn index(i: &str) -> Result<usize, Box<dyn Error>> {
+    match i.parse::<usize>()?.checked_sub(1) {
+        Some(i) => Ok(i),
+        None => Err("indices in OBJ faces start at 1".into()),
+    }
+}
 
-                    // With all data, but ignore it for flat shading
-                    (false, &[avi, ati, ani], &[bvi, bti, bni], &[cvi, cti, cni]) => {
-                        faces.push(Face::Face {
-                            x: avi.parse()?,
-                            y: bvi.parse()?,
-                            z: cvi.parse()?,
+impl Mesh {
+    pub fn read(r: impl Read) -> Result<Mesh, Box<dyn Error>> {
+        let reader = BufReader::new(r);
+
+        let mut vertices: Vec<Tup> = Vec::new();
+        let mut uvs: Vec<(f32, f32)> = Vec::new();
+        let mut normals: Vec<Tup> = Vec::new();
+        let mut faces: Vec<Vec<Corner>> = Vec::new();
+
+        for line in reader.lines().into_iter() {
+            let line: String = line?;
+            let split = line.trim().split(' ').collect::<Vec<&str>>();
+            match split.as_slice() {
+                &["v", x, y, z] => {
+                    vertices.push(point(x.parse()?, y.parse()?, z.parse()?));
+                }
+                &["vt", u, v, ..] => {
+                    uvs.push((u.parse()?, v.parse()?));
+                }
+                &["vn", x, y, z] => {
+                    normals.push(vector(x.parse()?, y.parse()?, z.parse()?));
+                }
+                ["f", corners @ ..] if corners.len() >= 3 => {
+                    let mut face = vec![];
+                    for corner in corners.iter() {
+                        let indices = corner.trim().split('/').collect::<Vec<&str>>();
+                        let (vertex, uv, normal) = match indices.as_slice() {
+                            &[v] => (v, "", ""),
+                            &[v, t] => (v, t, ""),
+                            &[v, t, n] => (v, t, n),
+                            // Who knows what else could be here!
+                            _ => continue,
+                        };
+                        let optional = |i: &str| match i {
+                            "" => Ok(None),
+                            i => index(i).map(Some),
+                        };
+                        face.push(Corner {
+                            vertex: index(vertex)?,
+                            uv: optional(uv)?,
+                            normal: optional(normal)?,
                         });
                     }
+                    faces.push(face);
+                }
+                _ => (),
+            }
+        }
+
+        Ok(Mesh {
+            vertices,
+            uvs,
+            normals,
+            faces,
+        })
+    }
+
+    /// Turns the mesh into triangles, fanning out from the first corner of every polygon. Vertex
+    /// normals are only used for smooth shading, and texture coordinates are kept for triangles
+    /// that have them at every corner.
+    pub fn tris(&self, transform: Mat, smooth: bool) -> Vec<Tri> {
+        self.faces
+            .iter()
+            .flat_map(|face| (1..face.len().saturating_sub(1)).map(move |k| (face, k)))
+            .filter_map(|(face, k)| {
+                let (a, b, c) = (&face[0], &face[k], &face[k + 1]);
+                let normal = |corner: &Corner| self.normals.get(corner.normal?).cloned();
+                let normals = match (normal(a), normal(b), normal(c)) {
+                    (Some(a), Some(b), Some(c)) if smooth => Some((a, b, c)),
+                    _ => None,
+                };
+                let uv = |corner: &Corner| self.uvs.get(corner.uv?).cloned();
+                let uvs = match (uv(a), uv(b), uv(c)) {
+                    (Some(a), Some(b), Some(c)) => Some([a, b, c]),
+                    _ => None,
+                };
+
+                Some(Tri::new(
+                    transform.clone(),
+                    self.vertices.get(a.vertex)?.clone(),
+                    self.vertices.get(b.vertex)?.clone(),
+                    self.vertices.get(c.vertex)?.clone(),
+                    normals,
+                    uvs,
+                ))
+            })
+            .collect()
+    }
 
-                    // Only vertex indices
-                    (_, &[avi], &[bvi], &[cvi]) => {
-                        faces.push(Face::Face {
-                            x: avi.parse()?,
-                            y: bvi.parse()?,
-                            z: cvi.parse()?,
+    /// Vertex and texture coordinate indices of the faces, counting from zero, leaving out faces
+    /// with missing vertices. Texture coordinates are only kept for triangles that have them at
+    /// every corner.
+    fn textured_triangles(&self) -> Vec<([usize; 3], Option<[usize; 3]>)> {
+        let uv = |corner: &Corner| corner.uv.filter(|uv| *uv < self.uvs.len());
+        self.faces
+            .iter()
+            .filter(|face| face.iter().all(|c| c.vertex < self.vertices.len()))
+            .flat_map(|face| {
+                (1..face.len().saturating_sub(1)).map(move |k| (&face[0], &face[k], &face[k + 1]))
+            })
+            .map(|(a, b, c)| {
+                let uvs = match (uv(a), uv(b), uv(c)) {
+                    (Some(a), Some(b), Some(c)) => Some([a, b, c]),
+                    _ => None,
+                };
+                ([a.vertex, b.vertex, c.vertex], uvs)
+            })
+            .collect()
+    }
+
+    /// Copy of the mesh with its triangles split until no edge is longer than `edge`, and every
+    /// vertex then moved along the normal there by the height given for its position. Normals
+    /// are averaged over the faces around each vertex, so that faces sharing a vertex can't tear
+    /// apart, and worked out again after the move for smooth shading. Texture coordinates are
+    /// split along with the edges.
+    pub fn displaced(&self, edge: f32, height: impl Fn(&Tup) -> f32) -> Mesh {
+        let mut vertices = self.vertices.clone();
+        let mut uvs = self.uvs.clone();
+        let textured = self.textured_triangles();
+        let mut triangles = textured.iter().map(|(t, _)| *t).collect::<Vec<_>>();
+        let mut triangle_uvs = textured.iter().map(|(_, uv)| *uv).collect::<Vec<_>>();
+        let mut normals = vertex_normals(&vertices, &triangles);
+
+        // Edges are split at their midpoints, shared by the faces on both sides, a few times at
+        // most in one go: faces with one or two long edges are cut in two or three and the long
+        // edges of those split in the next round.
+        for _ in 0..32 {
+            let mut midpoints: HashMap<(usize, usize), usize> = HashMap::new();
+            let mut uv_midpoints: HashMap<(usize, usize), usize> = HashMap::new();
+            let (mut split, mut split_uvs) = (vec![], vec![]);
+            for (face, face_uvs) in triangles.iter().zip(triangle_uvs.iter()) {
+                let (mut corners, mut corner_uvs, mut cuts) = (vec![], vec![], vec![]);
+                for k in 0..3 {
+                    let (a, b) = (face[k], face[(k + 1) % 3]);
+                    corners.push(a);
+                    cuts.push(false);
+                    if let Some(face_uvs) = face_uvs {
+                        corner_uvs.push(face_uvs[k]);
+                    }
+                    if (&vertices[b] - &vertices[a]).magnitude() > edge {
+                        let key = (a.min(b), a.max(b));
+                        let m = *midpoints.entry(key).or_insert_with(|| {
+                            vertices.push(point(
+                                (vertices[a].x + vertices[b].x) / 2.,
+                                (vertices[a].y + vertices[b].y) / 2.,
+                                (vertices[a].z + vertices[b].z) / 2.,
+                            ));
+                            normals.push((&normals[a] + &normals[b]).normalize());
+                            vertices.len() - 1
                         });
+                        corners.push(m);
+                        cuts.push(true);
+                        if let Some(face_uvs) = face_uvs {
+                            let (a, b) = (face_uvs[k], face_uvs[(k + 1) % 3]);
+                            let key = (a.min(b), a.max(b));
+                            corner_uvs.push(*uv_midpoints.entry(key).or_insert_with(|| {
+                                uvs.push(((uvs[a].0 + uvs[b].0) / 2., (uvs[a].1 + uvs[b].1) / 2.));
+                                uvs.len() - 1
+                            }));
+                        }
                     }
-
-                    // Who knows what else could be here!
-                    _ => (),
+                }
+                for [a, b, c] in cut(&cuts) {
+                    split.push([corners[a], corners[b], corners[c]]);
+                    split_uvs.push(face_uvs.map(|_| [corner_uvs[a], corner_uvs[b], corner_uvs[c]]));
                 }
             }
-            _ => (),
+            triangles = split;
+            triangle_uvs = split_uvs;
+            if midpoints.is_empty() {
+                break;
+            }
+        }
+
+        for (v, n) in vertices.iter_mut().zip(normals.iter()) {
+            *v = &*v + &(n * height(v));
+        }
+        let normals = vertex_normals(&vertices, &triangles);
+
+        Mesh {
+            vertices,
+            uvs,
+            normals,
+            faces: triangles
+                .iter()
+                .zip(triangle_uvs)
+                .map(|(face, face_uvs)| {
+                    (0..3)
+                        .map(|k| Corner {
+                            vertex: face[k],
+                            uv: face_uvs.map(|uvs| uvs[k]),
+                            normal: Some(face[k]),
+                        })
+                        .collect()
+                })
+                .collect(),
         }
     }
 
-    Ok(faces
-        .into_iter()
-        .filter_map(|face| match face {
-            Face::Face { x, y, z } => {
-                match (
-                    vertices.get(x - 1),
-                    vertices.get(y - 1),
-                    vertices.get(z - 1),
-                ) {
-                    (Some(x), Some(y), Some(z)) => Some(Tri::new(
-                        transform.clone(),
-                        x.clone(),
-                        y.clone(),
-                        z.clone(),
-                        None,
-                    )),
-                    _ => None,
+    /// Copy of the mesh smoothed by Catmull-Clark subdivision, every level splitting each face
+    /// into one quad per corner. Boundaries are kept sharp, and so are creases: edges where faces
+    /// meet at more than `crease_angle` degrees. Texture coordinates are carried over as they
+    /// were laid out on the faces, and normals are worked out again for smooth shading, apart on
+    /// either side of a crease.
+    pub fn subdivided(&self, levels: u32, crease_angle: Option<f32>) -> Mesh {
+        let mut vertices = self.vertices.clone();
+        let mut uvs = self.uvs.clone();
+        let mut faces = self
+            .faces
+            .iter()
+            .filter(|face| face.len() >= 3 && face.iter().all(|c| c.vertex < self.vertices.len()))
+            .map(|face| {
+                let uvs = face.iter().map(|c| c.uv.filter(|uv| *uv < self.uvs.len()));
+                let uvs = uvs.collect::<Option<Vec<usize>>>();
+                face.iter()
+                    .enumerate()
+                    .map(|(k, c)| (c.vertex, uvs.as_ref().map(|uvs| uvs[k])))
+                    .collect::<Vec<(usize, Option<usize>)>>()
+            })
+            .collect::<Vec<_>>();
+
+        let polygons = |faces: &[Vec<(usize, Option<usize>)>]| {
+            faces
+                .iter()
+                .map(|face| face.iter().map(|c| c.0).collect())
+                .collect::<Vec<Vec<usize>>>()
+        };
+        let mut creases = match crease_angle {
+            Some(angle) => sharp_edges(&vertices, &polygons(&faces), angle),
+            None => HashSet::new(),
+        };
+
+        for _ in 0..levels {
+            let face_points = faces
+                .iter()
+                .map(|face| average(face.iter().map(|c| &vertices[c.0])))
+                .collect::<Vec<Tup>>();
+
+            let mut edges: HashMap<(usize, usize), Vec<usize>> = HashMap::new();
+            let mut vertex_faces = vec![vec![]; vertices.len()];
+            for (i, face) in faces.iter().enumerate() {
+                for k in 0..face.len() {
+                    let (a, b) = (face[k].0, face[(k + 1) % face.len()].0);
+                    edges.entry(edge(a, b)).or_default().push(i);
+                    vertex_faces[a].push(i);
                 }
             }
-            Face::FaceWithNormal {
-                x,
-                y,
-                z,
-                x_n,
-                y_n,
-                z_n,
-            } => {
-                match (
-                    vertices.get(x - 1),
-                    vertices.get(y - 1),
-                    vertices.get(z - 1),
-                    normals.get(x_n - 1),
-                    normals.get(y_n - 1),
-                    normals.get(z_n - 1),
-                ) {
-                    (Some(x), Some(y), Some(z), Some(x_n), Some(y_n), Some(z_n)) => Some(Tri::new(
-                        transform.clone(),
-                        x.clone(),
-                        y.clone(),
-                        z.clone(),
-                        Some((x_n.clone(), y_n.clone(), z_n.clone())),
-                    )),
-                    _ => None,
+            let sharp = |e: &(usize, usize)| edges[e].len() != 2 || creases.contains(e);
+
+            // Edge points go after the vertices and face points.
+            let mut edge_points = HashMap::new();
+            let mut points = vec![];
+            for (e, around) in edges.iter() {
+                let ends = [&vertices[e.0], &vertices[e.1]];
+                let p = if sharp(e) {
+                    average(ends.iter().cloned())
+                } else {
+                    let centres = [&face_points[around[0]], &face_points[around[1]]];
+                    average(ends.iter().chain(centres.iter()).cloned())
+                };
+                edge_points.insert(*e, vertices.len() + faces.len() + points.len());
+                points.push(p);
+            }
+
+            let mut vertex_edges = vec![vec![]; vertices.len()];
+            for e in edges.keys() {
+                vertex_edges[e.0].push(*e);
+                vertex_edges[e.1].push(*e);
+            }
+            let moved = (0..vertices.len())
+                .map(|v| {
+                    let p = &vertices[v];
+                    let sharp_ends = vertex_edges[v]
+                        .iter()
+                        .filter(|e| sharp(e))
+                        .map(|e| &vertices[if e.0 == v { e.1 } else { e.0 }])
+                        .collect::<Vec<&Tup>>();
+                    match sharp_ends.len() {
+                        // Loose vertices, and corners of the boundary, stay where they are.
+                        _ if vertex_faces[v].len() <= 1 => p.clone(),
+                        0 | 1 => {
+                            let n = vertex_edges[v].len() as f32;
+                            let f = average(vertex_faces[v].iter().map(|i| &face_points[*i]));
+                            let midpoints = vertex_edges[v]
+                                .iter()
+                                .map(|e| average([&vertices[e.0], &vertices[e.1]].iter().cloned()))
+                                .collect::<Vec<Tup>>();
+                            let r = average(midpoints.iter());
+                            let sum = &(&f + &(&r * 2.)) + &(p * (n - 3.));
+                            point(sum.x / n, sum.y / n, sum.z / n)
+                        }
+                        2 => {
+                            let sum = &(&(p * 6.) + sharp_ends[0]) + sharp_ends[1];
+                            point(sum.x / 8., sum.y / 8., sum.z / 8.)
+                        }
+                        _ => p.clone(),
+                    }
+                })
+                .collect::<Vec<Tup>>();
+
+            let mut edge_uvs = HashMap::new();
+            let mut next = vec![];
+            for (i, face) in faces.iter().enumerate() {
+                let n = face.len();
+                let centre_uv = face
+                    .iter()
+                    .map(|c| c.1)
+                    .collect::<Option<Vec<usize>>>()
+                    .map(|f| {
+                        let count = f.len() as f32;
+                        let (u, v) = f
+                            .iter()
+                            .fold((0., 0.), |(u, v), i| (u + uvs[*i].0, v + uvs[*i].1));
+                        uvs.push((u / count, v / count));
+                        uvs.len() - 1
+                    });
+                let mut edge_uv = |a: Option<usize>, b: Option<usize>| {
+                    let (a, b) = (a?, b?);
+                    Some(*edge_uvs.entry(edge(a, b)).or_insert_with(|| {
+                        uvs.push(((uvs[a].0 + uvs[b].0) / 2., (uvs[a].1 + uvs[b].1) / 2.));
+                        uvs.len() - 1
+                    }))
+                };
+                for k in 0..n {
+                    let (prev, this, after) =
+                        (&face[(k + n - 1) % n], &face[k], &face[(k + 1) % n]);
+                    next.push(vec![
+                        (this.0, this.1),
+                        (
+                            edge_points[&edge(this.0, after.0)],
+                            edge_uv(this.1, after.1),
+                        ),
+                        (vertices.len() + i, centre_uv),
+                        (edge_points[&edge(prev.0, this.0)], edge_uv(prev.1, this.1)),
+                    ]);
                 }
             }
+
+            creases = creases
+                .iter()
+                .flat_map(|e| vec![edge(e.0, edge_points[e]), edge(edge_points[e], e.1)])
+                .collect();
+            vertices = moved.into_iter().chain(face_points).chain(points).collect();
+            faces = next;
+        }
+
+        let (normals, corner_normals) = match crease_angle {
+            Some(angle) => corner_normals(&vertices, &polygons(&faces), angle),
+            None => {
+                let normals = vertex_normals(&vertices, &polygons(&faces));
+                let corners = polygons(&faces);
+                (normals, corners)
+            }
+        };
+
+        Mesh {
+            vertices,
+            uvs,
+            normals,
+            faces: faces
+                .iter()
+                .zip(corner_normals)
+                .map(|(face, normals)| {
+                    face.iter()
+                        .zip(normals)
+                        .map(|(c, normal)| Corner {
+                            vertex: c.0,
+                            uv: c.1,
+                            normal: Some(normal),
+                        })
+                        .collect()
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Triangles covering a face with some of its edges split, as positions in the list of its
+/// corners and the midpoints of the split edges in order around it. `cuts` tells which of those
+/// are midpoints.
+fn cut(cuts: &[bool]) -> Vec<[usize; 3]> {
+    match cuts.len() {
+        3 => vec![[0, 1, 2]],
+        6 => vec![[0, 1, 5], [1, 2, 3], [3, 4, 5], [1, 3, 5]],
+        n => {
+            // Fan out from a midpoint, which never lines up with two neighbouring corners.
+            let start = cuts.iter().position(|c| *c).unwrap_or(0);
+            let at = |k: usize| (start + k) % n;
+            (1..n - 1).map(|k| [at(0), at(k), at(k + 1)]).collect()
+        }
+    }
+}
+
+/// Key of the edge between two vertices, whichever way round it is walked.
+fn edge(a: usize, b: usize) -> (usize, usize) {
+    (a.min(b), a.max(b))
+}
+
+fn average<'a>(points: impl Iterator<Item = &'a Tup>) -> Tup {
+    let (sum, count) = points.fold((vector(0., 0., 0.), 0.), |(sum, n), p| (&sum + p, n + 1.));
+    point(sum.x / count, sum.y / count, sum.z / count)
+}
+
+/// Normal of a polygon, as long as twice its area.
+fn face_normal(vertices: &[Tup], face: &[usize]) -> Tup {
+    (1..face.len().saturating_sub(1)).fold(vector(0., 0., 0.), |n, k| {
+        let (a, b, c) = (
+            &vertices[face[0]],
+            &vertices[face[k]],
+            &vertices[face[k + 1]],
+        );
+        let n = &n + &cross(&(b - a), &(c - a));
+        vector(n.x, n.y, n.z)
+    })
+}
+
+/// Edges where the faces on either side meet at more than the angle, in degrees.
+fn sharp_edges(vertices: &[Tup], faces: &[Vec<usize>], angle: f32) -> HashSet<(usize, usize)> {
+    let normals = faces
+        .iter()
+        .map(|f| face_normal(vertices, f).normalize())
+        .collect::<Vec<Tup>>();
+    let mut edges: HashMap<(usize, usize), Vec<usize>> = HashMap::new();
+    for (i, face) in faces.iter().enumerate() {
+        for k in 0..face.len() {
+            let e = edge(face[k], face[(k + 1) % face.len()]);
+            edges.entry(e).or_default().push(i);
+        }
+    }
+
+    let limit = angle.to_radians().cos();
+    edges
+        .into_iter()
+        .filter(|(_, around)| {
+            around.len() == 2 && dot(&normals[around[0]], &normals[around[1]]) < limit
         })
-        .collect())
+        .map(|(e, _)| e)
+        .collect()
+}
+
+/// Normals for the corners of the faces, averaged over the faces around each vertex that meet
+/// the corner's own face at no more than the angle, in degrees. Returns the normals and the index
+/// of the normal of every corner.
+fn corner_normals(
+    vertices: &[Tup],
+    faces: &[Vec<usize>],
+    angle: f32,
+) -> (Vec<Tup>, Vec<Vec<usize>>) {
+    let areas = faces
+        .iter()
+        .map(|f| face_normal(vertices, f))
+        .collect::<Vec<Tup>>();
+    let mut around = vec![vec![]; vertices.len()];
+    for (i, face) in faces.iter().enumerate() {
+        for v in face.iter() {
+            around[*v].push(i);
+        }
+    }
+
+    let limit = angle.to_radians().cos();
+    let mut normals = vec![];
+    let corners = faces
+        .iter()
+        .enumerate()
+        .map(|(i, face)| {
+            let own = areas[i].normalize();
+            face.iter()
+                .map(|v| {
+                    let n = around[*v]
+                        .iter()
+                        .filter(|j| dot(&areas[**j].normalize(), &own) >= limit)
+                        .fold(vector(0., 0., 0.), |n, j| &n + &areas[*j]);
+                    normals.push(n.normalize());
+                    normals.len() - 1
+                })
+                .collect()
+        })
+        .collect();
+    (normals, corners)
+}
+
+/// Normal at each vertex, the average of the faces around it weighted by their area. Faces
+/// going anticlockwise face outwards.
+fn vertex_normals(vertices: &[Tup], faces: &[impl AsRef<[usize]>]) -> Vec<Tup> {
+    let mut normals = vec![vector(0., 0., 0.); vertices.len()];
+    for face in faces.iter() {
+        let face = face.as_ref();
+        let n = face_normal(vertices, face);
+        for i in face.iter() {
+            normals[*i] = &normals[*i] + &n;
+        }
+    }
+    normals
+        .into_iter()
+        .map(|n| if n.magnitude() > 0. { n.normalize() } else { n })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::material::Material;
+    use super::super::matrix::identity;
+    use super::super::objects::{Geometry, Object};
+    use super::*;
+
+    #[test]
+    fn displacing_splits_long_edges_without_tearing() {
+        let square = "v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\nf 1 2 3\nf 1 3 4\n";
+        let mesh = Mesh::read(square.as_bytes()).unwrap();
+        let displaced = mesh.displaced(0.3, |p| if p.x < 0.5 { 1.0 } else { 0.5 });
+
+        for ([a, b, c], _) in displaced.textured_triangles() {
+            for (a, b) in [(a, b), (b, c), (c, a)] {
+                let (a, b) = (&displaced.vertices[a], &displaced.vertices[b]);
+                assert!(vector(b.x - a.x, b.y - a.y, 0.0).magnitude() <= 0.3);
+            }
+        }
+
+        // The square faces +z, and the midpoints of the diagonal are shared by both halves.
+        for (i, v) in displaced.vertices.iter().enumerate() {
+            assert!(v.z == 1.0 || v.z == 0.5);
+            assert!(displaced.vertices[..i]
+                .iter()
+                .all(|w| w.x != v.x || w.y != v.y));
+        }
+    }
+
+    const CUBE: &str = "v -1 -1 -1\nv 1 -1 -1\nv 1 1 -1\nv -1 1 -1\n\
+                        v -1 -1 1\nv 1 -1 1\nv 1 1 1\nv -1 1 1\n\
+                        f 1 4 3 2\nf 5 6 7 8\nf 1 2 6 5\nf 3 4 8 7\nf 2 3 7 6\nf 1 5 8 4\n";
+
+    #[test]
+    fn subdividing_rounds_off_corners_but_not_creases() {
+        let cube = Mesh::read(CUBE.as_bytes()).unwrap();
+        let smooth = cube.subdivided(2, None);
+        assert_eq!(smooth.faces.len(), 6 * 16);
+        assert_eq!(smooth.tris(identity(), true).len(), 6 * 16 * 2);
+
+        // Every point is pulled in from the cube towards a sphere.
+        let corner = smooth.vertices[0].clone();
+        assert!(corner.x > -0.6 && corner.x == corner.y && corner.y == corner.z);
+        assert!(smooth
+            .vertices
+            .iter()
+            .all(|v| v.x.abs() < 1.0 || v.y.abs() < 1.0));
+
+        // The edges of the cube are all creases, so nothing moves off the faces.
+        let creased = cube.subdivided(2, Some(45.0));
+        assert!(creased.vertices.iter().all(|v| {
+            let max = v.x.abs().max(v.y.abs()).max(v.z.abs());
+            (max - 1.0).abs() < 10e-6
+        }));
+        let up = creased.normals[creased.faces[6 * 4][0].normal.unwrap()].clone();
+        assert!(up.cmp_epsilon(0.0, 0.0, 1.0, 0.0));
+    }
+
+    #[test]
+    fn subdividing_keeps_texture_coordinates() {
+        let quad = "v 0 0 0\nv 2 0 0\nv 2 2 0\nv 0 2 0\n\
+                    vt 0 0\nvt 1 0\nvt 1 1\nvt 0 1\n\
+                    f 1/1 2/2 3/3 4/4\n";
+        let quad = Mesh::read(quad.as_bytes()).unwrap().subdivided(1, None);
+        assert_eq!(quad.faces.len(), 4);
+
+        // A lone face has only boundary edges, which stay straight.
+        for face in quad.faces.iter() {
+            for corner in face.iter() {
+                let p = &quad.vertices[corner.vertex];
+                let (u, v) = quad.uvs[corner.uv.unwrap()];
+                assert!((p.x - u * 2.0).abs() < 10e-6 && (p.y - v * 2.0).abs() < 10e-6);
+            }
+        }
+    }
+
+    #[test]
+    fn triangles_keep_texture_coordinates() {
+        let quad = "v 0 0 0\nv 2 0 0\nv 2 2 0\nv 0 2 0\n\
+                    vt 0 0\nvt 1 0\nvt 1 1\nvt 0 1\n\
+                    f 1/1 2/2 3/3 4/4\n";
+        let quad = Mesh::read(quad.as_bytes()).unwrap().displaced(0.5, |_| 0.0);
+        assert!(quad.faces.len() > 2);
+
+        for tri in quad.tris(identity(), false) {
+            let object = Object::new(Geometry::Tri(tri), Material::new(), None);
+            let centre = object.center();
+            let (u, v) = object.surface_uv(&centre, 0.0).unwrap();
+            assert!((centre.x - u * 2.0).abs() < 10e-5 && (centre.y - v * 2.0).abs() < 10e-5);
+        }
+    }
 }
@@ -1,14 +1,22 @@
 use super::material::Material;
 use super::matrix::{identity, Kind, Mat};
-use super::patterns::Pattern;
+use super::motion::Motion;
+use super::patterns::{Pattern, Texture};
 use super::ray::Ray;
-use super::tuple::{cross, dot, point, vector, Tup};
+use super::sdf::Shape;
+use super::tuple::{basis, color, cross, dot, point, vector, Tup};
+use rand::Rng;
 
 #[derive(Debug, Clone)]
 pub struct Object {
     pub geometry: Geometry,
     pub material: Material,
     pub normal_map: Option<Pattern>,
+    /// Transformation applied on top of the geometry's own, changing during the shutter interval.
+    pub motion: Option<Motion>,
+    /// Identifies the scene object this is part of, all the triangles of a model share it. Zero
+    /// when it was not given one.
+    pub id: u32,
 }
 
 #[derive(Debug, Clone)]
@@ -17,6 +25,11 @@ pub enum Geometry {
     Plane(Plane),
     Cube(Cube),
     Tri(Tri),
+    Sdf(Sdf),
+    Heightfield(Heightfield),
+    Curve(Curve),
+    Disk(Disk),
+    Rect(Rect),
 }
 
 impl Object {
@@ -25,10 +38,31 @@ impl Object {
             geometry,
             material,
             normal_map,
+            motion: None,
+            id: 0,
         }
     }
 
-    pub fn normal(&self, p: &Tup, uv: Option<(f32, f32)>) -> Tup {
+    /// Inverse of the motion transformation at the given time, if the object moves.
+    fn motion_inverse(&self, time: f32) -> Option<Mat> {
+        self.motion.as_ref().map(|motion| motion.inverse_at(time))
+    }
+
+    /// Transforms a point in world space to the object's space at the given time.
+    pub fn world_to_object(&self, p: &Tup, time: f32) -> Tup {
+        let p = match self.motion_inverse(time) {
+            Some(motion_inverse) => &motion_inverse * p,
+            None => p.clone(),
+        };
+        &self.transformation().inverse() * &p
+    }
+
+    pub fn normal(&self, p: &Tup, uv: Option<(f32, f32)>, time: f32) -> Tup {
+        let motion_inverse = self.motion_inverse(time);
+        let p = &match &motion_inverse {
+            Some(motion_inverse) => motion_inverse * p,
+            None => p.clone(),
+        };
         let local_point = |transform_inverse: &Mat| transform_inverse * p;
 
         let (local_normal, transform_inverse) = match &self.geometry {
@@ -51,10 +85,28 @@ impl Object {
                 },
                 &o.transform_inverse,
             ),
+            Geometry::Sdf(o) => (
+                o.normal(&local_point(&o.transform_inverse)),
+                &o.transform_inverse,
+            ),
+            Geometry::Heightfield(o) => (
+                o.normal(&local_point(&o.transform_inverse)),
+                &o.transform_inverse,
+            ),
+            Geometry::Curve(o) => (
+                o.normal(&local_point(&o.transform_inverse), uv.map(|(u, _)| u)),
+                &o.transform_inverse,
+            ),
+            Geometry::Disk(o) => (vector(0.0, 1.0, 0.0), &o.transform_inverse),
+            Geometry::Rect(o) => (vector(0.0, 1.0, 0.0), &o.transform_inverse),
         };
 
         let mut world_normal = &transform_inverse.transpose() * &local_normal;
         world_normal.w = 0.0;
+        if let Some(motion_inverse) = &motion_inverse {
+            world_normal = &motion_inverse.transpose() * &world_normal;
+            world_normal.w = 0.0;
+        }
 
         match &self.normal_map {
             Some(pattern) => {
@@ -77,6 +129,14 @@ impl Object {
     }
 
     pub fn intersect(object: &Self, r: &Ray) -> (Option<f32>, Option<f32>, Option<(f32, f32)>) {
+        let moved;
+        let r = match object.motion_inverse(r.time) {
+            Some(motion_inverse) => {
+                moved = r.transform(&motion_inverse);
+                &moved
+            }
+            None => r,
+        };
         let common = |ray: &Ray, transform_inverse: &Mat| ray.transform(&transform_inverse);
 
         match &object.geometry {
@@ -96,6 +156,26 @@ impl Object {
                 Some((t, u, v)) => (Some(t), None, Some((u, v))),
                 None => (None, None, None),
             },
+            Geometry::Sdf(o) => match o.intersect(&common(r, &o.transform_inverse)) {
+                Some((t1, t2)) => (Some(t1), Some(t2), None),
+                None => (None, None, None),
+            },
+            Geometry::Heightfield(o) => match o.intersect(&common(r, &o.transform_inverse)) {
+                Some(t) => (Some(t), None, None),
+                None => (None, None, None),
+            },
+            Geometry::Curve(o) => match o.intersect(&common(r, &o.transform_inverse)) {
+                Some((t1, t2, u)) => (Some(t1), Some(t2), Some((u, 0.))),
+                None => (None, None, None),
+            },
+            Geometry::Disk(o) => match o.intersect(&common(r, &o.transform_inverse)) {
+                Some(t) => (Some(t), None, None),
+                None => (None, None, None),
+            },
+            Geometry::Rect(o) => match o.intersect(&common(r, &o.transform_inverse)) {
+                Some(t) => (Some(t), None, None),
+                None => (None, None, None),
+            },
         }
     }
 
@@ -105,8 +185,187 @@ impl Object {
             Geometry::Plane(o) => o.transform.clone(),
             Geometry::Cube(o) => o.transform.clone(),
             Geometry::Tri(o) => o.transform.clone(),
+            Geometry::Sdf(o) => o.transform.clone(),
+            Geometry::Heightfield(o) => o.transform.clone(),
+            Geometry::Curve(o) => o.transform.clone(),
+            Geometry::Disk(o) => o.transform.clone(),
+            Geometry::Rect(o) => o.transform.clone(),
         }
     }
+
+    /// Texture coordinates the shape has of its own at a point in world space, for shapes that
+    /// have them: around and out from the middle of disks, across rectangles and as laid out on
+    /// the triangles of models.
+    pub fn surface_uv(&self, p: &Tup, time: f32) -> Option<(f32, f32)> {
+        let local = self.world_to_object(p, time);
+        match &self.geometry {
+            Geometry::Disk(o) => Some(o.uv(&local)),
+            Geometry::Tri(o) => o.uv(&local),
+            Geometry::Rect(_) => Some(((local.x + 1.) / 2., (local.z + 1.) / 2.)),
+            _ => None,
+        }
+    }
+
+    /// Direction along the object at the given point in world space, only for curves.
+    pub fn tangent(&self, p: &Tup, time: f32) -> Option<Tup> {
+        match &self.geometry {
+            Geometry::Curve(o) => {
+                let mut tangent = &o.transform * &o.tangent(&self.world_to_object(p, time));
+                if let Some(motion) = &self.motion {
+                    tangent = &motion.at(time) * &tangent;
+                }
+                tangent.w = 0.0;
+                Some(tangent.normalize())
+            }
+            _ => None,
+        }
+    }
+
+    /// Centre of the object in world space, ignoring motion.
+    pub fn center(&self) -> Tup {
+        let local = match &self.geometry {
+            Geometry::Tri(o) => point(
+                (o.p1.x + o.p2.x + o.p3.x) / 3.,
+                (o.p1.y + o.p2.y + o.p3.y) / 3.,
+                (o.p1.z + o.p2.z + o.p3.z) / 3.,
+            ),
+            Geometry::Heightfield(o) => point(0.5, (o.min + o.max) / 2., 0.5),
+            Geometry::Curve(o) => &o.points.iter().cloned().sum::<Tup>() / 4.,
+            _ => point(0., 0., 0.),
+        };
+        &self.transformation() * &local
+    }
+
+    /// Smallest and largest corners of a box around the object in world space, ignoring motion.
+    /// Planes are unbounded.
+    pub fn bounds(&self) -> Option<(Tup, Tup)> {
+        let corners = match &self.geometry {
+            Geometry::Plane(_) => return None,
+            Geometry::Tri(o) => vec![o.p1.clone(), o.p2.clone(), o.p3.clone()],
+            Geometry::Heightfield(o) => (0..8)
+                .map(|i| {
+                    let side = |bit: i32, min: f32, max: f32| if i & bit == 0 { min } else { max };
+                    point(side(1, 0., 1.), side(2, o.min, o.max), side(4, 0., 1.))
+                })
+                .collect(),
+            Geometry::Curve(o) => {
+                let r = o.widths.0.max(o.widths.1) / 2.;
+                let (x, y, z) = (
+                    extent(&o.points, |p| p.x),
+                    extent(&o.points, |p| p.y),
+                    extent(&o.points, |p| p.z),
+                );
+                (0..8)
+                    .map(|i| {
+                        let side = |bit: i32, (min, max): (f32, f32)| {
+                            if i & bit == 0 {
+                                min - r
+                            } else {
+                                max + r
+                            }
+                        };
+                        point(side(1, x), side(2, y), side(4, z))
+                    })
+                    .collect()
+            }
+            Geometry::Disk(_) | Geometry::Rect(_) => [(-1., -1.), (-1., 1.), (1., -1.), (1., 1.)]
+                .iter()
+                .map(|&(x, z)| point(x, 0., z))
+                .collect(),
+            Geometry::Sphere(_) | Geometry::Cube(_) | Geometry::Sdf(_) => {
+                let size = match &self.geometry {
+                    Geometry::Sdf(o) if o.bound.is_infinite() => return None,
+                    Geometry::Sdf(o) => o.bound,
+                    _ => 1.,
+                };
+                (0..8)
+                    .map(|i| {
+                        let side = |bit: i32| if i & bit == 0 { -size } else { size };
+                        point(side(1), side(2), side(4))
+                    })
+                    .collect()
+            }
+        };
+
+        let transform = self.transformation();
+        let corners = corners.iter().map(|c| &transform * c).collect::<Vec<Tup>>();
+        let fold = |f: fn(f32, f32) -> f32, start: f32| {
+            corners.iter().fold(point(start, start, start), |b, c| {
+                point(f(b.x, c.x), f(b.y, c.y), f(b.z, c.z))
+            })
+        };
+        Some((
+            fold(f32::min, f32::INFINITY),
+            fold(f32::max, f32::NEG_INFINITY),
+        ))
+    }
+
+    /// Random point on the surface and the normal there, in world space and ignoring motion.
+    /// Points are spread evenly over the untransformed shape, except for distance fields where
+    /// they are the surface seen from random points around it and heightfields where they are
+    /// spread over the ground below. Planes have no finite area and always give their origin.
+    pub fn sample_surface(&self) -> (Tup, Tup) {
+        let mut rng = rand::thread_rng();
+        let z = rng.gen::<f32>() * 2. - 1.;
+        let phi = 2. * std::f32::consts::PI * rng.gen::<f32>();
+        let direction = vector(
+            (1. - z * z).sqrt() * phi.cos(),
+            (1. - z * z).sqrt() * phi.sin(),
+            z,
+        );
+
+        let local = match &self.geometry {
+            Geometry::Sphere(_) => point(direction.x, direction.y, direction.z),
+            Geometry::Plane(_) => point(0., 0., 0.),
+            Geometry::Cube(_) => {
+                let side = if rng.gen::<bool>() { 1. } else { -1. };
+                let (u, v) = (rng.gen::<f32>() * 2. - 1., rng.gen::<f32>() * 2. - 1.);
+                match rng.gen_range(0, 3) {
+                    0 => point(side, u, v),
+                    1 => point(u, side, v),
+                    _ => point(u, v, side),
+                }
+            }
+            Geometry::Tri(o) => {
+                let (mut u, mut v) = (rng.gen::<f32>(), rng.gen::<f32>());
+                if u + v > 1. {
+                    u = 1. - u;
+                    v = 1. - v;
+                }
+                &(&o.p1 + &(&o.e1 * u)) + &(&o.e2 * v)
+            }
+            Geometry::Sdf(o) => {
+                let ray = Ray {
+                    origin: &point(0., 0., 0.) + &(&direction * o.bound.min(Sdf::MAX_DISTANCE)),
+                    direction: -&direction,
+                    time: 0.0,
+                    wavelength: None,
+                };
+                match o.intersect(&ray) {
+                    Some((t, _)) => ray.position(t),
+                    None => point(0., 0., 0.),
+                }
+            }
+            Geometry::Heightfield(o) => {
+                let (x, z) = (rng.gen::<f32>(), rng.gen::<f32>());
+                point(x, o.height(x, z), z)
+            }
+            Geometry::Curve(o) => {
+                let u = rng.gen::<f32>();
+                let (across, up) = basis(&o.derivative(u).normalize());
+                let around = &(&across * phi.cos()) + &(&up * phi.sin());
+                &o.at(u) + &(&around * (o.width(u) / 2.))
+            }
+            Geometry::Disk(o) => {
+                let r = (o.inner * o.inner + rng.gen::<f32>() * (1. - o.inner * o.inner)).sqrt();
+                point(r * phi.cos(), 0., r * phi.sin())
+            }
+            Geometry::Rect(_) => point(rng.gen::<f32>() * 2. - 1., 0., rng.gen::<f32>() * 2. - 1.),
+        };
+        let p = &self.transformation() * &local;
+        let normal = self.normal(&p, None, 0.0);
+        (p, normal)
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -192,6 +451,82 @@ impl Default for Plane {
     }
 }
 
+/// Disk of radius one around the y axis, lying flat like a plane, with a hole in the middle
+/// when the inner radius is above zero.
+#[derive(Debug, Clone)]
+pub struct Disk {
+    transform: Mat,
+    transform_inverse: Mat,
+    inner: f32,
+}
+
+impl Disk {
+    pub fn new(transform: Mat, inner: f32) -> Self {
+        let transform_inverse = transform.inverse();
+        Disk {
+            transform,
+            transform_inverse,
+            inner,
+        }
+    }
+
+    fn intersect(&self, ray: &Ray) -> Option<f32> {
+        if ray.direction.y.abs() < 10e-5 {
+            return None;
+        }
+        let t = (-ray.origin.y) / ray.direction.y;
+        let p = ray.position(t);
+        let distance = p.x * p.x + p.z * p.z;
+        if distance > 1. || distance < self.inner * self.inner {
+            None
+        } else {
+            Some(t)
+        }
+    }
+
+    /// Angle around the disk as u and distance from the inner to the outer edge as v.
+    fn uv(&self, p: &Tup) -> (f32, f32) {
+        let u = p.z.atan2(p.x).rem_euclid(2. * std::f32::consts::PI) / (2. * std::f32::consts::PI);
+        let r = (p.x * p.x + p.z * p.z).sqrt();
+        let v = if self.inner < 1. {
+            (r - self.inner) / (1. - self.inner)
+        } else {
+            0.
+        };
+        (u, v.clamp(0., 1.))
+    }
+}
+
+/// Square from -1 to 1 along x and z, lying flat like a plane.
+#[derive(Debug, Clone)]
+pub struct Rect {
+    transform: Mat,
+    transform_inverse: Mat,
+}
+
+impl Rect {
+    pub fn new(transform: Mat) -> Self {
+        let transform_inverse = transform.inverse();
+        Rect {
+            transform,
+            transform_inverse,
+        }
+    }
+
+    fn intersect(&self, ray: &Ray) -> Option<f32> {
+        if ray.direction.y.abs() < 10e-5 {
+            return None;
+        }
+        let t = (-ray.origin.y) / ray.direction.y;
+        let p = ray.position(t);
+        if p.x.abs() > 1. || p.z.abs() > 1. {
+            None
+        } else {
+            Some(t)
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Cube {
     transform: Mat,
@@ -280,7 +615,11 @@ pub struct Tri {
     e1: Tup,
     e2: Tup,
     normal: Tup,
+    /// Twice the area, which rays that only just miss being parallel are measured against.
+    double_area: f32,
     smooth_normals: Option<(Tup, Tup, Tup)>,
+    /// Texture coordinates at each corner, for image patterns mapped onto the surface.
+    uvs: Option<[(f32, f32); 3]>,
 }
 
 impl Tri {
@@ -290,10 +629,13 @@ impl Tri {
         p2: Tup,
         p3: Tup,
         smooth_normals: Option<(Tup, Tup, Tup)>,
+        uvs: Option<[(f32, f32); 3]>,
     ) -> Self {
         let transform_inverse = transform.inverse();
         let (e1, e2) = ((&p2 - &p1), (&p3 - &p1));
-        let normal = cross(&e2, &e1).normalize();
+        let normal = cross(&e2, &e1);
+        let double_area = normal.magnitude();
+        let normal = normal.normalize();
         Tri {
             transform,
             transform_inverse,
@@ -303,10 +645,32 @@ impl Tri {
             e1,
             e2,
             normal,
+            double_area,
             smooth_normals,
+            uvs,
         }
     }
 
+    /// Texture coordinates at a point on the triangle, blended from those at the corners.
+    fn uv(&self, p: &Tup) -> Option<(f32, f32)> {
+        let [uv1, uv2, uv3] = self.uvs?;
+        let d = p - &self.p1;
+        let (e1e1, e1e2, e2e2) = (
+            dot(&self.e1, &self.e1),
+            dot(&self.e1, &self.e2),
+            dot(&self.e2, &self.e2),
+        );
+        let (de1, de2) = (dot(&d, &self.e1), dot(&d, &self.e2));
+        let denominator = e1e1 * e2e2 - e1e2 * e1e2;
+        let u = (e2e2 * de1 - e1e2 * de2) / denominator;
+        let v = (e1e1 * de2 - e1e2 * de1) / denominator;
+        let w = 1. - u - v;
+        Some((
+            uv1.0 * w + uv2.0 * u + uv3.0 * v,
+            uv1.1 * w + uv2.1 * u + uv3.1 * v,
+        ))
+    }
+
     fn normal(&self, u: f32, v: f32) -> Tup {
         match &self.smooth_normals {
             Some((n1, n2, n3)) => n2 * u + n3 * v + n1 * (1. - u - v),
@@ -317,7 +681,7 @@ impl Tri {
     fn intersect(&self, ray: &Ray) -> Option<(f32, f32, f32)> {
         let dir_cross_e2 = cross(&ray.direction, &self.e2);
         let determinant = dot(&self.e1, &dir_cross_e2);
-        if determinant.abs() < 10e-4 {
+        if determinant.abs() < 10e-4 * self.double_area {
             return None;
         }
 
@@ -346,12 +710,521 @@ impl Default for Tri {
             point(1., 0., 0.),
             point(0., 1., 0.),
             None,
+            None,
+        )
+    }
+}
+
+/// Surface given by a signed distance function, intersected by sphere tracing: the ray steps
+/// forward by the distance to the surface, which can't overshoot it, until it is close enough.
+#[derive(Debug, Clone)]
+pub struct Sdf {
+    transform: Mat,
+    transform_inverse: Mat,
+    shape: Shape,
+    bound: f32,
+}
+
+impl Sdf {
+    /// Distance to the surface at which a ray is considered to hit it.
+    const EPSILON: f32 = 10e-6;
+    const MAX_STEPS: u32 = 512;
+    /// How far rays are traced through shapes that go on forever.
+    const MAX_DISTANCE: f32 = 100.;
+
+    pub fn new(transform: Mat, shape: Shape) -> Self {
+        let transform_inverse = transform.inverse();
+        Sdf {
+            transform,
+            transform_inverse,
+            bound: shape.bound(),
+            shape,
+        }
+    }
+
+    /// Gradient of the distance, by central differences.
+    fn normal(&self, p: &Tup) -> Tup {
+        let h = 10e-5;
+        let d = |x, y, z| self.shape.distance(&point(p.x + x, p.y + y, p.z + z));
+        vector(
+            d(h, 0., 0.) - d(-h, 0., 0.),
+            d(0., h, 0.) - d(0., -h, 0.),
+            d(0., 0., h) - d(0., 0., -h),
+        )
+        .normalize()
+    }
+
+    /// Where the ray enters and leaves the shape, either of which can be behind its origin.
+    fn intersect(&self, ray: &Ray) -> Option<(f32, f32)> {
+        let speed = ray.direction.magnitude();
+        let direction = &ray.direction / speed;
+        let origin = &ray.origin;
+
+        // Only the part of the ray inside the bounding sphere is traced.
+        let (start, end) = if self.bound.is_finite() {
+            let from_center = origin - &point(0., 0., 0.);
+            let b = dot(&direction, &from_center);
+            let c = dot(&from_center, &from_center) - self.bound.powi(2);
+            let discriminant = b * b - c;
+            if discriminant < 0. {
+                return None;
+            }
+            (-b - discriminant.sqrt(), -b + discriminant.sqrt())
+        } else {
+            (-Self::MAX_DISTANCE, Self::MAX_DISTANCE)
+        };
+
+        let (entry, exit) = if self.shape.distance(origin) < 0. {
+            let backwards = -&direction;
+            let entry = self
+                .march(origin, &backwards, 0., -start, true)
+                .unwrap_or((-start).max(0.));
+            let exit = self.march(origin, &direction, 0., end, true).unwrap_or(end);
+            (-entry, exit)
+        } else {
+            let entry = self.march(origin, &direction, start.max(0.), end, false)?;
+            let exit = self
+                .march(origin, &direction, entry + 10e-4, end, true)
+                .unwrap_or(end);
+            (entry, exit)
+        };
+
+        Some((entry / speed, exit / speed))
+    }
+
+    /// Distance along the direction, between the two given, at which the surface is reached
+    /// from the outside, or from the inside when `inside` is set.
+    fn march(
+        &self,
+        origin: &Tup,
+        direction: &Tup,
+        from: f32,
+        to: f32,
+        inside: bool,
+    ) -> Option<f32> {
+        let sign = if inside { -1. } else { 1. };
+        let mut s = from;
+        for _ in 0..Self::MAX_STEPS {
+            if s > to {
+                return None;
+            }
+            let d = sign * self.shape.distance(&(origin + &(direction * s)));
+            if d < Self::EPSILON {
+                return Some(s);
+            }
+            s += d;
+        }
+        None
+    }
+}
+
+/// Terrain over the unit square from x and z of 0 to 1, with the height of every point of a grid
+/// given. Each cell of the grid is split into two triangles, found by walking the cells under the
+/// ray, and the normals at the grid points are blended across them so that it looks smooth. Planar
+/// UV mapping lays an image exactly once over it, the same way round as the heights.
+#[derive(Debug, Clone)]
+pub struct Heightfield {
+    transform: Mat,
+    transform_inverse: Mat,
+    /// Heights row after row, rows going along z.
+    heights: Vec<f32>,
+    normals: Vec<Tup>,
+    columns: usize,
+    rows: usize,
+    min: f32,
+    max: f32,
+}
+
+impl Heightfield {
+    /// Tolerance for rays grazing the edges of the cells and of the box around them.
+    const EPSILON: f32 = 10e-6;
+
+    /// Heightfield with `columns` heights along x in every row. There must be at least two rows
+    /// and two columns.
+    pub fn new(transform: Mat, heights: Vec<f32>, columns: usize) -> Self {
+        let rows = heights.len() / columns;
+        let transform_inverse = transform.inverse();
+        let at = |x: usize, z: usize| heights[z * columns + x];
+        let (width, depth) = ((columns - 1) as f32, (rows - 1) as f32);
+
+        let normals = (0..rows * columns)
+            .map(|i| {
+                let (x, z) = (i % columns, i / columns);
+                let (left, right) = (x.saturating_sub(1), (x + 1).min(columns - 1));
+                let (back, front) = (z.saturating_sub(1), (z + 1).min(rows - 1));
+                let dx = (at(right, z) - at(left, z)) * width / (right - left) as f32;
+                let dz = (at(x, front) - at(x, back)) * depth / (front - back) as f32;
+                vector(-dx, 1., -dz).normalize()
+            })
+            .collect();
+
+        Heightfield {
+            transform,
+            transform_inverse,
+            min: heights.iter().cloned().fold(f32::INFINITY, f32::min),
+            max: heights.iter().cloned().fold(f32::NEG_INFINITY, f32::max),
+            heights,
+            normals,
+            columns,
+            rows,
+        }
+    }
+
+    /// Heightfield with a height for every pixel of the image, its brightness times the scale.
+    /// The top row of the image is at the far end, where z is 1.
+    pub fn from_texture(transform: Mat, texture: &Texture, scale: f32) -> Self {
+        let (width, height) = texture.size();
+        let heights = (0..width * height)
+            .map(|i| {
+                let (x, y) = (i % width, height - 1 - i / width);
+                let c = texture.color_at(x, y).unwrap_or_else(|| color(0., 0., 0.));
+                (c.x + c.y + c.z) / 3. * scale
+            })
+            .collect();
+        Heightfield::new(transform, heights, width as usize)
+    }
+
+    /// Grid cell under the point, and where in it the point is from 0 to 1 along x and z.
+    fn cell(&self, x: f32, z: f32) -> (usize, usize, f32, f32) {
+        let (gx, gz) = (x * (self.columns - 1) as f32, z * (self.rows - 1) as f32);
+        let cx = (gx.floor().max(0.) as usize).min(self.columns - 2);
+        let cz = (gz.floor().max(0.) as usize).min(self.rows - 2);
+        (cx, cz, gx - cx as f32, gz - cz as f32)
+    }
+
+    /// Heights at the corners of a cell: the near left, near right, far left and far right ones.
+    fn corners(&self, cx: usize, cz: usize) -> (f32, f32, f32, f32) {
+        let i = cz * self.columns + cx;
+        (
+            self.heights[i],
+            self.heights[i + 1],
+            self.heights[i + self.columns],
+            self.heights[i + self.columns + 1],
         )
     }
+
+    /// Height of the surface at the given x and z.
+    pub fn height(&self, x: f32, z: f32) -> f32 {
+        let (cx, cz, fx, fz) = self.cell(x, z);
+        let (h00, h10, h01, h11) = self.corners(cx, cz);
+        if fx >= fz {
+            h00 + (h10 - h00) * fx + (h11 - h10) * fz
+        } else {
+            h00 + (h11 - h01) * fx + (h01 - h00) * fz
+        }
+    }
+
+    fn normal(&self, p: &Tup) -> Tup {
+        let (cx, cz, fx, fz) = self.cell(p.x, p.z);
+        let (fx, fz) = (fx.clamp(0., 1.), fz.clamp(0., 1.));
+        let i = cz * self.columns + cx;
+        let n = &(&(&self.normals[i] * ((1. - fx) * (1. - fz)))
+            + &(&self.normals[i + 1] * (fx * (1. - fz))))
+            + &(&(&self.normals[i + self.columns] * ((1. - fx) * fz))
+                + &(&self.normals[i + self.columns + 1] * (fx * fz)));
+        n.normalize()
+    }
+
+    /// Closest hit in front of the ray, walking the cells under it from where it enters the box
+    /// around the terrain until it leaves.
+    fn intersect(&self, ray: &Ray) -> Option<f32> {
+        let (o, d) = (&ray.origin, &ray.direction);
+        let mut start = 0f32;
+        let mut end = f32::INFINITY;
+        let slabs = [
+            (o.x, d.x, 0., 1.),
+            (o.y, d.y, self.min, self.max),
+            (o.z, d.z, 0., 1.),
+        ];
+        for (o, d, min, max) in slabs {
+            if d.abs() < Self::EPSILON {
+                if o < min - Self::EPSILON || o > max + Self::EPSILON {
+                    return None;
+                }
+            } else {
+                let (t1, t2) = ((min - o) / d, (max - o) / d);
+                start = start.max(t1.min(t2));
+                end = end.min(t1.max(t2));
+            }
+        }
+        if start > end + Self::EPSILON {
+            return None;
+        }
+
+        // Walk the grid in cell units.
+        let (columns, rows) = ((self.columns - 1) as f32, (self.rows - 1) as f32);
+        let entry = ray.position(start);
+        let (cx, cz, _, _) = self.cell(entry.x, entry.z);
+        let (mut cx, mut cz) = (cx as i64, cz as i64);
+        let (dx, dz) = (d.x * columns, d.z * rows);
+        let crossing = |c: i64, g: f32, dg: f32| {
+            if dg > 0. {
+                ((c + 1) as f32 - g) / dg
+            } else if dg < 0. {
+                (c as f32 - g) / dg
+            } else {
+                f32::INFINITY
+            }
+        };
+        let mut next_x = crossing(cx, o.x * columns, dx);
+        let mut next_z = crossing(cz, o.z * rows, dz);
+        let mut t = start;
+
+        while cx >= 0 && cz >= 0 && cx < self.columns as i64 - 1 && cz < self.rows as i64 - 1 {
+            let leave = next_x.min(next_z).min(end);
+            if let Some(hit) = self.intersect_cell(ray, cx as usize, cz as usize, t, leave) {
+                return Some(hit);
+            }
+            if leave >= end {
+                break;
+            }
+            t = leave;
+            if next_x < next_z {
+                cx += if dx > 0. { 1 } else { -1 };
+                next_x += 1. / dx.abs();
+            } else {
+                cz += if dz > 0. { 1 } else { -1 };
+                next_z += 1. / dz.abs();
+            }
+        }
+        None
+    }
+
+    /// Closest hit on the two triangles of a cell between the distances given.
+    fn intersect_cell(&self, ray: &Ray, cx: usize, cz: usize, from: f32, to: f32) -> Option<f32> {
+        let (o, d) = (&ray.origin, &ray.direction);
+        let (columns, rows) = ((self.columns - 1) as f32, (self.rows - 1) as f32);
+        let (fx, dfx) = (o.x * columns - cx as f32, d.x * columns);
+        let (fz, dfz) = (o.z * rows - cz as f32, d.z * rows);
+        let (h00, h10, h01, h11) = self.corners(cx, cz);
+
+        // Each triangle is a plane h + a * x + b * z over its part of the cell.
+        [(h10 - h00, h11 - h10, 1.), (h11 - h01, h01 - h00, -1.)]
+            .iter()
+            .filter_map(|(a, b, side)| {
+                let t = (h00 + a * fx + b * fz - o.y) / (d.y - a * dfx - b * dfz);
+                let (x, z) = (fx + dfx * t, fz + dfz * t);
+                let inside = x >= -Self::EPSILON
+                    && z >= -Self::EPSILON
+                    && x <= 1. + Self::EPSILON
+                    && z <= 1. + Self::EPSILON
+                    && side * (x - z) >= -Self::EPSILON;
+                if inside && t > 0. && t >= from - Self::EPSILON && t <= to + Self::EPSILON {
+                    Some(t)
+                } else {
+                    None
+                }
+            })
+            .fold(None, |closest: Option<f32>, t| {
+                Some(closest.map_or(t, |c| c.min(t)))
+            })
+    }
+}
+
+/// Cubic Bézier curve swept by a circle whose width changes linearly along it, for hair, fur,
+/// grass and cables.
+#[derive(Debug, Clone)]
+pub struct Curve {
+    transform: Mat,
+    transform_inverse: Mat,
+    points: [Tup; 4],
+    widths: (f32, f32),
+}
+
+impl Curve {
+    const MAX_DEPTH: i32 = 10;
+
+    pub fn new(transform: Mat, points: [Tup; 4], widths: (f32, f32)) -> Self {
+        let transform_inverse = transform.inverse();
+        Self {
+            transform,
+            transform_inverse,
+            points,
+            widths,
+        }
+    }
+
+    fn at(&self, u: f32) -> Tup {
+        bezier(&self.points, u)
+    }
+
+    fn derivative(&self, u: f32) -> Tup {
+        let p = &self.points;
+        let v = 1. - u;
+        &(&(&(&p[1] - &p[0]) * (3. * v * v)) + &(&(&p[2] - &p[1]) * (6. * v * u)))
+            + &(&(&p[3] - &p[2]) * (3. * u * u))
+    }
+
+    fn width(&self, u: f32) -> f32 {
+        self.widths.0 * (1. - u) + self.widths.1 * u
+    }
+
+    /// Parameter of the point on the curve closest to p.
+    fn closest(&self, p: &Tup) -> f32 {
+        let distance = |u: f32| {
+            let d = p - &self.at(u);
+            dot(&d, &d)
+        };
+        let steps = 64;
+        let nearest = (0..=steps).min_by(|&a, &b| {
+            let (a, b) = (
+                distance(a as f32 / steps as f32),
+                distance(b as f32 / steps as f32),
+            );
+            a.partial_cmp(&b).unwrap_or(std::cmp::Ordering::Equal)
+        });
+        let u = nearest.unwrap_or(0) as f32 / steps as f32;
+        let step = 1. / steps as f32;
+        let (mut low, mut high) = ((u - step).max(0.), (u + step).min(1.));
+
+        // The distance is flattest there, so look for where p stops being ahead along the curve.
+        for _ in 0..24 {
+            let middle = (low + high) / 2.;
+            if dot(&(p - &self.at(middle)), &self.derivative(middle)) > 0. {
+                low = middle;
+            } else {
+                high = middle;
+            }
+        }
+        (low + high) / 2.
+    }
+
+    fn tangent(&self, p: &Tup) -> Tup {
+        self.derivative(self.closest(p)).normalize()
+    }
+
+    /// Normal pointing away from the middle of the curve, using the parameter of the hit when
+    /// it is known.
+    fn normal(&self, p: &Tup, u: Option<f32>) -> Tup {
+        let u = u.unwrap_or_else(|| self.closest(p));
+        let tangent = self.derivative(u).normalize();
+        let outward = p - &self.at(u);
+        let outward = &outward - &(&tangent * dot(&outward, &tangent));
+        if outward.magnitude() < 10e-6 {
+            basis(&tangent).0
+        } else {
+            outward.normalize()
+        }
+    }
+
+    /// Entry and exit distances through the curve and the parameter along it where the ray
+    /// passes, found by splitting the curve until the pieces are nearly straight.
+    fn intersect(&self, ray: &Ray) -> Option<(f32, f32, f32)> {
+        let length = ray.direction.magnitude();
+        let z = &ray.direction / length;
+        let (x, y) = basis(&z);
+        let to_ray = |p: &Tup| {
+            let p = p - &ray.origin;
+            vector(dot(&p, &x), dot(&p, &y), dot(&p, &z))
+        };
+        let points = [
+            to_ray(&self.points[0]),
+            to_ray(&self.points[1]),
+            to_ray(&self.points[2]),
+            to_ray(&self.points[3]),
+        ];
+
+        // Deep enough that the pieces are within a twentieth of the width from straight.
+        let bend = (0..2)
+            .map(|i| {
+                let d = &(&points[i] - &(&points[i + 1] * 2.)) + &points[i + 2];
+                d.x.abs().max(d.y.abs()).max(d.z.abs())
+            })
+            .fold(0., f32::max);
+        let epsilon = self.widths.0.max(self.widths.1) * 0.05;
+        let depth = if bend > 0. && epsilon > 0. {
+            let depth = (std::f32::consts::SQRT_2 * 6. * bend / (8. * epsilon)).log2() / 2.;
+            (depth.round() as i32).clamp(0, Self::MAX_DEPTH)
+        } else {
+            0
+        };
+
+        self.intersect_piece(&points, 0., 1., depth)
+            .map(|(t1, t2, u)| (t1 / length, t2 / length, u))
+    }
+
+    /// Hit on the piece of the curve between parameters u0 and u1, with its control points in
+    /// a space where the ray starts at the origin and runs along z.
+    fn intersect_piece(
+        &self,
+        p: &[Tup; 4],
+        u0: f32,
+        u1: f32,
+        depth: i32,
+    ) -> Option<(f32, f32, f32)> {
+        let r = self.width(u0).max(self.width(u1)) / 2.;
+        let ((x0, x1), (y0, y1), (_, z1)) =
+            (extent(p, |p| p.x), extent(p, |p| p.y), extent(p, |p| p.z));
+        if x0 - r > 0. || x1 + r < 0. || y0 - r > 0. || y1 + r < 0. || z1 + r < 0. {
+            return None;
+        }
+
+        if depth > 0 {
+            let middle = (u0 + u1) / 2.;
+            let (first, second) = split(p);
+            let near = self.intersect_piece(&first, u0, middle, depth - 1);
+            let far = self.intersect_piece(&second, middle, u1, depth - 1);
+            return match (near, far) {
+                (Some(a), Some(b)) => Some(if b.0 < a.0 { b } else { a }),
+                (a, b) => a.or(b),
+            };
+        }
+
+        // Outside the ends of the piece, cut square to its direction there.
+        if (p[1].x - p[0].x) * -p[0].x + (p[1].y - p[0].y) * -p[0].y < 0.
+            || (p[2].x - p[3].x) * -p[3].x + (p[2].y - p[3].y) * -p[3].y < 0.
+        {
+            return None;
+        }
+        let (sx, sy) = (p[3].x - p[0].x, p[3].y - p[0].y);
+        let along = sx * sx + sy * sy;
+        let w = if along > 0. {
+            ((-p[0].x * sx - p[0].y * sy) / along).clamp(0., 1.)
+        } else {
+            0.
+        };
+        let u = u0 + (u1 - u0) * w;
+        let c = bezier(p, w);
+        let (distance, r) = (c.x * c.x + c.y * c.y, self.width(u) / 2.);
+        if distance > r * r {
+            return None;
+        }
+        let half = (r * r - distance).sqrt();
+        if c.z + half < 0. {
+            return None;
+        }
+        Some((c.z - half, c.z + half, u))
+    }
+}
+
+/// Smallest and largest coordinate of the points along one axis.
+fn extent(points: &[Tup; 4], axis: fn(&Tup) -> f32) -> (f32, f32) {
+    points
+        .iter()
+        .map(axis)
+        .fold((f32::INFINITY, f32::NEG_INFINITY), |(min, max), v| {
+            (min.min(v), max.max(v))
+        })
+}
+
+fn bezier(p: &[Tup; 4], u: f32) -> Tup {
+    let v = 1. - u;
+    &(&(&p[0] * (v * v * v)) + &(&p[1] * (3. * v * v * u)))
+        + &(&(&p[2] * (3. * v * u * u)) + &(&p[3] * (u * u * u)))
+}
+
+/// Control points of the two halves of a curve.
+fn split(p: &[Tup; 4]) -> ([Tup; 4], [Tup; 4]) {
+    let mid = |a: &Tup, b: &Tup| &(a + b) / 2.;
+    let (a, b, c) = (mid(&p[0], &p[1]), mid(&p[1], &p[2]), mid(&p[2], &p[3]));
+    let (d, e) = (mid(&a, &b), mid(&b, &c));
+    let f = mid(&d, &e);
+    ([p[0].clone(), a, d, f.clone()], [f, e, c, p[3].clone()])
 }
 
 #[cfg(test)]
 mod tests {
+    use super::super::motion::Transform;
     use super::super::transformations::{rotate_z, scaling, translation};
     use super::*;
 
@@ -360,6 +1233,8 @@ mod tests {
         let r = Ray {
             origin: point(0.0, 0.0, -5.0),
             direction: vector(0.0, 0.0, 1.0),
+            time: 0.0,
+            wavelength: None,
         };
         let s = Sphere::new(scaling(2.0, 2.0, 2.0));
 
@@ -367,6 +1242,8 @@ mod tests {
             geometry: Geometry::Sphere(s),
             material: Material::new(),
             normal_map: None,
+            motion: None,
+            id: 0,
         };
         let ixs = Object::intersect(&obj, &r);
 
@@ -379,6 +1256,8 @@ mod tests {
         let r = Ray {
             origin: point(0.0, 0.0, -5.0),
             direction: vector(0.0, 0.0, 1.0),
+            time: 0.0,
+            wavelength: None,
         };
         let s = Sphere::new(translation(5.0, 2.0, 2.0));
 
@@ -386,6 +1265,8 @@ mod tests {
             geometry: Geometry::Sphere(s),
             material: Material::new(),
             normal_map: None,
+            motion: None,
+            id: 0,
         };
         let ixs = Object::intersect(&obj, &r);
 
@@ -427,8 +1308,10 @@ mod tests {
                 geometry: Geometry::Sphere(s),
                 material: Material::new(),
                 normal_map: None,
+                motion: None,
+                id: 0,
             };
-            let normal = obj.normal(&point(0.0, 1.70711, -0.70711), None);
+            let normal = obj.normal(&point(0.0, 1.70711, -0.70711), None, 0.0);
 
             assert_eq!(0.0, normal.x);
             assert!((normal.y - 0.70711).abs() < 10e-5);
@@ -443,8 +1326,10 @@ mod tests {
                 geometry: Geometry::Sphere(s),
                 material: Material::new(),
                 normal_map: None,
+                motion: None,
+                id: 0,
             };
-            let normal = obj.normal(&point(0.0, p, -p), None);
+            let normal = obj.normal(&point(0.0, p, -p), None, 0.0);
 
             assert!(normal.x.abs() < 10e-5);
             assert!((normal.y - 0.97014).abs() < 10e-5);
@@ -481,6 +1366,8 @@ mod tests {
             let r = Ray {
                 origin: point(0.0, 10.0, 0.0),
                 direction: vector(0.0, 0.0, 1.0),
+                time: 0.0,
+                wavelength: None,
             };
             let xs = p.intersect(&r);
 
@@ -495,6 +1382,8 @@ mod tests {
             let r = Ray {
                 origin: point(0.0, 0.0, 0.0),
                 direction: vector(0.0, 0.0, 1.0),
+                time: 0.0,
+                wavelength: None,
             };
             let xs = p.intersect(&r);
 
@@ -509,6 +1398,8 @@ mod tests {
             let r = Ray {
                 origin: point(0.0, 1.0, 0.0),
                 direction: vector(0.0, -1.0, 0.0),
+                time: 0.0,
+                wavelength: None,
             };
             let xs = p.intersect(&r);
 
@@ -523,6 +1414,8 @@ mod tests {
             let r = Ray {
                 origin: point(0.0, -1.0, 0.0),
                 direction: vector(0.0, 1.0, 0.0),
+                time: 0.0,
+                wavelength: None,
             };
             let xs = p.intersect(&r);
 
@@ -549,6 +1442,8 @@ mod tests {
                 Ray {
                     origin: point(5.0, 0.5, 0.0),
                     direction: vector(-1.0, 0.0, 0.0),
+                    time: 0.0,
+                    wavelength: None,
                 },
                 Some((4f32, 6f32)),
             ),
@@ -557,6 +1452,8 @@ mod tests {
                 Ray {
                     origin: point(-5.0, 0.5, 0.0),
                     direction: vector(1.0, 0.0, 0.0),
+                    time: 0.0,
+                    wavelength: None,
                 },
                 Some((4f32, 6f32)),
             ),
@@ -565,6 +1462,8 @@ mod tests {
                 Ray {
                     origin: point(0.5, 5.0, 0.0),
                     direction: vector(0.0, -1.0, 0.0),
+                    time: 0.0,
+                    wavelength: None,
                 },
                 Some((4f32, 6f32)),
             ),
@@ -573,6 +1472,8 @@ mod tests {
                 Ray {
                     origin: point(0.5, -5.0, 0.0),
                     direction: vector(0.0, 1.0, 0.0),
+                    time: 0.0,
+                    wavelength: None,
                 },
                 Some((4f32, 6f32)),
             ),
@@ -581,6 +1482,8 @@ mod tests {
                 Ray {
                     origin: point(0.5, 0.0, 5.0),
                     direction: vector(0.0, 0.0, -1.0),
+                    time: 0.0,
+                    wavelength: None,
                 },
                 Some((4f32, 6f32)),
             ),
@@ -589,6 +1492,8 @@ mod tests {
                 Ray {
                     origin: point(0.5, 0.0, -5.0),
                     direction: vector(0.0, 0.0, 1.0),
+                    time: 0.0,
+                    wavelength: None,
                 },
                 Some((4f32, 6f32)),
             ),
@@ -597,6 +1502,8 @@ mod tests {
                 Ray {
                     origin: point(0.0, 0.5, 0.0),
                     direction: vector(0.0, 0.0, 1.0),
+                    time: 0.0,
+                    wavelength: None,
                 },
                 Some((-1f32, 1f32)),
             ),
@@ -621,14 +1528,20 @@ mod tests {
             Ray {
                 origin: point(2., 0., 2.),
                 direction: vector(0., 0., -1.),
+                time: 0.0,
+                wavelength: None,
             },
             Ray {
                 origin: point(0., 2., 2.),
                 direction: vector(0., -1., 0.),
+                time: 0.0,
+                wavelength: None,
             },
             Ray {
                 origin: point(2., 2., 0.),
                 direction: vector(-1., 0., 0.),
+                time: 0.0,
+                wavelength: None,
             },
         ]
         .into_iter()
@@ -670,6 +1583,7 @@ mod tests {
             point(-1., 0., 0.),
             point(1., 0., 0.),
             None,
+            None,
         );
 
         assert_eq!(tri.e1, vector(-1., -1., 0.));
@@ -692,10 +1606,13 @@ mod tests {
             point(-1., 0., 0.),
             point(1., 0., 0.),
             None,
+            None,
         );
         let ray = Ray {
             origin: point(0., -1., -2.),
             direction: vector(0., 1., 0.),
+            time: 0.0,
+            wavelength: None,
         };
 
         assert_eq!(tri.intersect(&ray), None);
@@ -709,12 +1626,15 @@ mod tests {
             point(-1., 0., 0.),
             point(1., 0., 0.),
             None,
+            None,
         );
 
         // p1-p3 edge
         let ray = Ray {
             origin: point(1., 1., -2.),
             direction: vector(0., 0., 1.),
+            time: 0.0,
+            wavelength: None,
         };
         assert_eq!(tri.intersect(&ray), None);
 
@@ -722,6 +1642,8 @@ mod tests {
         let ray = Ray {
             origin: point(-1., 1., -2.),
             direction: vector(0., 0., 1.),
+            time: 0.0,
+            wavelength: None,
         };
         assert_eq!(tri.intersect(&ray), None);
 
@@ -729,6 +1651,8 @@ mod tests {
         let ray = Ray {
             origin: point(0., -1., -2.),
             direction: vector(0., 0., 1.),
+            time: 0.0,
+            wavelength: None,
         };
         assert_eq!(tri.intersect(&ray), None);
     }
@@ -741,12 +1665,247 @@ mod tests {
             point(-1., 0., 0.),
             point(1., 0., 0.),
             None,
+            None,
         );
         let ray = Ray {
             origin: point(0., 0.5, -2.),
             direction: vector(0., 0., 1.),
+            time: 0.0,
+            wavelength: None,
         };
 
         assert_eq!(tri.intersect(&ray), Some((2., 0.25, 0.25)));
     }
+
+    #[test]
+    fn intersecting_moving_sphere() {
+        let obj = Object {
+            geometry: Geometry::Sphere(Sphere::default()),
+            material: Material::new(),
+            normal_map: None,
+            motion: Some(
+                Motion::new(vec![
+                    (0.0, vec![Transform::Translation(0.0, 0.0, 0.0)]),
+                    (1.0, vec![Transform::Translation(4.0, 0.0, 0.0)]),
+                ])
+                .unwrap(),
+            ),
+            id: 0,
+        };
+        let ray = |time: f32| Ray {
+            origin: point(2.0, 0.0, -5.0),
+            direction: vector(0.0, 0.0, 1.0),
+            time,
+            wavelength: None,
+        };
+
+        assert_eq!(Object::intersect(&obj, &ray(0.0)), (None, None, None));
+        assert_eq!(
+            Object::intersect(&obj, &ray(0.5)),
+            (Some(4.0), Some(6.0), None)
+        );
+
+        let normal = obj.normal(&point(3.0, 0.0, 0.0), None, 0.5);
+        assert!(normal.cmp_epsilon(1.0, 0.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn sphere_tracing_distance_fields() {
+        let sdf = Object::new(
+            Geometry::Sdf(Sdf::new(
+                scaling(2.0, 2.0, 2.0),
+                Shape::Sphere { radius: 1.0 },
+            )),
+            Material::new(),
+            None,
+        );
+        let ray = |z| Ray {
+            origin: point(0.0, 0.0, z),
+            direction: vector(0.0, 0.0, 1.0),
+            time: 0.0,
+            wavelength: None,
+        };
+
+        // Same as the scaled sphere, from outside and from inside.
+        let (t1, t2, _) = Object::intersect(&sdf, &ray(-5.0));
+        assert!((t1.unwrap() - 3.0).abs() < 10e-4);
+        assert!((t2.unwrap() - 7.0).abs() < 10e-4);
+
+        let (t1, t2, _) = Object::intersect(&sdf, &ray(0.0));
+        assert!((t1.unwrap() + 2.0).abs() < 10e-4);
+        assert!((t2.unwrap() - 2.0).abs() < 10e-4);
+
+        let normal = sdf.normal(&point(0.0, 2.0, 0.0), None, 0.0);
+        assert!(normal.cmp_epsilon(0.0, 1.0, 0.0, 0.0));
+
+        let missed = Ray {
+            direction: vector(0.0, 1.0, 0.0),
+            ..ray(-5.0)
+        };
+        assert_eq!(Object::intersect(&sdf, &missed), (None, None, None));
+    }
+
+    #[test]
+    fn tracing_from_inside_endless_distance_fields() {
+        // Overlapping balls make a tube along x that goes on past where rays are traced.
+        let tube = Object::new(
+            Geometry::Sdf(Sdf::new(
+                identity(),
+                Shape::Repeat {
+                    shape: Box::new(Shape::Sphere { radius: 3.0 }),
+                    period: vector(4.0, 0.0, 0.0),
+                },
+            )),
+            Material::new(),
+            None,
+        );
+        let along = Ray {
+            origin: point(0.0, 0.0, 0.0),
+            direction: vector(1.0, 0.0, 0.0),
+            time: 0.0,
+            wavelength: None,
+        };
+
+        // The way in is as far behind the ray as it is traced, not ahead of it.
+        let (t1, t2, _) = Object::intersect(&tube, &along);
+        assert!((t1.unwrap() + Sdf::MAX_DISTANCE).abs() < 10e-4);
+        assert!(t2.unwrap() > 0.0);
+    }
+
+    #[test]
+    fn walking_a_heightfield() {
+        // A pyramid two units wide and one high in the middle.
+        #[rustfmt::skip]
+        let heights = vec![
+            0.0, 0.0, 0.0,
+            0.0, 1.0, 0.0,
+            0.0, 0.0, 0.0,
+        ];
+        let terrain = Object::new(
+            Geometry::Heightfield(Heightfield::new(scaling(2.0, 1.0, 2.0), heights, 3)),
+            Material::new(),
+            None,
+        );
+        let ray = |origin, direction| Ray {
+            origin,
+            direction,
+            time: 0.0,
+            wavelength: None,
+        };
+        let down = vector(0.0, -1.0, 0.0);
+
+        let (t, _, _) = Object::intersect(&terrain, &ray(point(1.0, 5.0, 1.0), down.clone()));
+        assert!((t.unwrap() - 4.0).abs() < 10e-5);
+        let (t, _, _) = Object::intersect(&terrain, &ray(point(0.5, 5.0, 0.5), down.clone()));
+        assert!((t.unwrap() - 4.5).abs() < 10e-5);
+
+        // Across the cells, in through the side of the box.
+        let across = ray(point(-1.0, 0.5, 1.0), vector(1.0, 0.0, 0.0));
+        let (t, _, _) = Object::intersect(&terrain, &across);
+        assert!((t.unwrap() - 1.5).abs() < 10e-5);
+
+        let above = ray(point(-1.0, 2.0, 1.0), vector(1.0, 0.0, 0.0));
+        assert_eq!(Object::intersect(&terrain, &above), (None, None, None));
+        let beside = ray(point(3.0, 5.0, 1.0), down);
+        assert_eq!(Object::intersect(&terrain, &beside), (None, None, None));
+
+        let top = terrain.normal(&point(1.0, 1.0, 1.0), None, 0.0);
+        assert!(top.cmp_epsilon(0.0, 1.0, 0.0, 0.0));
+        let slope = terrain.normal(&point(0.5, 0.5, 1.0), None, 0.0);
+        assert!(slope.x < 0.0 && slope.y > 0.0);
+    }
+
+    #[test]
+    fn hitting_disks_and_rectangles() {
+        let ring = Object::new(
+            Geometry::Disk(Disk::new(scaling(2.0, 1.0, 2.0), 0.5)),
+            Material::new(),
+            None,
+        );
+        let card = Object::new(Geometry::Rect(Rect::new(identity())), Material::new(), None);
+        let down = |x, z| Ray {
+            origin: point(x, 1.0, z),
+            direction: vector(0.0, -1.0, 0.0),
+            time: 0.0,
+            wavelength: None,
+        };
+
+        assert_eq!(
+            Object::intersect(&ring, &down(1.5, 0.0)),
+            (Some(1.0), None, None)
+        );
+        assert_eq!(
+            Object::intersect(&ring, &down(0.5, 0.0)),
+            (None, None, None)
+        );
+        assert_eq!(
+            Object::intersect(&ring, &down(1.5, 1.5)),
+            (None, None, None)
+        );
+        assert_eq!(
+            Object::intersect(&card, &down(0.9, -0.9)),
+            (Some(1.0), None, None)
+        );
+        assert_eq!(
+            Object::intersect(&card, &down(1.1, 0.0)),
+            (None, None, None)
+        );
+
+        let (u, v) = ring.surface_uv(&point(0.0, 0.0, 1.5), 0.0).unwrap();
+        assert!((u - 0.25).abs() < 10e-5 && (v - 0.5).abs() < 10e-5);
+        assert_eq!(
+            card.surface_uv(&point(0.5, 0.0, -1.0), 0.0),
+            Some((0.75, 0.0))
+        );
+        assert!(card
+            .normal(&point(0.0, 0.0, 0.0), None, 0.0)
+            .cmp_epsilon(0.0, 1.0, 0.0, 0.0));
+
+        for _ in 0..100 {
+            let (p, _) = ring.sample_surface();
+            let r = (p.x * p.x + p.z * p.z).sqrt();
+            assert!((1.0 - 10e-5..=2.0 + 10e-5).contains(&r));
+        }
+    }
+
+    #[test]
+    fn hitting_a_curve() {
+        // An arch from (-1, 0, 0) to (1, 0, 0) reaching up to y = 0.75, thinning towards its end.
+        let points = [
+            point(-1.0, 0.0, 0.0),
+            point(-1.0, 1.0, 0.0),
+            point(1.0, 1.0, 0.0),
+            point(1.0, 0.0, 0.0),
+        ];
+        let arch = Object::new(
+            Geometry::Curve(Curve::new(identity(), points, (0.2, 0.1))),
+            Material::new(),
+            None,
+        );
+        let ray = |origin, direction| Ray {
+            origin,
+            direction,
+            time: 0.0,
+            wavelength: None,
+        };
+
+        // Through the top, where the curve is 0.15 wide, at twice the speed.
+        let through = ray(point(0.0, 0.75, -5.0), vector(0.0, 0.0, 2.0));
+        let (t1, t2, uv) = Object::intersect(&arch, &through);
+        assert!((t1.unwrap() - 2.4625).abs() < 10e-4);
+        assert!((t2.unwrap() - 2.5375).abs() < 10e-4);
+        assert!((uv.unwrap().0 - 0.5).abs() < 10e-3);
+
+        let under = ray(point(0.0, 0.6, -5.0), vector(0.0, 0.0, 1.0));
+        assert_eq!(Object::intersect(&arch, &under), (None, None, None));
+        let behind = ray(point(0.0, 0.75, 5.0), vector(0.0, 0.0, 1.0));
+        assert_eq!(Object::intersect(&arch, &behind), (None, None, None));
+
+        let top = arch.normal(&point(0.0, 0.8, 0.0), None, 0.0);
+        assert!(top.cmp_epsilon(0.0, 1.0, 0.0, 0.0));
+        let front = arch.normal(&point(0.0, 0.75, -0.075), Some((0.5, 0.0)), 0.0);
+        assert!(front.cmp_epsilon(0.0, 0.0, -1.0, 0.0));
+        let along = arch.tangent(&point(0.0, 0.8, 0.0), 0.0).unwrap();
+        assert!(along.cmp_epsilon(1.0, 0.0, 0.0, 0.0));
+    }
 }
@@ -0,0 +1,24 @@
+use serde::Deserialize;
+
+/// Ambient occlusion: rays cast over the hemisphere around the normal check how much of the
+/// surroundings are open, darkening the ambient light in creases and where objects touch.
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct AmbientOcclusion {
+    /// Rays cast at every hit.
+    pub samples: u32,
+    /// Objects farther than this don't occlude.
+    pub distance: f32,
+    /// Renders the occlusion alone, as grey clay, ignoring materials and lights.
+    pub clay: bool,
+}
+
+impl Default for AmbientOcclusion {
+    fn default() -> Self {
+        AmbientOcclusion {
+            samples: 16,
+            distance: 1.0,
+            clay: false,
+        }
+    }
+}
@@ -1,9 +1,11 @@
 use super::matrix::{identity, Mat};
 use super::objects::Object;
+use super::tonemap::srgb_u8;
 use super::tuple::{color, color_u8, vector, Tup};
 use num_complex::Complex;
 use std::io;
 use std::error::Error;
+use std::sync::Arc;
 
 #[derive(Debug, Clone)]
 pub enum Pattern {
@@ -34,6 +36,9 @@ pub enum UVMapping {
     Spherical,
     Planar,
     Cubical,
+    /// Coordinates of the shape itself for disks, rectangles and models with texture coordinates,
+    /// planar for other shapes.
+    Surface,
 }
 
 #[derive(Debug)]
@@ -72,44 +77,53 @@ impl Pattern {
             Pattern::UV(mapping, pattern) => {
                 let ((u, v), face) = match mapping {
                     UVMapping::Spherical => (Pattern::spherical_map(p), None),
-                    UVMapping::Planar => (Pattern::planar_map(p), None),
+                    UVMapping::Planar | UVMapping::Surface => (Pattern::planar_map(p), None),
                     UVMapping::Cubical => {
                         let face = Pattern::cube_face_at_point(p);
                         let (u, v) = Pattern::cube_map(p, &face);
                         ((u, v), Some(face))
                     }
                 };
-                match pattern {
-                    UVPattern::Checker(color_a, color_b, width, height) => {
-                        match Pattern::uv_checker(*width, *height, u, v) {
-                            TwoColors::ColorA => color_a.clone(),
-                            TwoColors::ColorB => color_b.clone(),
-                        }
-                    }
-                    UVPattern::Image(texture) => Pattern::uv_image(texture, u, v),
-                    UVPattern::CubeImage {
-                        top,
-                        bottom,
-                        left,
-                        right,
-                        front,
-                        back,
-                    } => match face {
-                        None => color(0., 0., 0.),
-                        Some(CubeFace::Top) => Pattern::uv_image(top, u, v),
-                        Some(CubeFace::Bottom) => Pattern::uv_image(bottom, u, v),
-                        Some(CubeFace::Left) => Pattern::uv_image(left, u, v),
-                        Some(CubeFace::Right) => Pattern::uv_image(right, u, v),
-                        Some(CubeFace::Front) => Pattern::uv_image(front, u, v),
-                        Some(CubeFace::Back) => Pattern::uv_image(back, u, v),
-                    },
+                Pattern::uv_pattern(pattern, u, v, face)
+            }
+        }
+    }
+
+    fn uv_pattern(pattern: &UVPattern, u: f32, v: f32, face: Option<CubeFace>) -> Tup {
+        match pattern {
+            UVPattern::Checker(color_a, color_b, width, height) => {
+                match Pattern::uv_checker(*width, *height, u, v) {
+                    TwoColors::ColorA => color_a.clone(),
+                    TwoColors::ColorB => color_b.clone(),
                 }
             }
+            UVPattern::Image(texture) => Pattern::uv_image(texture, u, v),
+            UVPattern::CubeImage {
+                top,
+                bottom,
+                left,
+                right,
+                front,
+                back,
+            } => match face {
+                None => color(0., 0., 0.),
+                Some(CubeFace::Top) => Pattern::uv_image(top, u, v),
+                Some(CubeFace::Bottom) => Pattern::uv_image(bottom, u, v),
+                Some(CubeFace::Left) => Pattern::uv_image(left, u, v),
+                Some(CubeFace::Right) => Pattern::uv_image(right, u, v),
+                Some(CubeFace::Front) => Pattern::uv_image(front, u, v),
+                Some(CubeFace::Back) => Pattern::uv_image(back, u, v),
+            },
         }
     }
 
-    pub fn at_object(&self, o: &Object, p: &Tup) -> Tup {
-        let object_space = &o.transformation().inverse() * p;
+    pub fn at_object(&self, o: &Object, p: &Tup, time: f32) -> Tup {
+        if let Pattern::UV(UVMapping::Surface, pattern) = self {
+            if let Some((u, v)) = o.surface_uv(p, time) {
+                return Pattern::uv_pattern(pattern, u, v, None);
+            }
+        }
+        let object_space = o.world_to_object(p, time);
         self.at_object_local(&object_space)
     }
 
@@ -279,11 +293,23 @@ impl Pattern {
     }
 }
 
+/// How the values stored in a texture are turned into the linear values used for shading.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ColorSpace {
+    /// 8 bit sRGB encoded colours, what most images are saved as.
+    Srgb,
+    /// Colours that are already linear.
+    Linear,
+    /// Data that is not a colour at all, like normal maps. Used as it is.
+    Raw,
+}
+
 #[derive(Clone, Debug)]
 pub struct Texture {
-    image: image::RgbImage,
+    image: Arc<image::RgbImage>,
     width: u32,
     height: u32,
+    color_space: ColorSpace,
 }
 
 impl Texture {
@@ -293,13 +319,33 @@ impl Texture {
         let image = image::load_from_memory(image_data.as_slice())?.to_rgb();
         let width = image.width();
         let height = image.height();
-        Ok(Texture {image, width, height})
+        Ok(Texture {
+            image: Arc::new(image),
+            width,
+            height,
+            color_space: ColorSpace::Srgb,
+        })
+    }
+
+    pub fn with_color_space(mut self, color_space: ColorSpace) -> Self {
+        self.color_space = color_space;
+        self
+    }
+
+    /// Width and height in pixels.
+    pub fn size(&self) -> (u32, u32) {
+        (self.width, self.height)
     }
 
     pub fn color_at(&self, x: u32, y: u32) -> Option<Tup> {
         if x < self.width && y < self.height {
             let pixel = self.image.get_pixel(x, y);
-            Some(color_u8(pixel[0], pixel[1], pixel[2]))
+            match self.color_space {
+                ColorSpace::Srgb => Some(srgb_u8(pixel[0], pixel[1], pixel[2])),
+                ColorSpace::Linear | ColorSpace::Raw => {
+                    Some(color_u8(pixel[0], pixel[1], pixel[2]))
+                }
+            }
         } else {
             None
         }
@@ -405,8 +451,11 @@ mod tests {
                     geometry: Geometry::Sphere(obj),
                     material: Material::new(),
                     normal_map: None,
+                    motion: None,
+                    id: 0,
                 },
                 &point(1.5, 0.0, 0.0),
+                0.0,
             );
             assert_eq!(c, color(1.0, 1.0, 1.0));
         }
@@ -423,8 +472,11 @@ mod tests {
                     geometry: Geometry::Sphere(obj),
                     material: Material::new(),
                     normal_map: None,
+                    motion: None,
+                    id: 0,
                 },
                 &point(1.5, 0.0, 0.0),
+                0.0,
             );
             assert_eq!(c, color(1.0, 1.0, 1.0));
         }
@@ -441,10 +493,35 @@ mod tests {
                     geometry: Geometry::Sphere(obj),
                     material: Material::new(),
                     normal_map: None,
+                    motion: None,
+                    id: 0,
                 },
                 &point(2.5, 0.0, 0.0),
+                0.0,
             );
             assert_eq!(c, color(1.0, 1.0, 1.0));
         }
     }
+
+    #[test]
+    fn texture_color_spaces() {
+        let image = image::RgbImage::from_pixel(1, 1, image::Rgb([188, 255, 0]));
+        let texture = Texture {
+            image: Arc::new(image),
+            width: 1,
+            height: 1,
+            color_space: ColorSpace::Srgb,
+        };
+
+        let c = texture.color_at(0, 0).unwrap();
+        assert!((c.x - 0.5).abs() < 10e-3);
+        assert!((c.y - 1.0).abs() < 10e-5);
+        assert!(c.z.abs() < 10e-5);
+
+        let c = texture
+            .with_color_space(ColorSpace::Raw)
+            .color_at(0, 0)
+            .unwrap();
+        assert_eq!(c, color_u8(188, 255, 0));
+    }
 }
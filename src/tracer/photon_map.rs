@@ -0,0 +1,243 @@
+use super::tuple::{color, dot, Tup};
+use serde::Deserialize;
+
+/// Settings of the photon map used for caustics, light focused on diffuse surfaces by
+/// reflective and transparent objects.
+#[derive(Debug, Deserialize, Clone)]
+#[serde(default)]
+pub struct Caustics {
+    /// Photons shot from every light towards every reflective or transparent object.
+    pub photons: u32,
+    /// Photons gathered around a point to estimate the light reaching it.
+    pub neighbours: usize,
+    /// Largest distance photons are gathered from.
+    pub radius: f32,
+}
+
+impl Default for Caustics {
+    fn default() -> Self {
+        Caustics {
+            photons: 100_000,
+            neighbours: 64,
+            radius: 0.5,
+        }
+    }
+}
+
+/// Light that was reflected or refracted onto a diffuse surface.
+#[derive(Debug, Clone)]
+pub struct Photon {
+    pub position: Tup,
+    /// Direction the photon was travelling in when it landed.
+    pub direction: Tup,
+    pub power: Tup,
+}
+
+/// Photons stored as a balanced kd-tree: every slice of the array has its median photon in the
+/// middle, splitting the rest along `axes` of that photon.
+#[derive(Debug)]
+pub struct PhotonMap {
+    photons: Vec<Photon>,
+    axes: Vec<usize>,
+    neighbours: usize,
+    radius: f32,
+}
+
+impl PhotonMap {
+    pub fn new(mut photons: Vec<Photon>, settings: &Caustics) -> Self {
+        let mut axes = vec![0; photons.len()];
+        build(&mut photons, &mut axes);
+        PhotonMap {
+            photons,
+            axes,
+            neighbours: settings.neighbours.max(1),
+            radius: settings.radius,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.photons.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.photons.is_empty()
+    }
+
+    /// Up to `k` photons closest to the point and within the radius, nearest first, with their
+    /// squared distances.
+    pub fn nearest(&self, p: &Tup, k: usize, radius: f32) -> Vec<(f32, &Photon)> {
+        let mut found = vec![];
+        self.search(0, self.photons.len(), p, k, &mut radius.powi(2), &mut found);
+        found
+            .into_iter()
+            .map(|(d2, i)| (d2, &self.photons[i]))
+            .collect()
+    }
+
+    fn search(
+        &self,
+        lo: usize,
+        hi: usize,
+        p: &Tup,
+        k: usize,
+        max2: &mut f32,
+        found: &mut Vec<(f32, usize)>,
+    ) {
+        if lo >= hi {
+            return;
+        }
+        let mid = (lo + hi) / 2;
+        let photon = &self.photons[mid];
+        let d = coord(p, self.axes[mid]) - coord(&photon.position, self.axes[mid]);
+        let (near, far) = if d < 0.0 {
+            ((lo, mid), (mid + 1, hi))
+        } else {
+            ((mid + 1, hi), (lo, mid))
+        };
+
+        self.search(near.0, near.1, p, k, max2, found);
+
+        let d2 = (p - &photon.position).magnitude().powi(2);
+        if d2 < *max2 {
+            let at = found
+                .iter()
+                .position(|(f, _)| *f > d2)
+                .unwrap_or(found.len());
+            found.insert(at, (d2, mid));
+            found.truncate(k);
+            if found.len() == k {
+                *max2 = found[k - 1].0;
+            }
+        }
+
+        if d * d < *max2 {
+            self.search(far.0, far.1, p, k, max2, found);
+        }
+    }
+
+    /// Light per unit of area arriving at a point on a surface with the given normal, from the
+    /// photons around it that landed on its side of the surface.
+    pub fn irradiance(&self, p: &Tup, normal: &Tup) -> Tup {
+        let found = self.nearest(p, self.neighbours, self.radius);
+        if found.is_empty() {
+            return color(0.0, 0.0, 0.0);
+        }
+
+        // Gathering stopped at the farthest photon if enough were found.
+        let r2 = if found.len() == self.neighbours {
+            found[found.len() - 1].0
+        } else {
+            self.radius.powi(2)
+        };
+
+        let total = found
+            .iter()
+            .filter(|(_, photon)| dot(&photon.direction, normal) < 0.0)
+            .map(|(_, photon)| photon.power.clone())
+            .sum::<Tup>();
+        total / (std::f32::consts::PI * r2.max(10e-8))
+    }
+}
+
+fn coord(p: &Tup, axis: usize) -> f32 {
+    match axis {
+        0 => p.x,
+        1 => p.y,
+        _ => p.z,
+    }
+}
+
+/// Sorts the photons into a kd-tree, splitting every slice along its longest side.
+fn build(photons: &mut [Photon], axes: &mut [usize]) {
+    if photons.len() <= 1 {
+        return;
+    }
+
+    let extent = |axis: usize| {
+        let values = photons.iter().map(|p| coord(&p.position, axis));
+        let min = values.clone().fold(f32::INFINITY, f32::min);
+        let max = values.fold(f32::NEG_INFINITY, f32::max);
+        max - min
+    };
+    let axis = (0..3)
+        .max_by(|a, b| {
+            extent(*a)
+                .partial_cmp(&extent(*b))
+                .unwrap_or(std::cmp::Ordering::Less)
+        })
+        .unwrap_or(0);
+
+    let mid = photons.len() / 2;
+    photons.select_nth_unstable_by(mid, |a, b| {
+        coord(&a.position, axis)
+            .partial_cmp(&coord(&b.position, axis))
+            .unwrap_or(std::cmp::Ordering::Less)
+    });
+    axes[mid] = axis;
+
+    let (left, right) = photons.split_at_mut(mid);
+    let (left_axes, right_axes) = axes.split_at_mut(mid);
+    build(left, left_axes);
+    build(&mut right[1..], &mut right_axes[1..]);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tuple::{point, vector};
+    use super::*;
+    use rand::Rng;
+
+    fn photon(x: f32, y: f32, z: f32, power: f32) -> Photon {
+        Photon {
+            position: point(x, y, z),
+            direction: vector(0.0, -1.0, 0.0),
+            power: color(power, power, power),
+        }
+    }
+
+    #[test]
+    fn finds_the_same_neighbours_as_brute_force() {
+        let mut rng = rand::thread_rng();
+        let photons = (0..500)
+            .map(|_| photon(rng.gen(), rng.gen(), rng.gen(), 1.0))
+            .collect::<Vec<Photon>>();
+        let map = PhotonMap::new(photons.clone(), &Caustics::default());
+
+        let p = point(0.5, 0.4, 0.6);
+        let mut expected = photons
+            .iter()
+            .map(|photon| (&p - &photon.position).magnitude().powi(2))
+            .filter(|d2| *d2 < 0.09)
+            .collect::<Vec<f32>>();
+        expected.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        expected.truncate(10);
+
+        let found = map.nearest(&p, 10, 0.3);
+        assert_eq!(found.len(), expected.len());
+        for ((d2, _), e) in found.iter().zip(expected.iter()) {
+            assert!((d2 - e).abs() < 10e-6);
+        }
+    }
+
+    #[test]
+    fn irradiance_of_evenly_spread_photons() {
+        // One photon of power 0.01 per 0.01 square, landing from above.
+        let photons = (0..100 * 100)
+            .map(|i| photon((i % 100) as f32 * 0.1, 0.0, (i / 100) as f32 * 0.1, 0.01))
+            .collect();
+        let map = PhotonMap::new(
+            photons,
+            &Caustics {
+                photons: 0,
+                neighbours: 200,
+                radius: 1.0,
+            },
+        );
+
+        let up = map.irradiance(&point(5.0, 0.0, 5.0), &vector(0.0, 1.0, 0.0));
+        assert!((up.x - 1.0).abs() < 0.15);
+
+        let down = map.irradiance(&point(5.0, 0.0, 5.0), &vector(0.0, -1.0, 0.0));
+        assert_eq!(down, color(0.0, 0.0, 0.0));
+    }
+}
@@ -0,0 +1,228 @@
+use super::tuple::{color, Tup};
+use rand::Rng;
+use serde::Deserialize;
+
+/// An image space effect, applied to the linear image before tone mapping. Effects are applied
+/// in the order they are listed in the scene file.
+#[derive(Debug, Deserialize, Clone)]
+#[serde(tag = "type")]
+pub enum Effect {
+    /// Glow around pixels brighter than the threshold.
+    Bloom {
+        #[serde(default = "one")]
+        threshold: f32,
+        #[serde(default = "half")]
+        intensity: f32,
+        /// Size of the glow in pixels.
+        #[serde(default = "eight")]
+        radius: f32,
+    },
+    /// Darkens the image towards the corners.
+    Vignette {
+        #[serde(default = "half")]
+        strength: f32,
+    },
+    /// Red and blue are scaled away from the centre of the image, splitting edges into fringes
+    /// towards the corners. The amount is a fraction of the distance to the centre.
+    ChromaticAberration {
+        #[serde(default = "aberration")]
+        amount: f32,
+    },
+    /// Film grain, brightens or darkens every pixel by up to the amount.
+    Grain {
+        #[serde(default = "grain")]
+        amount: f32,
+    },
+}
+
+fn one() -> f32 {
+    1.0
+}
+
+fn half() -> f32 {
+    0.5
+}
+
+fn eight() -> f32 {
+    8.0
+}
+
+fn aberration() -> f32 {
+    0.005
+}
+
+fn grain() -> f32 {
+    0.05
+}
+
+/// Applies the effects in order to an image given in rows of `width` pixels.
+pub fn apply(effects: &[Effect], width: u32, image: Vec<Tup>) -> Vec<Tup> {
+    effects
+        .iter()
+        .fold(image, |image, effect| effect.apply(width, image))
+}
+
+impl Effect {
+    pub fn apply(&self, width: u32, image: Vec<Tup>) -> Vec<Tup> {
+        let (width, height) = (width as usize, image.len() / width as usize);
+
+        match self {
+            Effect::Bloom {
+                threshold,
+                intensity,
+                radius,
+            } => {
+                let bright = image
+                    .iter()
+                    .map(|c| {
+                        let over = |c: f32| (c - threshold).max(0.0);
+                        color(over(c.x), over(c.y), over(c.z))
+                    })
+                    .collect::<Vec<Tup>>();
+                let glow = blur(&bright, width, height, *radius);
+
+                image
+                    .iter()
+                    .zip(glow.iter())
+                    .map(|(c, g)| c + &(g * *intensity))
+                    .collect()
+            }
+            Effect::Vignette { strength } => {
+                let (cx, cy) = (width as f32 / 2.0, height as f32 / 2.0);
+                let corner = (cx * cx + cy * cy).sqrt();
+
+                image
+                    .into_iter()
+                    .enumerate()
+                    .map(|(i, c)| {
+                        let (x, y) = ((i % width) as f32 + 0.5, (i / width) as f32 + 0.5);
+                        let r = ((x - cx).powi(2) + (y - cy).powi(2)).sqrt() / corner;
+                        c * (1.0 - strength * r * r).max(0.0)
+                    })
+                    .collect()
+            }
+            Effect::ChromaticAberration { amount } => {
+                let (cx, cy) = (width as f32 / 2.0, height as f32 / 2.0);
+
+                (0..image.len())
+                    .map(|i| {
+                        let (x, y) = ((i % width) as f32 + 0.5, (i / width) as f32 + 0.5);
+                        let sample = |scale: f32| {
+                            let (sx, sy) = (cx + (x - cx) * scale, cy + (y - cy) * scale);
+                            bilinear(&image, width, height, sx - 0.5, sy - 0.5)
+                        };
+
+                        color(sample(1.0 + amount).x, image[i].y, sample(1.0 - amount).z)
+                    })
+                    .collect()
+            }
+            Effect::Grain { amount } => {
+                let mut rng = rand::thread_rng();
+                image
+                    .into_iter()
+                    .map(|c| c * (1.0 + amount * (rng.gen::<f32>() * 2.0 - 1.0)))
+                    .collect()
+            }
+        }
+    }
+}
+
+/// Separable gaussian blur, the radius is three times the standard deviation.
+fn blur(image: &[Tup], width: usize, height: usize, radius: f32) -> Vec<Tup> {
+    let sigma = (radius / 3.0).max(10e-3);
+    let size = radius.ceil() as i64;
+    let kernel = (-size..=size)
+        .map(|d| (-(d * d) as f32 / (2.0 * sigma * sigma)).exp())
+        .collect::<Vec<f32>>();
+    let total = kernel.iter().sum::<f32>();
+
+    let pass = |image: &[Tup], horizontal: bool| -> Vec<Tup> {
+        (0..image.len())
+            .map(|i| {
+                let (x, y) = ((i % width) as i64, (i / width) as i64);
+                (-size..=size)
+                    .map(|d| {
+                        let (sx, sy) = if horizontal { (x + d, y) } else { (x, y + d) };
+                        let sx = sx.clamp(0, width as i64 - 1) as usize;
+                        let sy = sy.clamp(0, height as i64 - 1) as usize;
+                        &image[sy * width + sx] * (kernel[(d + size) as usize] / total)
+                    })
+                    .sum()
+            })
+            .collect()
+    };
+
+    pass(&pass(image, true), false)
+}
+
+fn bilinear(image: &[Tup], width: usize, height: usize, x: f32, y: f32) -> Tup {
+    let x = x.clamp(0.0, (width - 1) as f32);
+    let y = y.clamp(0.0, (height - 1) as f32);
+    let (x0, y0) = (x.floor() as usize, y.floor() as usize);
+    let (x1, y1) = ((x0 + 1).min(width - 1), (y0 + 1).min(height - 1));
+    let (fx, fy) = (x - x0 as f32, y - y0 as f32);
+
+    let at = |x: usize, y: usize| &image[y * width + x];
+    let top = &(at(x0, y0) * (1.0 - fx)) + &(at(x1, y0) * fx);
+    let bottom = &(at(x0, y1) * (1.0 - fx)) + &(at(x1, y1) * fx);
+    &(top * (1.0 - fy)) + &(bottom * fy)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn effect(yaml: &str) -> Effect {
+        serde_yaml::from_str(yaml).unwrap()
+    }
+
+    #[test]
+    fn bloom_spreads_bright_pixels() {
+        let mut image = vec![color(0.5, 0.5, 0.5); 9 * 9];
+        image[4 * 9 + 4] = color(10.0, 10.0, 10.0);
+
+        let result = effect("{type: Bloom, radius: 2}").apply(9, image.clone());
+
+        assert!(result[4 * 9 + 5].x > 0.5);
+        assert_eq!(result[0], color(0.5, 0.5, 0.5));
+        assert!(result[4 * 9 + 4].x > 10.0);
+    }
+
+    #[test]
+    fn vignette_darkens_corners() {
+        let image = vec![color(1.0, 1.0, 1.0); 10 * 10];
+        let result = effect("{type: Vignette, strength: 1}").apply(10, image);
+
+        assert!(result[5 * 10 + 5].x > 0.95);
+        assert!(result[0].x < 0.2);
+    }
+
+    #[test]
+    fn chromatic_aberration_leaves_the_centre() {
+        let image = (0..11 * 11)
+            .map(|i| color((i % 11) as f32, 1.0, (i % 11) as f32))
+            .collect::<Vec<Tup>>();
+        let result = effect("{type: ChromaticAberration, amount: 0.1}").apply(11, image.clone());
+
+        assert_eq!(result[5 * 11 + 5], image[5 * 11 + 5]);
+        assert!(result[5 * 11 + 10].x > result[5 * 11 + 10].z);
+    }
+
+    #[test]
+    fn effects_apply_in_order() {
+        let mut image = vec![color(0.2, 0.2, 0.2); 16];
+        image[0] = color(3.0, 3.0, 3.0);
+        let effects = vec![
+            effect("{type: Bloom, threshold: 1, intensity: 1, radius: 2}"),
+            effect("{type: Vignette, strength: 1}"),
+        ];
+        let reversed = effects.iter().rev().cloned().collect::<Vec<_>>();
+
+        let result = apply(&effects, 4, image.clone());
+        let result_reversed = apply(&reversed, 4, image);
+        assert!(result
+            .iter()
+            .zip(result_reversed.iter())
+            .any(|(a, b)| (a.x - b.x).abs() > 0.01));
+    }
+}
@@ -6,6 +6,10 @@ use super::tuple::Tup;
 pub struct Ray {
     pub origin: Tup,
     pub direction: Tup,
+    /// Point in time within the camera shutter at which the ray was cast.
+    pub time: f32,
+    /// Wavelength in nanometres the ray is traced at in spectral mode.
+    pub wavelength: Option<f32>,
 }
 
 impl Ray {
@@ -17,6 +21,8 @@ impl Ray {
         Ray {
             origin: m * &self.origin,
             direction: m * &self.direction,
+            time: self.time,
+            wavelength: self.wavelength,
         }
     }
 }
@@ -34,6 +40,8 @@ mod tests {
         let r = Ray {
             origin: point(2.0, 3.0, 4.0),
             direction: vector(1.0, 0.0, 0.0),
+            time: 0.0,
+            wavelength: None,
         };
         assert!(r.position(0.0).cmp_epsilon(2.0, 3.0, 4.0, 1.0));
         assert!(r.position(1.0).cmp_epsilon(3.0, 3.0, 4.0, 1.0));
@@ -46,6 +54,8 @@ mod tests {
         let r = Ray {
             origin: point(0.0, 0.0, -5.0),
             direction: vector(0.0, 0.0, 1.0),
+            time: 0.0,
+            wavelength: None,
         };
         let s = Object::new (Geometry::Sphere(Sphere::default()), Material::new(), None);
         let ixs = Object::intersect(&s, &r);
@@ -59,6 +69,8 @@ mod tests {
         let r = Ray {
             origin: point(0.0, 1.0, -5.0),
             direction: vector(0.0, 0.0, 1.0),
+            time: 0.0,
+            wavelength: None,
         };
         let s = Object::new (Geometry::Sphere(Sphere::default()), Material::new(), None);
         let ixs = Object::intersect(&s, &r);
@@ -72,6 +84,8 @@ mod tests {
         let r = Ray {
             origin: point(0.0, 2.0, -5.0),
             direction: vector(0.0, 0.0, 1.0),
+            time: 0.0,
+            wavelength: None,
         };
         let s = Object::new (Geometry::Sphere(Sphere::default()), Material::new(), None);
         let ixs = Object::intersect(&s, &r);
@@ -84,6 +98,8 @@ mod tests {
         let r = Ray {
             origin: point(0.0, 0.0, 0.0),
             direction: vector(0.0, 0.0, 1.0),
+            time: 0.0,
+            wavelength: None,
         };
         let s = Object::new (Geometry::Sphere(Sphere::default()), Material::new(), None);
         let ixs = Object::intersect(&s, &r);
@@ -97,6 +113,8 @@ mod tests {
         let r = Ray {
             origin: point(0.0, 0.0, 5.0),
             direction: vector(0.0, 0.0, 1.0),
+            time: 0.0,
+            wavelength: None,
         };
         let s = Object::new (Geometry::Sphere(Sphere::default()), Material::new(), None);
         let ixs = Object::intersect(&s, &r);
@@ -112,6 +130,8 @@ mod tests {
         let r = Ray {
             origin: point(1.0, 2.0, 3.0),
             direction: vector(0.0, 1.0, 0.0),
+            time: 0.0,
+            wavelength: None,
         };
         let r = r.transform(&translation(3.0, 4.0, 5.0));
 
@@ -124,6 +144,8 @@ mod tests {
         let r = Ray {
             origin: point(1.0, 2.0, 3.0),
             direction: vector(0.0, 1.0, 0.0),
+            time: 0.0,
+            wavelength: None,
         };
         let r = r.transform(&scaling(2.0, 3.0, 4.0));
 
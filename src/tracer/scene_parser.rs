@@ -1,17 +1,35 @@
-use super::camera::Camera;
-use super::light::{AreaLight, Light, PointLight};
-use super::material::Material;
-use super::matrix;
+use super::animation::Track;
+use super::aov::Pass;
+use super::asset_store::AssetStore;
+use super::camera::{Camera, Projection};
+use super::curve_parser::parse_curves;
+use super::denoise::Denoiser;
+use super::light::{AreaLight, AreaShape, Light, ObjectLight, PointLight};
+use super::light_tree::LightTree;
+use super::material::{Emission, Material, Subsurface};
 use super::matrix::Mat;
-use super::obj_parser::parse_obj;
-use super::objects::{Cube, Geometry, Object, Plane, Sphere, Tri};
+use super::medium::{Fog, Medium};
+use super::motion::{combine, Motion, Transform};
+use super::obj_parser::{Mesh, MAX_SUBDIVISION_LEVELS};
+use super::objects::{
+    Cube, Curve, Disk, Geometry, Heightfield, Object, Plane, Rect, Sdf, Sphere, Tri,
+};
+use super::occlusion::AmbientOcclusion;
 use super::patterns::*;
+use super::photon_map::Caustics;
+use super::postprocess::Effect;
+use super::sdf::Shape;
+use super::spectrum::Dispersion;
+use super::tonemap::{srgb_u8, white_balance, Operator, ToneMapping, Transfer};
 use super::transformations::*;
-use super::tuple::{color, color_u8, point, vector, Tup};
+use super::tuple::{color, point, vector, Tup};
 use super::world::World;
 use serde::Deserialize;
+use std::collections::hash_map::DefaultHasher;
 use std::collections::HashMap;
 use std::error::Error;
+use std::hash::{Hash, Hasher};
+use std::sync::{Arc, Mutex};
 
 #[derive(Debug, Deserialize)]
 struct SceneFile {
@@ -24,6 +42,8 @@ struct SceneFile {
     #[serde(default)]
     rendering: RenderingSpec,
 
+    fog: Option<FogSpec>,
+
     #[serde(default)]
     objects: Vec<ObjectSpec>,
 
@@ -44,6 +64,18 @@ struct SceneFile {
 
     #[serde(default)]
     patterns: HashMap<String, PatternSpec>,
+
+    #[serde(skip)]
+    assets: Arc<Mutex<Assets>>,
+}
+
+/// Textures and models that have already been decoded, shared by every frame of an animation so
+/// that they are only read once. Assets referenced by hash are read from the store.
+#[derive(Debug, Default)]
+struct Assets {
+    store: Option<AssetStore>,
+    textures: HashMap<String, Texture>,
+    models: HashMap<String, Arc<Mesh>>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -76,6 +108,18 @@ pub struct AreaLightSpec {
     pub v_size: [f32; 3],
     pub u_steps: u32,
     pub v_steps: u32,
+    /// Rectangle filling the sizes unless given.
+    pub shape: Option<AreaShapeSpec>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type")]
+pub enum AreaShapeSpec {
+    Rect,
+    Disk {
+        #[serde(default)]
+        inner_radius: f32,
+    },
 }
 
 #[derive(Debug, Deserialize)]
@@ -85,6 +129,23 @@ pub struct RenderingSpec {
     pub randomize_rays: bool,
     pub antialias: u32,
     pub partial_render: Option<Vec<(u32, u32)>>,
+    /// Extra passes to render next to the final image.
+    pub passes: Vec<Pass>,
+    /// Filter applied to the rendered image, disabled unless given.
+    pub denoise: Option<Denoiser>,
+    /// Image effects applied in order after denoising, before tone mapping.
+    pub postprocess: Vec<Effect>,
+    /// Traces every sample at its own wavelength, for dispersion.
+    pub spectral: bool,
+    /// Rays averaged where a ray first hits a rough reflective or transparent surface.
+    pub glossy_samples: u32,
+    /// Photon mapping pre-pass for caustics, disabled unless given.
+    pub caustics: Option<Caustics>,
+    /// Darkens the ambient light in creases, or renders the occlusion alone.
+    pub ambient_occlusion: Option<AmbientOcclusion>,
+    /// Lights picked at random, and shadow rays cast, at every hit. Every light is used when not
+    /// given.
+    pub light_samples: Option<u32>,
 }
 
 impl Default for RenderingSpec {
@@ -94,6 +155,14 @@ impl Default for RenderingSpec {
             randomize_rays: false,
             antialias: 0,
             partial_render: None,
+            passes: vec![],
+            denoise: None,
+            postprocess: vec![],
+            spectral: false,
+            glossy_samples: 8,
+            caustics: None,
+            ambient_occlusion: None,
+            light_samples: None,
         }
     }
 }
@@ -106,7 +175,55 @@ struct CameraSection {
     from: [f32; 3],
     to: [f32; 3],
     up: [f32; 3],
-    gamma: f32,
+    /// Display gamma, the sRGB curve is used when not given.
+    gamma: Option<f32>,
+    /// Exposure compensation in stops.
+    exposure: Option<f32>,
+    white_balance: Option<WhiteBalanceSpec>,
+    tone_mapping: Option<ToneMappingSpec>,
+    projection: Option<ProjectionSpec>,
+    shutter: Option<(f32, f32)>,
+    motion: Option<Vec<CameraKeySpec>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct CameraKeySpec {
+    time: f32,
+    from: [f32; 3],
+    to: [f32; 3],
+    up: Option<[f32; 3]>,
+}
+
+#[derive(Debug, Deserialize)]
+struct MotionKeySpec {
+    time: f32,
+    transform: Vec<TransformSpec>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type")]
+enum ProjectionSpec {
+    Perspective,
+    Orthographic { width: f32 },
+    Fisheye,
+    Equirectangular,
+    CubeMap,
+}
+
+#[derive(Debug, Deserialize)]
+enum ToneMappingSpec {
+    Clip,
+    Reinhard,
+    Aces,
+    Hable,
+}
+
+/// Either a colour temperature in Kelvin or a multiplier for each channel.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum WhiteBalanceSpec {
+    Kelvin(f32),
+    Multipliers(f32, f32, f32),
 }
 
 #[derive(Debug, Deserialize)]
@@ -116,19 +233,48 @@ enum ObjectSpec {
     Plane(PlaneSpec),
     Cube(CubeSpec),
     Tri(TriSpec),
+    Sdf(SdfSpec),
+    Heightfield(HeightfieldSpec),
+    Curve(CurveSpec),
+    Curves(CurvesSpec),
+    Disk(DiskSpec),
+    Rect(RectSpec),
     Model {
         model: ModelSpec,
         material: MaterialSpec,
         transform: Vec<TransformSpec>,
         smooth: bool,
+        subdivision: Option<SubdivisionSpec>,
+        displacement: Option<DisplacementSpec>,
+        motion: Option<Vec<MotionKeySpec>>,
     },
 }
 
+/// Smooths a model by Catmull-Clark subdivision, keeping edges sharper than the crease angle, in
+/// degrees, if one is given. Every level makes four times as many faces, so there are at most
+/// `MAX_SUBDIVISION_LEVELS` of them.
+#[derive(Debug, Deserialize)]
+struct SubdivisionSpec {
+    levels: u32,
+    crease_angle: Option<f32>,
+}
+
+/// Moves the surface of a model out along its normals by the brightness of a pattern, after
+/// splitting its triangles down to the given edge length.
+#[derive(Debug, Deserialize)]
+struct DisplacementSpec {
+    pattern: PatternSpec,
+    #[serde(default = "one")]
+    scale: f32,
+    edge_length: f32,
+}
+
 #[derive(Debug, Deserialize)]
 struct PlaneSpec {
     transform: Vec<TransformSpec>,
     material: MaterialSpec,
     normal_map: Option<PatternSpec>,
+    motion: Option<Vec<MotionKeySpec>>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -136,12 +282,14 @@ struct SphereSpec {
     transform: Vec<TransformSpec>,
     material: MaterialSpec,
     normal_map: Option<PatternSpec>,
+    motion: Option<Vec<MotionKeySpec>>,
 }
 
 #[derive(Debug, Deserialize)]
 struct CubeSpec {
     transform: Vec<TransformSpec>,
     material: MaterialSpec,
+    motion: Option<Vec<MotionKeySpec>>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -152,6 +300,139 @@ struct TriSpec {
     p2: (f32, f32, f32),
     p3: (f32, f32, f32),
     smooth: bool,
+    motion: Option<Vec<MotionKeySpec>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct SdfSpec {
+    transform: Vec<TransformSpec>,
+    material: MaterialSpec,
+    sdf: ShapeSpec,
+    motion: Option<Vec<MotionKeySpec>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct HeightfieldSpec {
+    transform: Vec<TransformSpec>,
+    material: MaterialSpec,
+    /// Greyscale image of the heights, white being as high as the scale.
+    heightmap: TextureSpec,
+    #[serde(default = "one")]
+    scale: f32,
+    motion: Option<Vec<MotionKeySpec>>,
+}
+
+/// Cubic Bézier curve through its first and last control points, with its width at each end.
+#[derive(Debug, Deserialize)]
+struct CurveSpec {
+    transform: Vec<TransformSpec>,
+    material: MaterialSpec,
+    points: [(f32, f32, f32); 4],
+    widths: (f32, f32),
+    motion: Option<Vec<MotionKeySpec>>,
+}
+
+/// Many curves sharing a material, read from a file in the format of `curve_parser`.
+#[derive(Debug, Deserialize)]
+struct CurvesSpec {
+    transform: Vec<TransformSpec>,
+    material: MaterialSpec,
+    curves: CurveFileSpec,
+    motion: Option<Vec<MotionKeySpec>>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum CurveFileSpec {
+    File { path: String },
+    B64 { data: String },
+    Hash { hash: String },
+}
+
+/// Disk of radius one lying flat like a plane, with a hole of the inner radius in the middle.
+#[derive(Debug, Deserialize)]
+struct DiskSpec {
+    transform: Vec<TransformSpec>,
+    material: MaterialSpec,
+    #[serde(default)]
+    inner_radius: f32,
+    motion: Option<Vec<MotionKeySpec>>,
+}
+
+/// Square from -1 to 1 lying flat like a plane.
+#[derive(Debug, Deserialize)]
+struct RectSpec {
+    transform: Vec<TransformSpec>,
+    material: MaterialSpec,
+    motion: Option<Vec<MotionKeySpec>>,
+}
+
+/// Node of a distance function tree, see `sdf::Shape`.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type")]
+enum ShapeSpec {
+    Sphere {
+        #[serde(default = "one")]
+        radius: f32,
+    },
+    Box {
+        size: [f32; 3],
+    },
+    Torus {
+        major: f32,
+        minor: f32,
+    },
+    Capsule {
+        a: [f32; 3],
+        b: [f32; 3],
+        radius: f32,
+    },
+    Mandelbulb {
+        #[serde(default = "mandelbulb_power")]
+        power: f32,
+        #[serde(default = "mandelbulb_iterations")]
+        iterations: u32,
+    },
+    Union {
+        shapes: Vec<ShapeSpec>,
+        #[serde(default)]
+        smoothness: f32,
+    },
+    Subtraction {
+        shape: Box<ShapeSpec>,
+        cut: Box<ShapeSpec>,
+        #[serde(default)]
+        smoothness: f32,
+    },
+    Intersection {
+        shapes: Vec<ShapeSpec>,
+        #[serde(default)]
+        smoothness: f32,
+    },
+    Repeat {
+        shape: Box<ShapeSpec>,
+        period: [f32; 3],
+    },
+    Twist {
+        shape: Box<ShapeSpec>,
+        amount: f32,
+    },
+    Transform {
+        shape: Box<ShapeSpec>,
+        transform: Vec<TransformSpec>,
+    },
+}
+
+fn one() -> f32 {
+    1.0
+}
+
+fn mandelbulb_power() -> f32 {
+    8.0
+}
+
+fn mandelbulb_iterations() -> u32 {
+    12
 }
 
 #[derive(Debug, Deserialize)]
@@ -170,10 +451,18 @@ struct Phong {
     specular: f32,
     shininess: f32,
     reflectiveness: f32,
+    roughness: f32,
     transparency: f32,
     refractive_index: f32,
+    dispersion: Option<DispersionSpec>,
+    absorption: Option<ColorSpec>,
+    absorption_distance: f32,
     pattern: Option<PatternSpec>,
     light_through: bool,
+    subsurface: Option<SubsurfaceSpec>,
+    medium: Option<MediumSpec>,
+    emission: Option<EmissionSpec>,
+    hair: bool,
 }
 
 impl Default for Phong {
@@ -185,14 +474,100 @@ impl Default for Phong {
             specular: 0.9,
             shininess: 200.0,
             reflectiveness: 0.0,
+            roughness: 0.0,
             pattern: None,
             transparency: 0.0,
             refractive_index: 1.0,
+            dispersion: None,
+            absorption: None,
+            absorption_distance: 1.0,
             light_through: false,
+            subsurface: None,
+            medium: None,
+            emission: None,
+            hair: false,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+struct EmissionSpec {
+    color: ColorSpec,
+    strength: f32,
+    pattern: Option<PatternSpec>,
+    light: bool,
+    samples: u32,
+}
+
+impl Default for EmissionSpec {
+    fn default() -> Self {
+        EmissionSpec {
+            color: ColorSpec::Ints(255, 255, 255),
+            strength: 1.0,
+            pattern: None,
+            light: false,
+            samples: 8,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+struct SubsurfaceSpec {
+    color: ColorSpec,
+    mean_free_path: f32,
+    samples: u32,
+}
+
+impl Default for SubsurfaceSpec {
+    fn default() -> Self {
+        SubsurfaceSpec {
+            color: ColorSpec::Floats(0.9, 0.9, 0.9),
+            mean_free_path: 0.2,
+            samples: 8,
         }
     }
 }
 
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type")]
+enum DispersionSpec {
+    Cauchy { a: f32, b: f32 },
+    Sellmeier { b: [f32; 3], c: [f32; 3] },
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+struct MediumSpec {
+    density: f32,
+    color: ColorSpec,
+    anisotropy: f32,
+    steps: u32,
+}
+
+impl Default for MediumSpec {
+    fn default() -> Self {
+        MediumSpec {
+            density: 0.1,
+            color: ColorSpec::Ints(255, 255, 255),
+            anisotropy: 0.0,
+            steps: 16,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct FogSpec {
+    #[serde(flatten)]
+    medium: MediumSpec,
+    #[serde(default)]
+    height: f32,
+    #[serde(default)]
+    falloff: f32,
+    distance: Option<f32>,
+}
+
 #[derive(Debug, Deserialize)]
 #[serde(untagged)]
 pub enum ColorSpec {
@@ -265,14 +640,36 @@ enum UVMappingSpec {
     Spherical,
     Planar,
     Cubical,
+    Surface,
 }
 
 #[derive(Debug, Deserialize)]
 #[serde(untagged)]
 pub enum TextureSpec {
-    Reference { name: String },
-    File { path: String },
-    B64 { data: String },
+    Reference {
+        name: String,
+        color_space: Option<ColorSpaceSpec>,
+    },
+    File {
+        path: String,
+        color_space: Option<ColorSpaceSpec>,
+    },
+    B64 {
+        data: String,
+        color_space: Option<ColorSpaceSpec>,
+    },
+    Hash {
+        hash: String,
+        color_space: Option<ColorSpaceSpec>,
+    },
+}
+
+/// Colour textures are sRGB and normal maps are raw unless told otherwise.
+#[derive(Debug, Deserialize)]
+pub enum ColorSpaceSpec {
+    Srgb,
+    Linear,
+    Raw,
 }
 
 #[derive(Debug, Deserialize)]
@@ -281,6 +678,7 @@ pub enum ModelSpec {
     Reference { name: String },
     File { path: String },
     B64 { data: String },
+    Hash { hash: String },
 }
 
 #[derive(Debug, Deserialize)]
@@ -295,11 +693,70 @@ enum TransformSpec {
     Shearing(f32, f32, f32, f32, f32, f32),
 }
 
+#[derive(Debug, Deserialize, Default)]
+struct AnimationSection {
+    frames: Option<(u32, u32)>,
+
+    #[serde(default)]
+    tracks: Vec<Track>,
+}
+
+/// A scene file with keyframed values, which can be turned into the scene for any frame.
+#[derive(Debug)]
+pub struct Animation {
+    scene: serde_yaml::Value,
+    section: AnimationSection,
+    assets: Arc<Mutex<Assets>>,
+}
+
+impl Animation {
+    pub fn from_reader(r: impl std::io::Read) -> Result<Self, Box<dyn Error>> {
+        let scene: serde_yaml::Value = serde_yaml::from_reader(r)?;
+        let section = match scene.get("animation") {
+            Some(section) => serde_yaml::from_value(section.clone())?,
+            None => AnimationSection::default(),
+        };
+
+        Ok(Animation {
+            scene,
+            section,
+            assets: Arc::default(),
+        })
+    }
+
+    /// Resolves assets referenced by hash from the given store.
+    pub fn with_store(self, store: AssetStore) -> Self {
+        self.assets.lock().unwrap().store = Some(store);
+        self
+    }
+
+    /// First and last frames (both inclusive) declared in the scene file, static scenes have a
+    /// single frame.
+    pub fn frames(&self) -> (u32, u32) {
+        self.section.frames.unwrap_or((1, 1))
+    }
+
+    pub fn frame(&self, frame: u32) -> Result<(World, Camera, RenderingSpec), Box<dyn Error>> {
+        let mut scene = self.scene.clone();
+        for track in self.section.tracks.iter() {
+            track.apply(&mut scene, frame as f32)?;
+        }
+
+        let mut scene: SceneFile = serde_yaml::from_value(scene)?;
+        scene.assets = self.assets.clone();
+        build(scene)
+    }
+}
+
+/// Reads a scene file, animated scenes are read at their first frame.
 pub fn from_reader(
     r: impl std::io::Read,
 ) -> Result<(World, Camera, RenderingSpec), Box<dyn Error>> {
-    let scene: SceneFile = serde_yaml::from_reader(r)?;
+    let animation = Animation::from_reader(r)?;
+    animation.frame(animation.frames().0)
+}
 
+fn build(scene: SceneFile) -> Result<(World, Camera, RenderingSpec), Box<dyn Error>> {
     let mut world = World::new();
 
     world.lights = scene
@@ -321,6 +778,12 @@ pub fn from_reader(
                         spec.u_steps,
                         f32x3_to_vec(spec.v_size),
                         spec.v_steps,
+                        match spec.shape {
+                            None | Some(AreaShapeSpec::Rect) => AreaShape::Rect,
+                            Some(AreaShapeSpec::Disk { inner_radius }) => AreaShape::Disk {
+                                inner: inner_radius,
+                            },
+                        },
                     );
                     println!("{:#?}", area);
                     Ok(area)
@@ -335,8 +798,26 @@ pub fn from_reader(
         deg2rad(scene.camera.fov),
         scene.rendering.antialias,
         scene.rendering.max_bounces,
-        scene.camera.gamma,
+        scene.camera.gamma.unwrap_or(2.2),
     );
+    camera.tone_mapping = ToneMapping {
+        exposure: scene.camera.exposure.unwrap_or(0.0),
+        white_balance: match scene.camera.white_balance {
+            None => color(1.0, 1.0, 1.0),
+            Some(WhiteBalanceSpec::Kelvin(kelvin)) => white_balance(kelvin),
+            Some(WhiteBalanceSpec::Multipliers(r, g, b)) => color(r, g, b),
+        },
+        operator: match scene.camera.tone_mapping {
+            None | Some(ToneMappingSpec::Clip) => Operator::Clip,
+            Some(ToneMappingSpec::Reinhard) => Operator::Reinhard,
+            Some(ToneMappingSpec::Aces) => Operator::Aces,
+            Some(ToneMappingSpec::Hable) => Operator::Hable,
+        },
+        transfer: match scene.camera.gamma {
+            Some(gamma) => Transfer::Gamma(gamma),
+            None => Transfer::Srgb,
+        },
+    };
     camera.set_transform(view(
         point(
             scene.camera.from[0],
@@ -346,24 +827,66 @@ pub fn from_reader(
         point(scene.camera.to[0], scene.camera.to[1], scene.camera.to[2]),
         vector(scene.camera.up[0], scene.camera.up[1], scene.camera.up[2]),
     ));
+    camera.set_projection(match scene.camera.projection {
+        None | Some(ProjectionSpec::Perspective) => Projection::Perspective,
+        Some(ProjectionSpec::Orthographic { width }) => Projection::Orthographic { width },
+        Some(ProjectionSpec::Fisheye) => Projection::Fisheye,
+        Some(ProjectionSpec::Equirectangular) => Projection::Equirectangular,
+        Some(ProjectionSpec::CubeMap) => Projection::CubeMap,
+    });
+    camera.spectral = scene.rendering.spectral;
+    if let Some((open, close)) = scene.camera.shutter {
+        camera.shutter = (open, close);
+    }
+    if let Some(keys) = &scene.camera.motion {
+        let keys = keys
+            .iter()
+            .map(|key| {
+                let up = key.up.unwrap_or(scene.camera.up);
+                let transform = Transform::View(
+                    f32x3_to_point(key.from),
+                    f32x3_to_point(key.to),
+                    f32x3_to_vec(up),
+                );
+                (key.time, vec![transform])
+            })
+            .collect();
+        camera.set_motion(Motion::new(keys)?);
+    }
 
     world.background_color = scene.process_color(&scene.background_color)?;
+    world.glossy_samples = scene.rendering.glossy_samples;
+    world.ambient_occlusion = scene.rendering.ambient_occlusion.clone();
+    world.fog = match &scene.fog {
+        Some(fog) => Some(Fog {
+            medium: scene.process_medium(&fog.medium)?,
+            height: fog.height,
+            falloff: fog.falloff,
+            distance: fog.distance.unwrap_or(100.0),
+        }),
+        None => None,
+    };
 
     let mut objects: Vec<Object> = Vec::new();
 
     let results: Result<Vec<()>, Box<dyn Error>> = scene
         .objects
         .iter()
-        .map(|spec| match spec {
+        .enumerate()
+        .map(|(index, spec)| match spec {
             ObjectSpec::Sphere(spec) => {
                 let sphere = Sphere::new(scene.process_transformations(&spec.transform)?);
                 objects.push(Object {
                     geometry: Geometry::Sphere(sphere),
-                    material: scene.process_material(&spec.material)?,
+                    material: scene.process_object_material(&spec.material, index)?,
                     normal_map: match &spec.normal_map {
-                        Some(normal_map_spec) => Some(scene.process_pattern(&normal_map_spec)?),
+                        Some(normal_map_spec) => {
+                            Some(scene.process_pattern(&normal_map_spec, ColorSpace::Raw)?)
+                        }
                         None => None,
                     },
+                    motion: scene.process_motion(&spec.motion)?,
+                    id: index as u32 + 1,
                 });
                 Ok(())
             }
@@ -371,11 +894,15 @@ pub fn from_reader(
                 let plane = Plane::new(scene.process_transformations(&spec.transform)?);
                 objects.push(Object {
                     geometry: Geometry::Plane(plane),
-                    material: scene.process_material(&spec.material)?,
+                    material: scene.process_object_material(&spec.material, index)?,
                     normal_map: match &spec.normal_map {
-                        Some(normal_map_spec) => Some(scene.process_pattern(&normal_map_spec)?),
+                        Some(normal_map_spec) => {
+                            Some(scene.process_pattern(&normal_map_spec, ColorSpace::Raw)?)
+                        }
                         None => None,
                     },
+                    motion: scene.process_motion(&spec.motion)?,
+                    id: index as u32 + 1,
                 });
                 Ok(())
             }
@@ -383,8 +910,10 @@ pub fn from_reader(
                 let cube = Cube::new(scene.process_transformations(&spec.transform)?);
                 objects.push(Object {
                     geometry: Geometry::Cube(cube),
-                    material: scene.process_material(&spec.material)?,
+                    material: scene.process_object_material(&spec.material, index)?,
                     normal_map: None,
+                    motion: scene.process_motion(&spec.motion)?,
+                    id: index as u32 + 1,
                 });
                 Ok(())
             }
@@ -395,11 +924,112 @@ pub fn from_reader(
                     point(spec.p2.0, spec.p2.1, spec.p2.2),
                     point(spec.p3.0, spec.p3.1, spec.p3.2),
                     None,
+                    None,
                 );
                 objects.push(Object {
                     geometry: Geometry::Tri(tri),
-                    material: scene.process_material(&spec.material)?,
+                    material: scene.process_object_material(&spec.material, index)?,
+                    normal_map: None,
+                    motion: scene.process_motion(&spec.motion)?,
+                    id: index as u32 + 1,
+                });
+                Ok(())
+            }
+            ObjectSpec::Sdf(spec) => {
+                let sdf = Sdf::new(
+                    scene.process_transformations(&spec.transform)?,
+                    scene.process_shape(&spec.sdf)?,
+                );
+                objects.push(Object {
+                    geometry: Geometry::Sdf(sdf),
+                    material: scene.process_object_material(&spec.material, index)?,
+                    normal_map: None,
+                    motion: scene.process_motion(&spec.motion)?,
+                    id: index as u32 + 1,
+                });
+                Ok(())
+            }
+            ObjectSpec::Heightfield(spec) => {
+                let heightmap = scene.process_texture(&spec.heightmap, ColorSpace::Raw)?;
+                let (width, height) = heightmap.size();
+                if width < 2 || height < 2 {
+                    return Err("heightmaps need at least two pixels across each side".into());
+                }
+                let heightfield = Heightfield::from_texture(
+                    scene.process_transformations(&spec.transform)?,
+                    &heightmap,
+                    spec.scale,
+                );
+                objects.push(Object {
+                    geometry: Geometry::Heightfield(heightfield),
+                    material: scene.process_object_material(&spec.material, index)?,
                     normal_map: None,
+                    motion: scene.process_motion(&spec.motion)?,
+                    id: index as u32 + 1,
+                });
+                Ok(())
+            }
+            ObjectSpec::Curve(spec) => {
+                let p = |(x, y, z): (f32, f32, f32)| point(x, y, z);
+                let curve = Curve::new(
+                    scene.process_transformations(&spec.transform)?,
+                    [
+                        p(spec.points[0]),
+                        p(spec.points[1]),
+                        p(spec.points[2]),
+                        p(spec.points[3]),
+                    ],
+                    spec.widths,
+                );
+                objects.push(Object {
+                    geometry: Geometry::Curve(curve),
+                    material: scene.process_object_material(&spec.material, index)?,
+                    normal_map: None,
+                    motion: scene.process_motion(&spec.motion)?,
+                    id: index as u32 + 1,
+                });
+                Ok(())
+            }
+            ObjectSpec::Curves(spec) => {
+                let curves = scene.process_curves(&spec.curves, &spec.transform)?;
+                let motion = scene.process_motion(&spec.motion)?;
+                let material = scene.process_object_material(&spec.material, index)?;
+                for curve in curves.into_iter() {
+                    objects.push(Object {
+                        geometry: Geometry::Curve(curve),
+                        material: material.clone(),
+                        normal_map: None,
+                        motion: motion.clone(),
+                        id: index as u32 + 1,
+                    });
+                }
+                Ok(())
+            }
+            ObjectSpec::Disk(spec) => {
+                if !(0.0..1.0).contains(&spec.inner_radius) {
+                    return Err("the inner radius of a disk has to be from 0 up to 1".into());
+                }
+                let disk = Disk::new(
+                    scene.process_transformations(&spec.transform)?,
+                    spec.inner_radius,
+                );
+                objects.push(Object {
+                    geometry: Geometry::Disk(disk),
+                    material: scene.process_object_material(&spec.material, index)?,
+                    normal_map: None,
+                    motion: scene.process_motion(&spec.motion)?,
+                    id: index as u32 + 1,
+                });
+                Ok(())
+            }
+            ObjectSpec::Rect(spec) => {
+                let rect = Rect::new(scene.process_transformations(&spec.transform)?);
+                objects.push(Object {
+                    geometry: Geometry::Rect(rect),
+                    material: scene.process_object_material(&spec.material, index)?,
+                    normal_map: None,
+                    motion: scene.process_motion(&spec.motion)?,
+                    id: index as u32 + 1,
                 });
                 Ok(())
             }
@@ -408,13 +1038,21 @@ pub fn from_reader(
                 material,
                 transform,
                 smooth,
+                subdivision,
+                displacement,
+                motion,
             } => {
-                let tris = scene.process_model(model, transform, *smooth)?;
+                let tris =
+                    scene.process_model(model, transform, *smooth, subdivision, displacement)?;
+                let motion = scene.process_motion(motion)?;
+                let material = scene.process_object_material(material, index)?;
                 for tri in tris.into_iter() {
                     objects.push(Object {
                         geometry: Geometry::Tri(tri),
-                        material: scene.process_material(&material)?,
+                        material: material.clone(),
                         normal_map: None,
+                        motion: motion.clone(),
+                        id: index as u32 + 1,
                     });
                 }
                 Ok(())
@@ -426,44 +1064,111 @@ pub fn from_reader(
         Ok(_) => Ok(objects),
         Err(error) => Err(error),
     }?;
+    world.lights.extend(object_lights(&world.objects));
+    world.light_tree = scene
+        .rendering
+        .light_samples
+        .map(|samples| LightTree::new(&world.lights, samples));
+    world.caustics = match &scene.rendering.caustics {
+        Some(caustics) => Some(world.emit_photons(caustics, scene.rendering.max_bounces)),
+        None => None,
+    };
 
     Ok((world, camera, scene.rendering))
 }
 
+/// Lights for the emissive objects that are meant to light the scene, one for each scene object.
+fn object_lights(objects: &[Object]) -> Vec<Light> {
+    let mut ids = objects
+        .iter()
+        .filter(|o| matches!(&o.material.emission, Some(e) if e.light))
+        .map(|o| o.id)
+        .collect::<Vec<u32>>();
+    ids.dedup();
+
+    ids.into_iter()
+        .map(|id| {
+            let parts = objects
+                .iter()
+                .filter(|o| o.id == id)
+                .cloned()
+                .collect::<Vec<Object>>();
+            let emission = parts[0].material.emission.as_ref().unwrap();
+            let position = parts.iter().map(|o| o.center()).sum::<Tup>() / parts.len() as f32;
+            Light::Object(ObjectLight {
+                position,
+                color: &emission.color * emission.strength,
+                samples: emission.samples,
+                parts,
+            })
+        })
+        .collect()
+}
+
 impl SceneFile {
     fn process_transformations(&self, t: &[TransformSpec]) -> Result<Mat, Box<dyn Error>> {
-        let mut m = matrix::identity();
+        Ok(combine(&self.expand_transformations(t)?))
+    }
+
+    fn expand_transformations(
+        &self,
+        t: &[TransformSpec],
+    ) -> Result<Vec<Transform>, Box<dyn Error>> {
+        let mut transforms = Vec::with_capacity(t.len());
 
         for transform in t.iter() {
-            m = m * self.process_transform(transform)?;
+            transforms.extend(self.expand_transform(transform)?);
         }
 
-        Ok(m)
+        Ok(transforms)
     }
 
-    fn process_transform(&self, t: &TransformSpec) -> Result<Mat, Box<dyn Error>> {
+    fn expand_transform(&self, t: &TransformSpec) -> Result<Vec<Transform>, Box<dyn Error>> {
         match t {
             TransformSpec::Group(name) => match self.groups.get(name) {
-                Some(transforms) => Ok(self.process_transformations(transforms)?),
+                Some(transforms) => Ok(self.expand_transformations(transforms)?),
                 None => {
                     Err(format!("could not find transformation group with name '{}'", name).into())
                 }
             },
-            TransformSpec::Translation(x, y, z) => Ok(translation(*x, *y, *z)),
-            TransformSpec::RotateX(deg) => Ok(rotate_x(deg2rad(*deg))),
-            TransformSpec::RotateY(deg) => Ok(rotate_y(deg2rad(*deg))),
-            TransformSpec::RotateZ(deg) => Ok(rotate_z(deg2rad(*deg))),
-            TransformSpec::Rotate(x, y, z) => {
-                Ok(rotate_z(deg2rad(*z)) * rotate_y(deg2rad(*y)) * rotate_x(deg2rad(*x)))
-            }
-            TransformSpec::Scaling(x, y, z) => Ok(scaling(*x, *y, *z)),
+            TransformSpec::Translation(x, y, z) => Ok(vec![Transform::Translation(*x, *y, *z)]),
+            TransformSpec::RotateX(deg) => Ok(vec![Transform::RotateX(deg2rad(*deg))]),
+            TransformSpec::RotateY(deg) => Ok(vec![Transform::RotateY(deg2rad(*deg))]),
+            TransformSpec::RotateZ(deg) => Ok(vec![Transform::RotateZ(deg2rad(*deg))]),
+            TransformSpec::Rotate(x, y, z) => Ok(vec![
+                Transform::RotateZ(deg2rad(*z)),
+                Transform::RotateY(deg2rad(*y)),
+                Transform::RotateX(deg2rad(*x)),
+            ]),
+            TransformSpec::Scaling(x, y, z) => Ok(vec![Transform::Scaling(*x, *y, *z)]),
             TransformSpec::Shearing(xy, xz, yx, yz, zx, zy) => {
-                Ok(shearing(*xy, *xz, *yx, *yz, *zx, *zy))
+                Ok(vec![Transform::Shearing(*xy, *xz, *yx, *yz, *zx, *zy)])
+            }
+        }
+    }
+
+    fn process_motion(
+        &self,
+        keys: &Option<Vec<MotionKeySpec>>,
+    ) -> Result<Option<Motion>, Box<dyn Error>> {
+        match keys {
+            Some(keys) => {
+                let keys = keys
+                    .iter()
+                    .map(|key| Ok((key.time, self.expand_transformations(&key.transform)?)))
+                    .collect::<Result<Vec<(f32, Vec<Transform>)>, Box<dyn Error>>>()?;
+                Ok(Some(Motion::new(keys)?))
             }
+            None => Ok(None),
         }
     }
 
-    fn process_pattern(&self, spec: &PatternSpec) -> Result<Pattern, Box<dyn Error>> {
+    /// Textures in the pattern that don't say which colour space they are in are read as `space`.
+    fn process_pattern(
+        &self,
+        spec: &PatternSpec,
+        space: ColorSpace,
+    ) -> Result<Pattern, Box<dyn Error>> {
         match spec {
             PatternSpec::Stripe {
                 color_a,
@@ -506,6 +1211,7 @@ impl SceneFile {
                     UVMappingSpec::Spherical => UVMapping::Spherical,
                     UVMappingSpec::Planar => UVMapping::Planar,
                     UVMappingSpec::Cubical => UVMapping::Cubical,
+                    UVMappingSpec::Surface => UVMapping::Surface,
                 };
                 let pattern = match pattern {
                     UVPatternSpec::Checker {
@@ -520,7 +1226,7 @@ impl SceneFile {
                         *height,
                     ),
                     UVPatternSpec::Image { texture } => {
-                        let texture = self.process_texture(texture)?;
+                        let texture = self.process_texture(texture, space)?;
                         UVPattern::Image(texture)
                     }
                     UVPatternSpec::CubeImage {
@@ -531,12 +1237,12 @@ impl SceneFile {
                         front,
                         back,
                     } => {
-                        let top = self.process_texture(top)?;
-                        let bottom = self.process_texture(bottom)?;
-                        let left = self.process_texture(left)?;
-                        let right = self.process_texture(right)?;
-                        let front = self.process_texture(front)?;
-                        let back = self.process_texture(back)?;
+                        let top = self.process_texture(top, space)?;
+                        let bottom = self.process_texture(bottom, space)?;
+                        let left = self.process_texture(left, space)?;
+                        let right = self.process_texture(right, space)?;
+                        let front = self.process_texture(front, space)?;
+                        let back = self.process_texture(back, space)?;
                         UVPattern::CubeImage {
                             top,
                             bottom,
@@ -570,7 +1276,7 @@ impl SceneFile {
                 },
             )),
             PatternSpec::Reference { name } => match self.patterns.get(name) {
-                Some(name) => Ok(self.process_pattern(name)?),
+                Some(name) => Ok(self.process_pattern(name, space)?),
                 None => Err(format!("could not find pattern with name '{}'", name).into()),
             },
         }
@@ -586,6 +1292,25 @@ impl SceneFile {
         }
     }
 
+    /// Gives the material of an object an id: named materials are numbered in name order and
+    /// inline ones after them, one for each object.
+    fn process_object_material(
+        &self,
+        spec: &MaterialSpec,
+        index: usize,
+    ) -> Result<Material, Box<dyn Error>> {
+        let mut material = self.process_material(spec)?;
+        material.id = match spec {
+            MaterialSpec::Reference(name) => {
+                let mut names = self.materials.keys().collect::<Vec<&String>>();
+                names.sort();
+                names.iter().position(|n| *n == name).unwrap_or(0) + 1
+            }
+            MaterialSpec::Phong(_) => self.materials.len() + index + 1,
+        } as u32;
+        Ok(material)
+    }
+
     fn phong_to_material(&self, p: &Phong) -> Result<Material, Box<dyn Error>> {
         Ok(Material {
             color: self.process_color(&p.color)?,
@@ -594,37 +1319,175 @@ impl SceneFile {
             specular: p.specular,
             shininess: p.shininess,
             reflectiveness: p.reflectiveness,
+            roughness: p.roughness,
             pattern: match &p.pattern {
-                Some(p) => Some(self.process_pattern(&p)?),
+                Some(p) => Some(self.process_pattern(&p, ColorSpace::Srgb)?),
                 None => None,
             },
             transparency: p.transparency,
             refractive_index: p.refractive_index,
+            dispersion: match &p.dispersion {
+                Some(DispersionSpec::Cauchy { a, b }) => Some(Dispersion::Cauchy { a: *a, b: *b }),
+                Some(DispersionSpec::Sellmeier { b, c }) => {
+                    Some(Dispersion::Sellmeier { b: *b, c: *c })
+                }
+                None => None,
+            },
+            absorption: match &p.absorption {
+                Some(c) => self.process_color(c)?,
+                None => color(1.0, 1.0, 1.0),
+            },
+            absorption_distance: p.absorption_distance,
             light_through: p.light_through,
+            subsurface: match &p.subsurface {
+                Some(s) => Some(Subsurface {
+                    color: self.process_color(&s.color)?,
+                    mean_free_path: s.mean_free_path,
+                    samples: s.samples,
+                }),
+                None => None,
+            },
+            medium: match &p.medium {
+                Some(m) => Some(self.process_medium(m)?),
+                None => None,
+            },
+            emission: match &p.emission {
+                Some(e) => Some(Emission {
+                    color: self.process_color(&e.color)?,
+                    strength: e.strength,
+                    pattern: match &e.pattern {
+                        Some(p) => Some(self.process_pattern(p, ColorSpace::Srgb)?),
+                        None => None,
+                    },
+                    light: e.light,
+                    samples: e.samples,
+                }),
+                None => None,
+            },
+            hair: p.hair,
+            id: 0,
+        })
+    }
+
+    fn process_shape(&self, spec: &ShapeSpec) -> Result<Shape, Box<dyn Error>> {
+        let boxed = |spec: &ShapeSpec| -> Result<Box<Shape>, Box<dyn Error>> {
+            Ok(Box::new(self.process_shape(spec)?))
+        };
+        let all = |specs: &[ShapeSpec]| -> Result<Vec<Shape>, Box<dyn Error>> {
+            specs.iter().map(|s| self.process_shape(s)).collect()
+        };
+
+        Ok(match spec {
+            ShapeSpec::Sphere { radius } => Shape::Sphere { radius: *radius },
+            ShapeSpec::Box { size } => Shape::Box {
+                size: f32x3_to_vec(*size),
+            },
+            ShapeSpec::Torus { major, minor } => Shape::Torus {
+                major: *major,
+                minor: *minor,
+            },
+            ShapeSpec::Capsule { a, b, radius } => Shape::Capsule {
+                a: f32x3_to_point(*a),
+                b: f32x3_to_point(*b),
+                radius: *radius,
+            },
+            ShapeSpec::Mandelbulb { power, iterations } => Shape::Mandelbulb {
+                power: *power,
+                iterations: *iterations,
+            },
+            ShapeSpec::Union { shapes, smoothness } => Shape::Union {
+                shapes: all(shapes)?,
+                smoothness: *smoothness,
+            },
+            ShapeSpec::Subtraction {
+                shape,
+                cut,
+                smoothness,
+            } => Shape::Subtraction {
+                shape: boxed(shape)?,
+                cut: boxed(cut)?,
+                smoothness: *smoothness,
+            },
+            ShapeSpec::Intersection { shapes, smoothness } => Shape::Intersection {
+                shapes: all(shapes)?,
+                smoothness: *smoothness,
+            },
+            ShapeSpec::Repeat { shape, period } => Shape::Repeat {
+                shape: boxed(shape)?,
+                period: f32x3_to_vec(*period),
+            },
+            ShapeSpec::Twist { shape, amount } => Shape::Twist {
+                shape: boxed(shape)?,
+                amount: *amount,
+            },
+            ShapeSpec::Transform { shape, transform } => Shape::transformed(
+                self.process_shape(shape)?,
+                self.process_transformations(transform)?,
+            ),
+        })
+    }
+
+    fn process_medium(&self, m: &MediumSpec) -> Result<Medium, Box<dyn Error>> {
+        Ok(Medium {
+            density: m.density,
+            color: self.process_color(&m.color)?,
+            anisotropy: m.anisotropy,
+            steps: m.steps,
         })
     }
 
     fn process_color(&self, c: &ColorSpec) -> Result<Tup, Box<dyn Error>> {
         match c {
-            ColorSpec::Ints(r, g, b) => Ok(color_u8(*r, *g, *b)),
+            ColorSpec::Ints(r, g, b) => Ok(srgb_u8(*r, *g, *b)),
             ColorSpec::Floats(r, g, b) => Ok(color(*r, *g, *b)),
             ColorSpec::Reference(name) => match self.colors.get(name) {
                 Some(color) => Ok(self.process_color(color)?),
                 None => Err(format!("could not find material with name '{}'", name).into()),
             },
-            ColorSpec::Hex(hex) => Ok(color_u8((*hex >> 16) as u8, (*hex >> 8) as u8, *hex as u8)),
+            ColorSpec::Hex(hex) => Ok(srgb_u8((*hex >> 16) as u8, (*hex >> 8) as u8, *hex as u8)),
         }
     }
 
-    fn process_texture(&self, t: &TextureSpec) -> Result<Texture, Box<dyn Error>> {
-        match t {
-            TextureSpec::File { path } => Ok(Texture::read(std::fs::File::open(path)?)?),
-            TextureSpec::B64 { data } => Ok(Texture::read(base64::decode(data)?.as_slice())?),
-            TextureSpec::Reference { name } => match self.textures.get(name) {
-                Some(texture) => Ok(self.process_texture(texture)?),
+    fn process_texture(
+        &self,
+        t: &TextureSpec,
+        space: ColorSpace,
+    ) -> Result<Texture, Box<dyn Error>> {
+        let own = self.color_space(t);
+        let space = own.unwrap_or(space);
+        let texture = match t {
+            TextureSpec::File { path, .. } => self.cached_texture(format!("file:{}", path), || {
+                Texture::read(std::fs::File::open(path)?)
+            }),
+            TextureSpec::B64 { data, .. } => self.cached_texture(data_key(data), || {
+                Texture::read(base64::decode(data)?.as_slice())
+            }),
+            TextureSpec::Hash { hash, .. } => self.cached_texture(format!("hash:{}", hash), || {
+                Texture::read(self.stored_asset(hash)?.as_slice())
+            }),
+            TextureSpec::Reference { name, .. } => match self.textures.get(name) {
+                // The definition decides, unless a space is given where the texture is used.
+                Some(texture) if own.is_none() => return self.process_texture(texture, space),
+                Some(texture) => self.process_texture(texture, space),
                 None => Err(format!("could not find texture with name '{}'", name).into()),
             },
-        }
+        }?;
+
+        Ok(texture.with_color_space(space))
+    }
+
+    fn color_space(&self, t: &TextureSpec) -> Option<ColorSpace> {
+        let space = match t {
+            TextureSpec::Reference { color_space, .. }
+            | TextureSpec::File { color_space, .. }
+            | TextureSpec::B64 { color_space, .. }
+            | TextureSpec::Hash { color_space, .. } => color_space,
+        };
+        space.as_ref().map(|space| match space {
+            ColorSpaceSpec::Srgb => ColorSpace::Srgb,
+            ColorSpaceSpec::Linear => ColorSpace::Linear,
+            ColorSpaceSpec::Raw => ColorSpace::Raw,
+        })
     }
 
     fn process_model(
@@ -632,24 +1495,123 @@ impl SceneFile {
         m: &ModelSpec,
         t: &[TransformSpec],
         smooth: bool,
+        subdivision: &Option<SubdivisionSpec>,
+        displacement: &Option<DisplacementSpec>,
     ) -> Result<Vec<Tri>, Box<dyn Error>> {
+        let mut mesh = self.process_mesh(m)?;
+        if let Some(subdivision) = subdivision {
+            if subdivision.levels > MAX_SUBDIVISION_LEVELS {
+                return Err(format!(
+                    "subdivision levels must be at most {}",
+                    MAX_SUBDIVISION_LEVELS
+                )
+                .into());
+            }
+            mesh = Arc::new(mesh.subdivided(subdivision.levels, subdivision.crease_angle));
+        }
+        let transform = self.process_transformations(t)?;
+        match displacement {
+            Some(displacement) => {
+                if displacement.edge_length <= 0.0 {
+                    return Err("displacement edge_length must be greater than zero".into());
+                }
+                let pattern = self.process_pattern(&displacement.pattern, ColorSpace::Raw)?;
+                let height = |p: &Tup| {
+                    let c = pattern.at_object_local(p);
+                    (c.x + c.y + c.z) / 3.0 * displacement.scale
+                };
+                Ok(mesh
+                    .displaced(displacement.edge_length, height)
+                    .tris(transform, smooth))
+            }
+            None => Ok(mesh.tris(transform, smooth)),
+        }
+    }
+
+    fn process_mesh(&self, m: &ModelSpec) -> Result<Arc<Mesh>, Box<dyn Error>> {
         match m {
-            ModelSpec::File { path } => Ok(parse_obj(
-                std::fs::File::open(path)?,
-                self.process_transformations(t)?,
-                smooth,
-            )?),
-            ModelSpec::B64 { data } => Ok(parse_obj(
-                base64::decode(data)?.as_slice(),
-                self.process_transformations(t)?,
-                smooth,
-            )?),
+            ModelSpec::File { path } => self.cached_mesh(format!("file:{}", path), || {
+                Mesh::read(std::fs::File::open(path)?)
+            }),
+            ModelSpec::B64 { data } => self.cached_mesh(data_key(data), || {
+                Mesh::read(base64::decode(data)?.as_slice())
+            }),
+            ModelSpec::Hash { hash } => self.cached_mesh(format!("hash:{}", hash), || {
+                Mesh::read(self.stored_asset(hash)?.as_slice())
+            }),
             ModelSpec::Reference { name } => match self.models.get(name) {
-                Some(model) => Ok(self.process_model(model, t, smooth)?),
+                Some(model) => self.process_mesh(model),
                 None => Err(format!("could not find model with name '{}'", name).into()),
             },
         }
     }
+
+    fn process_curves(
+        &self,
+        c: &CurveFileSpec,
+        t: &[TransformSpec],
+    ) -> Result<Vec<Curve>, Box<dyn Error>> {
+        let transform = self.process_transformations(t)?;
+        match c {
+            CurveFileSpec::File { path } => parse_curves(std::fs::File::open(path)?, transform),
+            CurveFileSpec::B64 { data } => {
+                parse_curves(base64::decode(data)?.as_slice(), transform)
+            }
+            CurveFileSpec::Hash { hash } => {
+                parse_curves(self.stored_asset(hash)?.as_slice(), transform)
+            }
+        }
+    }
+
+    fn stored_asset(&self, hash: &str) -> Result<Vec<u8>, Box<dyn Error>> {
+        match &self.assets.lock().unwrap().store {
+            Some(store) => store.get(hash),
+            None => Err(format!(
+                "asset '{}' is referenced by hash but there is no asset store",
+                hash
+            )
+            .into()),
+        }
+    }
+
+    fn cached_texture(
+        &self,
+        key: String,
+        read: impl FnOnce() -> Result<Texture, Box<dyn Error>>,
+    ) -> Result<Texture, Box<dyn Error>> {
+        if let Some(texture) = self.assets.lock().unwrap().textures.get(&key) {
+            return Ok(texture.clone());
+        }
+
+        let texture = read()?;
+        self.assets
+            .lock()
+            .unwrap()
+            .textures
+            .insert(key, texture.clone());
+        Ok(texture)
+    }
+
+    fn cached_mesh(
+        &self,
+        key: String,
+        read: impl FnOnce() -> Result<Mesh, Box<dyn Error>>,
+    ) -> Result<Arc<Mesh>, Box<dyn Error>> {
+        if let Some(mesh) = self.assets.lock().unwrap().models.get(&key) {
+            return Ok(mesh.clone());
+        }
+
+        let mesh = Arc::new(read()?);
+        self.assets.lock().unwrap().models.insert(key, mesh.clone());
+        Ok(mesh)
+    }
+}
+
+/// Cache key for inline base64 assets, hashing keeps the cache from holding a copy of the data.
+fn data_key(data: &str) -> String {
+    let mut hasher = DefaultHasher::new();
+    data.hash(&mut hasher);
+    format!("data:{:016x}:{}", hasher.finish(), data.len())
 }
 
 fn deg2rad(a: f32) -> f32 {
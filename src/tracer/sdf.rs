@@ -0,0 +1,312 @@
+use super::matrix::Mat;
+use super::tuple::{dot, point, vector, Tup};
+
+/// Signed distance function built from primitives and operations on them: negative inside the
+/// surface, positive outside and zero on it. Primitives are centred on the origin.
+#[derive(Debug, Clone)]
+pub enum Shape {
+    Sphere {
+        radius: f32,
+    },
+    /// Box with the given half extents.
+    Box {
+        size: Tup,
+    },
+    /// Ring around the y axis.
+    Torus {
+        major: f32,
+        minor: f32,
+    },
+    /// Segment from a to b with rounded ends.
+    Capsule {
+        a: Tup,
+        b: Tup,
+        radius: f32,
+    },
+    Mandelbulb {
+        power: f32,
+        iterations: u32,
+    },
+    /// Union of the shapes, blended over the smoothness distance.
+    Union {
+        shapes: Vec<Shape>,
+        smoothness: f32,
+    },
+    /// The shape with the cut carved out of it.
+    Subtraction {
+        shape: Box<Shape>,
+        cut: Box<Shape>,
+        smoothness: f32,
+    },
+    Intersection {
+        shapes: Vec<Shape>,
+        smoothness: f32,
+    },
+    /// Copies of the shape every period along each axis, zero for no copies along that axis.
+    Repeat {
+        shape: Box<Shape>,
+        period: Tup,
+    },
+    /// Rotates the shape around the y axis by the amount, in radians per unit of height.
+    Twist {
+        shape: Box<Shape>,
+        amount: f32,
+    },
+    Transformed {
+        shape: Box<Shape>,
+        transform: Mat,
+        inverse: Mat,
+        /// Largest factor the transformation stretches distances by.
+        stretch: f32,
+        /// Smallest factor it shrinks them by, so distances measured in the shape's own space
+        /// can be brought back without overestimating them.
+        shrink: f32,
+    },
+}
+
+impl Shape {
+    pub fn transformed(shape: Shape, transform: Mat) -> Self {
+        Shape::Transformed {
+            shape: Box::new(shape),
+            inverse: transform.inverse(),
+            stretch: stretch(&transform),
+            shrink: 1.0 / stretch(&transform.inverse()),
+            transform,
+        }
+    }
+
+    /// Distance from the point to the surface, never more than the actual distance for shapes
+    /// that only give an estimate (twists, fractals, non-uniform scaling) so that sphere tracing
+    /// can't step through the surface.
+    pub fn distance(&self, p: &Tup) -> f32 {
+        match self {
+            Shape::Sphere { radius } => (p - &point(0.0, 0.0, 0.0)).magnitude() - radius,
+            Shape::Box { size } => {
+                let q = vector(p.x.abs() - size.x, p.y.abs() - size.y, p.z.abs() - size.z);
+                let outside = vector(q.x.max(0.0), q.y.max(0.0), q.z.max(0.0)).magnitude();
+                outside + q.x.max(q.y).max(q.z).min(0.0)
+            }
+            Shape::Torus { major, minor } => {
+                let ring = (p.x * p.x + p.z * p.z).sqrt() - major;
+                (ring * ring + p.y * p.y).sqrt() - minor
+            }
+            Shape::Capsule { a, b, radius } => {
+                let (pa, ba) = (p - a, b - a);
+                let h = (dot(&pa, &ba) / dot(&ba, &ba)).clamp(0.0, 1.0);
+                (&pa - &(&ba * h)).magnitude() - radius
+            }
+            Shape::Mandelbulb { power, iterations } => mandelbulb(p, *power, *iterations),
+            Shape::Union { shapes, smoothness } => shapes
+                .iter()
+                .map(|s| s.distance(p))
+                .reduce(|a, b| smooth_min(a, b, *smoothness))
+                .unwrap_or(f32::INFINITY),
+            Shape::Subtraction {
+                shape,
+                cut,
+                smoothness,
+            } => -smooth_min(-shape.distance(p), cut.distance(p), *smoothness),
+            Shape::Intersection { shapes, smoothness } => -shapes
+                .iter()
+                .map(|s| -s.distance(p))
+                .reduce(|a, b| smooth_min(a, b, *smoothness))
+                .unwrap_or(f32::INFINITY),
+            Shape::Repeat { shape, period } => {
+                let wrap = |x: f32, period: f32| {
+                    if period > 0.0 {
+                        x - period * (x / period).round()
+                    } else {
+                        x
+                    }
+                };
+                shape.distance(&point(
+                    wrap(p.x, period.x),
+                    wrap(p.y, period.y),
+                    wrap(p.z, period.z),
+                ))
+            }
+            Shape::Twist { shape, amount } => {
+                let (sin, cos) = (amount * p.y).sin_cos();
+                let q = point(cos * p.x - sin * p.z, p.y, sin * p.x + cos * p.z);
+                // The twist stretches space more the farther it is from the axis.
+                let r = (p.x * p.x + p.z * p.z).sqrt();
+                shape.distance(&q) / (1.0 + (amount * r).powi(2)).sqrt()
+            }
+            Shape::Transformed {
+                shape,
+                inverse,
+                shrink,
+                ..
+            } => shape.distance(&(inverse * p)) * shrink,
+        }
+    }
+
+    /// Radius of a sphere around the origin that holds the whole shape, infinite for repeated
+    /// shapes.
+    pub fn bound(&self) -> f32 {
+        match self {
+            Shape::Sphere { radius } => *radius,
+            Shape::Box { size } => size.magnitude(),
+            Shape::Torus { major, minor } => major + minor,
+            Shape::Capsule { a, b, radius } => {
+                let origin = point(0.0, 0.0, 0.0);
+                (a - &origin).magnitude().max((b - &origin).magnitude()) + radius
+            }
+            Shape::Mandelbulb { .. } => 1.5,
+            Shape::Union { shapes, smoothness } => {
+                shapes.iter().map(|s| s.bound()).fold(0.0, f32::max) + smoothness
+            }
+            Shape::Subtraction { shape, .. } => shape.bound(),
+            Shape::Intersection { shapes, .. } => shapes
+                .iter()
+                .map(|s| s.bound())
+                .fold(f32::INFINITY, f32::min),
+            Shape::Repeat { shape, period } => {
+                if period.x > 0.0 || period.y > 0.0 || period.z > 0.0 {
+                    f32::INFINITY
+                } else {
+                    shape.bound()
+                }
+            }
+            Shape::Twist { shape, .. } => shape.bound(),
+            Shape::Transformed {
+                shape,
+                transform,
+                stretch,
+                ..
+            } => {
+                let origin = point(0.0, 0.0, 0.0);
+                (&(transform * &origin) - &origin).magnitude() + shape.bound() * stretch
+            }
+        }
+    }
+}
+
+/// Polynomial smooth minimum, the plain minimum when k is zero.
+fn smooth_min(a: f32, b: f32, k: f32) -> f32 {
+    if k <= 0.0 {
+        return a.min(b);
+    }
+    let h = (0.5 + 0.5 * (b - a) / k).clamp(0.0, 1.0);
+    b * (1.0 - h) + a * h - k * h * (1.0 - h)
+}
+
+/// Largest factor the matrix stretches a vector by, found by power iteration.
+fn stretch(m: &Mat) -> f32 {
+    let transpose = m.transpose();
+    let mut v = vector(1.0, 0.7, 0.4).normalize();
+    for _ in 0..16 {
+        let mut next = &transpose * &(m * &v);
+        next.w = 0.0;
+        if next.magnitude() == 0.0 {
+            return 0.0;
+        }
+        v = next.normalize();
+    }
+    let mut stretched = m * &v;
+    stretched.w = 0.0;
+    stretched.magnitude()
+}
+
+/// Distance estimate of the Mandelbulb fractal.
+fn mandelbulb(p: &Tup, power: f32, iterations: u32) -> f32 {
+    let mut z = p.clone();
+    let (mut dr, mut r) = (1.0, 0.0);
+
+    for _ in 0..iterations {
+        r = (&z - &point(0.0, 0.0, 0.0)).magnitude();
+        if r > 2.0 {
+            break;
+        }
+        let theta = (z.z / r.max(10e-8)).clamp(-1.0, 1.0).acos() * power;
+        let phi = z.y.atan2(z.x) * power;
+        dr = r.powf(power - 1.0) * power * dr + 1.0;
+
+        let zr = r.powf(power);
+        z = point(
+            p.x + zr * theta.sin() * phi.cos(),
+            p.y + zr * theta.sin() * phi.sin(),
+            p.z + zr * theta.cos(),
+        );
+    }
+
+    0.5 * r.max(10e-8).ln() * r / dr
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::transformations::{scaling, translation};
+    use super::*;
+
+    fn sphere() -> Shape {
+        Shape::Sphere { radius: 1.0 }
+    }
+
+    #[test]
+    fn primitive_distances() {
+        let p = point(3.0, 0.0, 0.0);
+        assert!((sphere().distance(&p) - 2.0).abs() < 10e-5);
+
+        let cube = Shape::Box {
+            size: vector(1.0, 2.0, 1.0),
+        };
+        assert!((cube.distance(&p) - 2.0).abs() < 10e-5);
+        assert!((cube.distance(&point(0.0, 0.0, 0.0)) + 1.0).abs() < 10e-5);
+
+        let torus = Shape::Torus {
+            major: 2.0,
+            minor: 0.5,
+        };
+        assert!((torus.distance(&p) - 0.5).abs() < 10e-5);
+        assert!(torus.distance(&point(0.0, 0.0, 0.0)) > 1.0);
+
+        let capsule = Shape::Capsule {
+            a: point(0.0, -1.0, 0.0),
+            b: point(0.0, 1.0, 0.0),
+            radius: 0.5,
+        };
+        assert!((capsule.distance(&point(0.0, 3.0, 0.0)) - 1.5).abs() < 10e-5);
+    }
+
+    #[test]
+    fn combining_shapes() {
+        let apart = |x| Shape::transformed(sphere(), translation(x, 0.0, 0.0));
+        let union = |smoothness| Shape::Union {
+            shapes: vec![apart(-1.5), apart(1.5)],
+            smoothness,
+        };
+        let between = point(0.0, 0.0, 0.0);
+        assert!((union(0.0).distance(&between) - 0.5).abs() < 10e-5);
+        assert!(union(1.0).distance(&between) < 0.5);
+
+        let hollow = Shape::Subtraction {
+            shape: Box::new(sphere()),
+            cut: Box::new(Shape::Sphere { radius: 0.5 }),
+            smoothness: 0.0,
+        };
+        assert!(hollow.distance(&between) > 0.0);
+        assert!(hollow.distance(&point(0.75, 0.0, 0.0)) < 0.0);
+
+        let repeated = Shape::Repeat {
+            shape: Box::new(Shape::Sphere { radius: 0.5 }),
+            period: vector(4.0, 0.0, 0.0),
+        };
+        assert!(repeated.distance(&point(40.0, 0.0, 0.0)) < 0.0);
+        assert!(repeated.bound().is_infinite());
+    }
+
+    #[test]
+    fn scaling_keeps_distances_safe() {
+        let stretched = Shape::transformed(sphere(), scaling(3.0, 1.0, 1.0));
+        assert!((stretched.distance(&point(5.0, 0.0, 0.0)) - 2.0 / 3.0).abs() < 10e-4);
+        assert!(stretched.distance(&point(0.0, 3.0, 0.0)) <= 2.0);
+        assert!((stretched.bound() - 3.0).abs() < 10e-4);
+
+        let bulb = Shape::Mandelbulb {
+            power: 8.0,
+            iterations: 8,
+        };
+        assert!(bulb.distance(&point(0.0, 0.0, 0.0)) < 10e-3);
+        assert!(bulb.distance(&point(3.0, 0.0, 0.0)) > 0.5);
+    }
+}
@@ -0,0 +1,95 @@
+use super::tuple::{color, Tup};
+
+/// Visible range sampled in spectral mode, in nanometres.
+pub const WAVELENGTHS: (f32, f32) = (380.0, 780.0);
+
+/// Wavelength the refractive index of dispersive materials is given at outside spectral mode,
+/// the helium d line.
+pub const REFERENCE_WAVELENGTH: f32 = 587.6;
+
+/// How the refractive index of a material changes with the wavelength of light. Both formulas
+/// take the wavelength in micrometres, as their coefficients are usually published that way.
+#[derive(Debug, Clone)]
+pub enum Dispersion {
+    /// `n = a + b / λ²`
+    Cauchy { a: f32, b: f32 },
+    /// `n² = 1 + Σ bᵢλ² / (λ² - cᵢ)`
+    Sellmeier { b: [f32; 3], c: [f32; 3] },
+}
+
+impl Dispersion {
+    pub fn refractive_index(&self, wavelength: f32) -> f32 {
+        let l2 = (wavelength / 1000.0).powi(2);
+        match self {
+            Dispersion::Cauchy { a, b } => a + b / l2,
+            Dispersion::Sellmeier { b, c } => (1.0
+                + b.iter()
+                    .zip(c.iter())
+                    .map(|(b, c)| b * l2 / (l2 - c))
+                    .sum::<f32>())
+            .sqrt(),
+        }
+    }
+}
+
+/// Weight of a wavelength in each RGB channel. A colour traced at wavelengths spread evenly over
+/// the visible range and multiplied by their weights averages out to the same colour, so only
+/// dispersion splits light into a rainbow.
+pub fn weight(wavelength: f32) -> Tup {
+    let (x, y, z) = cie_xyz(wavelength);
+
+    // Linear sRGB of each wavelength, divided by its average over the visible range.
+    color(
+        (3.2406 * x - 1.5372 * y - 0.4986 * z) / 0.320_906_7,
+        (-0.9689 * x + 1.8758 * y + 0.0415 * z) / 0.253_871_6,
+        (0.0557 * x - 0.2040 * y + 1.0570 * z) / 0.242_623_9,
+    )
+}
+
+/// CIE 1931 colour matching functions, multi-lobe fit by Wyman, Sloan and Shirley.
+fn cie_xyz(l: f32) -> (f32, f32, f32) {
+    let g = |mu: f32, below: f32, above: f32| {
+        let sigma = if l < mu { below } else { above };
+        (-0.5 * ((l - mu) / sigma).powi(2)).exp()
+    };
+
+    (
+        1.056 * g(599.8, 37.9, 31.0) + 0.362 * g(442.0, 16.0, 26.7) - 0.065 * g(501.1, 20.4, 26.2),
+        0.821 * g(568.8, 46.9, 40.5) + 0.286 * g(530.9, 16.3, 31.1),
+        1.217 * g(437.0, 11.8, 36.0) + 0.681 * g(459.0, 26.0, 13.8),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn weights_average_to_white() {
+        let n = 4000;
+        let (low, high) = WAVELENGTHS;
+        let total = (0..n)
+            .map(|i| weight(low + (high - low) * (i as f32 + 0.5) / n as f32) / n as f32)
+            .sum::<Tup>();
+
+        assert!((total.x - 1.0).abs() < 10e-3);
+        assert!((total.y - 1.0).abs() < 10e-3);
+        assert!((total.z - 1.0).abs() < 10e-3);
+        assert!(weight(450.0).z > weight(450.0).x);
+        assert!(weight(650.0).x > weight(650.0).z);
+    }
+
+    #[test]
+    fn glass_bends_blue_more_than_red() {
+        // BK7 crown glass.
+        let bk7 = Dispersion::Sellmeier {
+            b: [1.039_612, 0.231_792_34, 1.010_469_5],
+            c: [0.006_000_699, 0.020_017_914, 103.560_65],
+        };
+        assert!((bk7.refractive_index(REFERENCE_WAVELENGTH) - 1.5168).abs() < 10e-4);
+        assert!(bk7.refractive_index(450.0) > bk7.refractive_index(650.0));
+
+        let cauchy = Dispersion::Cauchy { a: 1.5, b: 0.004 };
+        assert!((cauchy.refractive_index(500.0) - 1.516).abs() < 10e-5);
+    }
+}
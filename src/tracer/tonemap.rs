@@ -0,0 +1,191 @@
+use super::tuple::{color, Tup};
+
+/// Curve that compresses the unbounded light of a render into [0, 1].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Operator {
+    /// Leaves the values as they are, anything over 1 is clipped by the output.
+    Clip,
+    Reinhard,
+    /// Filmic curve fitted to the ACES reference rendering transform (Krzysztof Narkowicz).
+    Aces,
+    /// Filmic curve from Uncharted 2 (John Hable).
+    Hable,
+}
+
+/// Encoding of the tone mapped values for display.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Transfer {
+    /// Plain power curve, `c^(1/gamma)`.
+    Gamma(f32),
+    /// The sRGB OETF: linear near black, then a 2.4 power curve.
+    Srgb,
+}
+
+/// Turns the linear light of a render into display values: exposure and white balance are
+/// applied first, then the tone mapping operator and finally the transfer function.
+#[derive(Debug, Clone)]
+pub struct ToneMapping {
+    /// Exposure compensation in stops, every stop doubles the light.
+    pub exposure: f32,
+    /// Multiplier for each channel.
+    pub white_balance: Tup,
+    pub operator: Operator,
+    pub transfer: Transfer,
+}
+
+impl ToneMapping {
+    /// Only a gamma curve, which is what the camera used to do.
+    pub fn gamma(gamma: f32) -> Self {
+        ToneMapping {
+            exposure: 0.0,
+            white_balance: color(1.0, 1.0, 1.0),
+            operator: Operator::Clip,
+            transfer: Transfer::Gamma(gamma),
+        }
+    }
+
+    pub fn apply(&self, c: &Tup) -> Tup {
+        let exposed = &(c * 2f32.powf(self.exposure)) * &self.white_balance;
+
+        let map = |c: f32| {
+            let c = match self.operator {
+                Operator::Clip => c,
+                Operator::Reinhard => c / (1.0 + c),
+                Operator::Aces => aces(c),
+                Operator::Hable => (hable(c * 2.0) / hable(11.2)).clamp(0.0, 1.0),
+            };
+
+            match self.transfer {
+                Transfer::Gamma(gamma) => c.powf(1. / gamma),
+                Transfer::Srgb => srgb_oetf(c),
+            }
+        };
+
+        color(map(exposed.x), map(exposed.y), map(exposed.z))
+    }
+}
+
+fn aces(c: f32) -> f32 {
+    let (a, b, c_, d, e) = (2.51, 0.03, 2.43, 0.59, 0.14);
+    ((c * (a * c + b)) / (c * (c_ * c + d) + e)).clamp(0.0, 1.0)
+}
+
+fn hable(c: f32) -> f32 {
+    let (a, b, c_, d, e, f) = (0.15, 0.50, 0.10, 0.20, 0.02, 0.30);
+    ((c * (a * c + c_ * b) + d * e) / (c * (a * c + b) + d * f)) - e / f
+}
+
+pub fn srgb_oetf(c: f32) -> f32 {
+    if c <= 0.003_130_8 {
+        12.92 * c
+    } else {
+        1.055 * c.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+/// Inverse of the sRGB OETF, turns sRGB encoded values back into linear light.
+pub fn srgb_eotf(c: f32) -> f32 {
+    if c <= 0.040_45 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// Decodes an 8 bit sRGB colour into linear light.
+pub fn srgb_u8(r: u8, g: u8, b: u8) -> Tup {
+    let c = |c: u8| srgb_eotf(c as f32 / 255.0);
+    color(c(r), c(g), c(b))
+}
+
+/// Channel multipliers that make light of the given colour temperature (in Kelvin) look white,
+/// relative to daylight at 6500K. Like on a camera, lower temperatures cool the image down and
+/// higher ones warm it up.
+pub fn white_balance(kelvin: f32) -> Tup {
+    let reference = blackbody(6500.0);
+    let target = blackbody(kelvin);
+    color(
+        reference.x / target.x,
+        reference.y / target.y,
+        reference.z / target.z,
+    )
+}
+
+/// Approximate colour of a black body at the given temperature (Tanner Helland's fit).
+fn blackbody(kelvin: f32) -> Tup {
+    let t = kelvin.clamp(1000.0, 40000.0) / 100.0;
+
+    let r = if t <= 66.0 {
+        255.0
+    } else {
+        329.698_73 * (t - 60.0).powf(-0.133_204_76)
+    };
+    let g = if t <= 66.0 {
+        99.470_8 * t.ln() - 161.119_57
+    } else {
+        288.122_16 * (t - 60.0).powf(-0.075_514_85)
+    };
+    let b = if t >= 66.0 {
+        255.0
+    } else if t <= 19.0 {
+        0.0
+    } else {
+        138.517_73 * (t - 10.0).ln() - 305.044_8
+    };
+
+    let c = |c: f32| srgb_eotf(c.clamp(1.0, 255.0) / 255.0);
+    color(c(r), c(g), c(b))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gamma_only_matches_a_power_curve() {
+        let t = ToneMapping::gamma(2.0);
+        assert_eq!(t.apply(&color(0.25, 1.0, 4.0)), color(0.5, 1.0, 2.0));
+    }
+
+    #[test]
+    fn operators_keep_bright_values_in_range() {
+        for operator in [Operator::Reinhard, Operator::Aces, Operator::Hable].iter() {
+            let t = ToneMapping {
+                operator: *operator,
+                transfer: Transfer::Srgb,
+                ..ToneMapping::gamma(1.0)
+            };
+            let bright = t.apply(&color(2.8, 10.0, 100.0));
+            let dark = t.apply(&color(0.01, 0.01, 0.01));
+
+            assert!(bright.x < bright.y && bright.y <= bright.z);
+            assert!(bright.z <= 1.0 + 10e-6);
+            assert!(dark.x > 0.0 && dark.x < bright.x);
+        }
+    }
+
+    #[test]
+    fn exposure_doubles_light_per_stop() {
+        let t = ToneMapping {
+            exposure: 2.0,
+            ..ToneMapping::gamma(1.0)
+        };
+        assert_eq!(t.apply(&color(0.1, 0.2, 0.25)), color(0.4, 0.8, 1.0));
+    }
+
+    #[test]
+    fn srgb_round_trip() {
+        for c in [0.0, 0.002, 0.2, 0.5, 1.0].iter() {
+            assert!((srgb_eotf(srgb_oetf(*c)) - c).abs() < 10e-5);
+        }
+    }
+
+    #[test]
+    fn white_balance_of_daylight_is_neutral() {
+        let wb = white_balance(6500.0);
+        assert!((wb.x - 1.0).abs() < 10e-5 && (wb.y - 1.0).abs() < 10e-5);
+
+        let tungsten = white_balance(3200.0);
+        assert!(tungsten.z > tungsten.x);
+    }
+}
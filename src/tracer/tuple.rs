@@ -265,6 +265,18 @@ pub fn cross(a: &Tup, b: &Tup) -> Tup {
     )
 }
 
+/// Two unit vectors perpendicular to the given one and to each other.
+pub fn basis(n: &Tup) -> (Tup, Tup) {
+    let helper = if n.x.abs() > 0.9 {
+        vector(0.0, 1.0, 0.0)
+    } else {
+        vector(1.0, 0.0, 0.0)
+    };
+    let tangent = cross(&helper, n).normalize();
+    let bitangent = cross(n, &tangent);
+    (tangent, bitangent)
+}
+
 /// Dot product
 pub fn dot(a: &Tup, b: &Tup) -> f32 {
     a.x * b.x + a.y * b.y + a.z * b.z + a.w * b.w
@@ -1,17 +1,34 @@
+use super::aov::Aovs;
 use super::intersections::{hit, Computations, Intersection, Intersections};
 use super::light::*;
-use super::material::Material;
+use super::light_tree::LightTree;
+use super::material::{Material, Subsurface};
+use super::medium::{Fog, Medium};
 use super::objects::{Geometry, Object, Sphere};
+use super::occlusion::AmbientOcclusion;
+use super::photon_map::{Caustics, Photon, PhotonMap};
 use super::ray::Ray;
 use super::transformations::scaling;
-use super::tuple::{color, dot, point, vector, Tup};
+use super::tuple::{basis, color, dot, point, vector, Tup};
 use rand::Rng;
+use std::collections::HashMap;
 
 #[derive(Debug)]
 pub struct World {
     pub objects: Vec<Object>,
     pub lights: Vec<Light>,
     pub background_color: Tup,
+    /// Medium filling the space between objects, none for a vacuum.
+    pub fog: Option<Fog>,
+    /// Rays averaged where a ray first hits a rough reflective or transparent surface.
+    pub glossy_samples: u32,
+    /// Photons focused by reflective and transparent objects, none when caustics are off.
+    pub caustics: Option<PhotonMap>,
+    /// Darkens the ambient light where the surroundings are closed in, off when none.
+    pub ambient_occlusion: Option<AmbientOcclusion>,
+    /// Picks a few lights at random at every hit instead of shading with all of them, none to use
+    /// every light.
+    pub light_tree: Option<LightTree>,
 }
 
 impl World {
@@ -23,6 +40,11 @@ impl World {
                 color: color(1.0, 1.0, 1.0),
             })],
             background_color: color(0.0, 0.0, 0.0),
+            fog: None,
+            glossy_samples: 8,
+            caustics: None,
+            ambient_occlusion: None,
+            light_tree: None,
         }
     }
 
@@ -47,29 +69,27 @@ impl World {
                 color: color(1.0, 1.0, 1.0),
             })],
             background_color: color(0.0, 0.0, 0.0),
+            fog: None,
+            glossy_samples: 8,
+            caustics: None,
+            ambient_occlusion: None,
+            light_tree: None,
         }
     }
 
-    fn intersect(&self, r: &Ray, is_shadow: bool) -> Intersections {
+    fn intersect(&self, r: &Ray) -> Intersections {
         // Generally, objects will return at most 2 intersections, so make space for them.
         let mut i: Intersections = Vec::with_capacity(self.objects.len() * 2);
 
         self.objects
             .iter()
-            .filter(|o| {
-                // If we're looking for intersections to find out whether a point is under shadow,
-                // filter out the objects that are supposed to let light through.
-                if is_shadow {
-                    o.material.light_through != is_shadow
-                } else {
-                    true
-                }
-            })
+            // Objects filled with a medium have no surface of their own, see `media`.
+            .filter(|o| o.material.medium.is_none())
             .for_each(|object| match Object::intersect(&object, r) {
                 (None, None, None) => (),
-                (Some(t1), Some(t2), None) => {
-                    i.push(Intersection::new(t1, object, None));
-                    i.push(Intersection::new(t2, object, None));
+                (Some(t1), Some(t2), uv) => {
+                    i.push(Intersection::new(t1, object, uv));
+                    i.push(Intersection::new(t2, object, uv));
                 }
                 (Some(t), None, uv) => {
                     i.push(Intersection::new(t, object, uv));
@@ -82,121 +102,559 @@ impl World {
         i
     }
 
-    fn shadow_at_point(&self, p: &Tup) -> Tup {
-        self.lights
-            .iter()
-            .map(|light| match &light {
-                Light::Point(light) => {
-                    &light.color * (1. - self.point_shadow_intensity(&light.position, p))
-                }
-                Light::Area(light) => {
-                    &light.color * (1. - self.area_light_shadow_intensity(p, light))
-                }
+    /// Stretches of the ray inside each of the objects that pass the filter, from where the ray
+    /// enters an object to where it leaves it. Surfaces that don't enclose anything, like planes,
+    /// give stretches of no length.
+    fn spans(&self, r: &Ray, filter: impl Fn(&Object) -> bool) -> Vec<(f32, f32, &Object)> {
+        let mut groups: HashMap<(u32, usize), (Vec<f32>, &Object)> = HashMap::new();
+        for (index, object) in self.objects.iter().enumerate().filter(|(_, o)| filter(o)) {
+            let ts = match Object::intersect(object, r) {
+                (Some(t1), Some(t2), _) => vec![t1, t2],
+                (Some(t), None, _) => vec![t],
+                _ => continue,
+            };
+            groups
+                .entry(group(index, object))
+                .or_insert((vec![], object))
+                .0
+                .extend(ts);
+        }
+
+        let mut spans = vec![];
+        for (_, (mut ts, object)) in groups {
+            ts.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Less));
+            for pair in ts.chunks(2) {
+                spans.push((pair[0], pair[pair.len() - 1], object));
+            }
+        }
+        spans
+    }
+
+    /// Points on the light, as many as it takes samples of, each with the colour of the light
+    /// that makes it from there to `p`. Every one of them is shaded on its own, in the same way
+    /// as a single light picked from the light tree, so both give the same image.
+    fn light_samples(&self, light: &Light, p: &Tup, time: f32) -> Vec<(Tup, Tup)> {
+        (0..light.samples())
+            .map(|_| {
+                let from = light.sample_point(p);
+                let visible = self.light_transmittance(&from, p, time);
+                (from, visible)
             })
-            .sum::<Tup>() / (self.lights.len() as f32)
+            .collect()
     }
 
-    fn point_shadow_intensity(&self, light: &Tup, p: &Tup) -> f32 {
+    /// Colour of the light that makes it from `p` to a point on a light. Every transparent object
+    /// on the way lets its transparency through, tinted by what it absorbs over the distance the
+    /// light travels inside it, and media thin the light out further.
+    fn light_transmittance(&self, light: &Tup, p: &Tup, time: f32) -> Tup {
         let v = light - p;
         let distance = v.magnitude();
-        let direction = v.normalize();
-
         let ray = Ray {
             origin: p.clone(),
-            direction,
+            direction: v.normalize(),
+            time,
+            wavelength: None,
         };
 
-        let intersections = &self.intersect(&ray, true);
+        // Objects that are supposed to let light through don't cast shadows at all.
+        let casts_shadow = |o: &Object| !o.material.light_through && o.material.medium.is_none();
+
+        // Only surfaces between the point and the light are in the way, objects around both of
+        // them, like a room, are not. The first opaque object in the way blocks the light, and
+        // the stretches inside transparent objects are only worked out when the light crosses
+        // one. Light through transparent objects is already in the caustics when there are any,
+        // so they block it too.
+        let between = |t: f32| t > 0.0 && t < distance;
+        let mut crossed = false;
+        for object in self.objects.iter().filter(|o| casts_shadow(o)) {
+            let (t1, t2, _) = Object::intersect(object, &ray);
+            if !t1.iter().chain(t2.iter()).any(|t| between(*t)) {
+                continue;
+            }
+            if object.material.transparency == 0.0 || self.caustics.is_some() {
+                return color(0.0, 0.0, 0.0);
+            }
+            crossed = true;
+        }
 
-        match hit(intersections) {
-            (hit, idx, true) => {
-                if hit < distance {
-                    // Make the intensity of the shadow dependant on how transparent the object is.
-                    1.0 - intersections[idx].object.material.transparency
-                } else {
-                    0.0
+        let mut transmittance = color(1.0, 1.0, 1.0);
+        if crossed {
+            let transparent = |o: &Object| casts_shadow(o) && o.material.transparency > 0.0;
+            for (start, stop, object) in self.spans(&ray, transparent) {
+                if !between(start) && !between(stop) {
+                    continue;
                 }
+                let material = &object.material;
+                let inside = stop.min(distance) - start.max(0.0);
+                transmittance = &transmittance * &(material.absorb(inside) * material.transparency);
             }
-            _ => 0.0,
         }
+
+        transmittance * self.media_transmittance(p, light, time)
     }
 
-    fn area_light_shadow_intensity(&self, p: &Tup, light: &AreaLight) -> f32 {
-        let mut total = 0.0;
-        let mut rng = rand::thread_rng();
+    fn media_transmittance(&self, from: &Tup, to: &Tup, time: f32) -> f32 {
+        let v = to - from;
+        let distance = v.magnitude();
+        let ray = Ray {
+            origin: from.clone(),
+            direction: v.normalize(),
+            time,
+            wavelength: None,
+        };
 
-        for v in 1..=light.vsteps {
-            for u in 1..=light.usteps {
-                let light_position =
-                    light.point_on(u, v, rng.gen_range(0.8, 1.2), rng.gen_range(0.8, 1.2));
+        let mut depth = self
+            .media_intervals(&ray, distance)
+            .iter()
+            .map(|(start, stop, medium)| medium.density * (stop - start))
+            .sum::<f32>();
+        if let Some(fog) = &self.fog {
+            depth += fog.optical_depth(from, to);
+        }
+        (-depth).exp()
+    }
 
-                total += self.point_shadow_intensity(&light_position, p)
+    /// Stretches of the ray, clipped to [0, end], that go through objects filled with a medium.
+    fn media_intervals(&self, r: &Ray, end: f32) -> Vec<(f32, f32, &Medium)> {
+        self.spans(r, |o| o.material.medium.is_some())
+            .into_iter()
+            .filter_map(|(start, stop, object)| {
+                let (start, stop) = (start.max(0.0), stop.min(end));
+                match &object.material.medium {
+                    Some(medium) if start < stop => Some((start, stop, medium)),
+                    _ => None,
+                }
+            })
+            .collect()
+    }
+
+    /// Ray marches the fog and the objects filled with a medium between the origin of the ray
+    /// and `end`. Returns how much of the light coming from `end` makes it through, and the
+    /// light of the scene's lights scattered towards the origin on the way.
+    fn media(&self, r: &Ray, end: f32) -> (f32, Tup) {
+        let intervals = self.media_intervals(r, end);
+        if intervals.is_empty() && self.fog.is_none() {
+            return (1.0, color(0.0, 0.0, 0.0));
+        }
+
+        let fog_end = match &self.fog {
+            Some(fog) => end.min(fog.distance),
+            None => 0.0,
+        };
+        let mut bounds = vec![0.0, fog_end];
+        for (start, stop, _) in intervals.iter() {
+            bounds.push(*start);
+            bounds.push(*stop);
+        }
+        bounds.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Less));
+        bounds.dedup();
+
+        let speed = r.direction.magnitude();
+        let direction = r.direction.normalize();
+        let mut rng = rand::thread_rng();
+        let mut transmittance = 1.0;
+        let mut scattered = color(0.0, 0.0, 0.0);
+
+        // Between two bounds the same media are crossed all along.
+        for bound in bounds.windows(2) {
+            let (a, b) = (bound[0], bound[1]);
+            let middle = (a + b) / 2.0;
+            let fog = self.fog.as_ref().filter(|_| middle < fog_end);
+            let volumes = intervals
+                .iter()
+                .filter(|(start, stop, _)| *start <= middle && middle <= *stop)
+                .map(|(_, _, medium)| *medium)
+                .collect::<Vec<&Medium>>();
+
+            let steps = volumes
+                .iter()
+                .map(|m| m.steps)
+                .chain(fog.map(|f| f.medium.steps))
+                .max();
+            let steps = match steps {
+                Some(steps) => steps.max(1),
+                None => continue,
+            };
+            let dt = (b - a) / steps as f32;
+
+            for step in 0..steps {
+                let p = r.position(a + (step as f32 + rng.gen::<f32>()) * dt);
+                let media = volumes
+                    .iter()
+                    .map(|m| (*m, m.density))
+                    .chain(fog.map(|f| (&f.medium, f.density_at(&p))))
+                    .collect::<Vec<(&Medium, f32)>>();
+
+                let inscattered = self
+                    .lights
+                    .iter()
+                    .map(|light| {
+                        let position = light.sample_point(&p);
+                        let cos = dot(&(&position - &p).normalize(), &direction);
+                        let visible =
+                            light.color() * &self.light_transmittance(&position, &p, r.time);
+
+                        media
+                            .iter()
+                            .map(|(m, density)| &(&visible * &m.color) * (density * m.phase(cos)))
+                            .sum::<Tup>()
+                    })
+                    .sum::<Tup>();
+
+                let distance = dt * speed;
+                scattered = scattered + inscattered * (transmittance * distance);
+                transmittance *= (-media.iter().map(|(_, d)| d).sum::<f32>() * distance).exp();
             }
         }
 
-        total / light.samples as f32
+        (transmittance, scattered)
     }
 
     pub fn color_at(&self, r: &Ray, depth_remaining: u32) -> Tup {
-        let intersections = self.intersect(r, false);
+        self.trace(r, depth_remaining, self.glossy_samples).0
+    }
 
-        match hit(&intersections) {
-            (_, _, false) => self.background_color.clone(),
-            (_, i, true) => self.shade_hit(
-                &intersections[i].computations(&r, Some(&intersections)),
-                depth_remaining,
+    /// Colour seen along the ray, along with the distance to the surface it comes from, which is
+    /// infinite for the background. `samples` is the number of rays rough surfaces average, the
+    /// rays they spawn only take one sample each so that the number of rays doesn't explode.
+    fn trace(&self, r: &Ray, depth_remaining: u32, samples: u32) -> (Tup, f32) {
+        let intersections = self.intersect(r);
+
+        let (t, surface) = match hit(&intersections) {
+            (_, _, false) => (f32::INFINITY, self.background_color.clone()),
+            (t, i, true) => (
+                t,
+                self.shade_hit(
+                    &intersections[i].computations(r, Some(&intersections)),
+                    depth_remaining,
+                    samples,
+                ),
             ),
-        }
+        };
+
+        let (transmittance, scattered) = self.media(r, t);
+        (surface * transmittance + scattered, t)
+    }
+
+    /// Same as color_at, also returning the passes of the first surface hit.
+    pub fn color_and_aovs_at(&self, r: &Ray, depth_remaining: u32) -> (Tup, Aovs) {
+        let intersections = self.intersect(r);
+
+        let mut aovs = match hit(&intersections) {
+            (_, _, false) => Aovs::background(self.background_color.clone()),
+            (_, i, true) => {
+                let c = intersections[i].computations(r, Some(&intersections));
+                let (direct, reflection, refraction) =
+                    self.shade(&c, depth_remaining, self.glossy_samples);
+
+                Aovs {
+                    depth: c.t,
+                    normal: c.normal.clone(),
+                    albedo: c.object.material.color_at(c.object, &c.over_point, c.time),
+                    object_id: c.object.id,
+                    material_id: c.object.material.id,
+                    direct,
+                    reflection,
+                    refraction,
+                }
+            }
+        };
+
+        // Light scattered by media counts as direct light.
+        let (transmittance, scattered) = self.media(r, aovs.depth);
+        aovs.direct = &aovs.direct * transmittance + scattered;
+        aovs.reflection = &aovs.reflection * transmittance;
+        aovs.refraction = &aovs.refraction * transmittance;
+
+        (&(&aovs.direct + &aovs.reflection) + &aovs.refraction, aovs)
+    }
+
+    fn shade_hit(&self, c: &Computations, depth_remaining: u32, samples: u32) -> Tup {
+        let (direct, reflection, refraction) = self.shade(c, depth_remaining, samples);
+        direct + reflection + refraction
     }
 
-    fn shade_hit(&self, c: &Computations, depth_remaining: u32) -> Tup {
+    /// Light leaving the surface, split into what comes straight from the lights and what comes
+    /// through reflection and refraction, already weighted as they are added up.
+    fn shade(&self, c: &Computations, depth_remaining: u32, samples: u32) -> (Tup, Tup, Tup) {
         let reflectiveness = c.object.material.reflectiveness;
         let transparency = c.object.material.transparency;
 
-        let shadow_color = self.shadow_at_point(&c.over_point); //TODO: - transparency;
+        let ambient_occlusion = match &self.ambient_occlusion {
+            Some(settings) => self.occlusion(c, settings),
+            None => 1.0,
+        };
+        if let Some(AmbientOcclusion { clay: true, .. }) = &self.ambient_occlusion {
+            let clay = color(ambient_occlusion, ambient_occlusion, ambient_occlusion);
+            return (clay, color(0.0, 0.0, 0.0), color(0.0, 0.0, 0.0));
+        }
 
-        let surface = c.object.material.lighting(
-            &(*c.object),
-            &self.lights,
-            c.over_point.clone(),
-            c.eye.clone(),
-            c.normal.clone(),
-            shadow_color,
-        );
+        let surface = match &self.light_tree {
+            Some(tree) if self.lights.len() > tree.shadow_rays as usize => {
+                self.sampled_lighting(c, tree, ambient_occlusion)
+            }
+            _ => {
+                let visible = self
+                    .lights
+                    .iter()
+                    .map(|light| self.light_samples(light, &c.over_point, c.time))
+                    .collect::<Vec<_>>();
+                c.object.material.lighting(
+                    &(*c.object),
+                    &self.lights,
+                    c.over_point.clone(),
+                    c.eye.clone(),
+                    c.normal.clone(),
+                    &visible,
+                    ambient_occlusion,
+                    c.time,
+                )
+            }
+        };
+
+        let surface = surface
+            + c.object
+                .material
+                .emission_at(c.object, &c.over_point, c.time);
+
+        let surface = match &self.caustics {
+            Some(caustics) => {
+                let albedo = c.object.material.color_at(c.object, &c.over_point, c.time);
+                surface
+                    + &(&caustics.irradiance(&c.point, &c.normal) * &albedo)
+                        * c.object.material.diffuse
+            }
+            None => surface,
+        };
+
+        let surface = match &c.object.material.subsurface {
+            Some(subsurface) => {
+                let albedo = c.object.material.color_at(c.object, &c.over_point, c.time);
+                surface + &(&self.subsurface(c, subsurface) * &albedo) * c.object.material.diffuse
+            }
+            None => surface,
+        };
 
-        let refracted = self.refracted_color(&c, depth_remaining);
-        let reflected = self.reflected_color(&c, depth_remaining);
+        let refracted = self.refracted_color(c, depth_remaining, samples);
+        let reflected = self.reflected_color(c, depth_remaining, samples);
 
         if reflectiveness > 0.0 && transparency > 0.0 {
             let reflectance = c.schlick();
-            surface + (reflected * reflectance) + (refracted * (1.0 - reflectance))
+            (
+                surface,
+                reflected * reflectance,
+                refracted * (1.0 - reflectance),
+            )
         } else {
-            surface + reflected + refracted
+            (surface, reflected, refracted)
         }
     }
 
-    fn reflected_color(&self, c: &Computations, depth_remaining: u32) -> Tup {
+    /// Lighting of the point by a few lights picked from the tree, each weighted by the inverse of
+    /// the probability it was picked with so the result averages out to the light of all of them.
+    /// Ambient light is cheap and comes from every light.
+    fn sampled_lighting(&self, c: &Computations, tree: &LightTree, ambient_occlusion: f32) -> Tup {
+        let mut rng = rand::thread_rng();
+        let material = &c.object.material;
+        let object_color = material.color_at(c.object, &c.over_point, c.time);
+        let ambient = &(&object_color * tree.color()) * (material.ambient * ambient_occlusion);
+        let rays = tree.shadow_rays.max(1);
+        let tangent = if material.hair {
+            c.object.tangent(&c.over_point, c.time)
+        } else {
+            None
+        };
+        // Fibres are lit from all around, so no light is left out for being behind the normal.
+        let normal = if material.hair { None } else { Some(&c.normal) };
+
+        (0..rays)
+            .filter_map(|_| tree.sample(&c.over_point, normal, &mut rng))
+            .map(|(i, probability)| {
+                let light = &self.lights[i];
+                let (p, time) = (&c.over_point, c.time);
+                let from = light.sample_point(p);
+                let visible = self.light_transmittance(&from, p, time);
+                let direct = material.direct_lighting(
+                    &object_color,
+                    light,
+                    &from,
+                    p,
+                    &c.eye,
+                    &c.normal,
+                    tangent.as_ref(),
+                );
+                &direct * &visible / (probability * rays as f32)
+            })
+            .fold(ambient, |total, light| total + light)
+    }
+
+    /// Share of the rays cast from the point over the hemisphere around its normal that don't
+    /// hit anything within the occlusion distance.
+    fn occlusion(&self, c: &Computations, settings: &AmbientOcclusion) -> f32 {
+        let mut rng = rand::thread_rng();
+        let samples = settings.samples.max(1);
+
+        let open = (0..samples)
+            .filter(|_| {
+                let ray = Ray {
+                    origin: c.over_point.clone(),
+                    direction: cosine_direction(&c.normal, &mut rng),
+                    time: c.time,
+                    wavelength: None,
+                };
+                match hit(&self.intersect(&ray)) {
+                    (t, _, true) => t > settings.distance,
+                    (_, _, false) => true,
+                }
+            })
+            .count();
+
+        open as f32 / samples as f32
+    }
+
+    /// Light that enters the object and comes back out after scattering under the surface. Every
+    /// random walk starts into the surface, scatters after distances drawn from the mean free
+    /// path, keeps the subsurface colour of the light on every bounce and picks up the light
+    /// reaching the point where it leaves the object.
+    fn subsurface(&self, c: &Computations, subsurface: &Subsurface) -> Tup {
+        let mut rng = rand::thread_rng();
+        let own = self
+            .objects
+            .iter()
+            .position(|o| std::ptr::eq(o, c.object))
+            .map(|index| group(index, c.object));
+        let samples = subsurface.samples.max(1);
+
+        let mut total = color(0.0, 0.0, 0.0);
+        for _ in 0..samples {
+            let mut ray = Ray {
+                origin: c.under_point.clone(),
+                direction: cosine_direction(&-&c.normal, &mut rng),
+                time: c.time,
+                wavelength: c.wavelength,
+            };
+            let mut throughput = color(1.0, 1.0, 1.0);
+
+            for _ in 0..64 {
+                let (mut t, mut exit) = (f32::INFINITY, None);
+                for (index, object) in self.objects.iter().enumerate() {
+                    if Some(group(index, object)) != own {
+                        continue;
+                    }
+                    let (t1, t2, uv) = Object::intersect(object, &ray);
+                    for hit in t1.iter().chain(t2.iter()) {
+                        if *hit > 10e-5 && *hit < t {
+                            t = *hit;
+                            exit = Some((object, uv));
+                        }
+                    }
+                }
+                // Walks that find no way out escaped through a hole in an open mesh.
+                let (object, uv) = match exit {
+                    Some(exit) => exit,
+                    None => break,
+                };
+
+                let distance = -(1.0 - rng.gen::<f32>()).ln() * subsurface.mean_free_path;
+                if distance >= t {
+                    let point = ray.position(t);
+                    let mut normal = object.normal(&point, uv, c.time);
+                    if dot(&normal, &ray.direction) < 0.0 {
+                        normal = -&normal;
+                    }
+                    let outside = &point + &(&normal * 10e-5);
+                    total = total + &throughput * &self.irradiance(&outside, &normal, c.time);
+                    break;
+                }
+
+                ray.origin = ray.position(distance);
+                ray.direction = sphere_direction(&mut rng);
+                throughput = &throughput * &subsurface.color;
+            }
+        }
+
+        total / samples as f32
+    }
+
+    /// Light from the lights arriving at a point, weighted by the cosine with the normal. Lights
+    /// with a size are sampled at random points, as their centre may be hidden by the light's own
+    /// surface.
+    fn irradiance(&self, p: &Tup, normal: &Tup, time: f32) -> Tup {
+        self.lights
+            .iter()
+            .map(|light| {
+                let samples = light.samples();
+                let total = (0..samples)
+                    .map(|_| {
+                        let position = light.sample_point(p);
+                        let cos = dot(&(&position - p).normalize(), normal);
+                        if cos <= 0.0 {
+                            return color(0.0, 0.0, 0.0);
+                        }
+                        &self.light_transmittance(&position, p, time) * cos
+                    })
+                    .sum::<Tup>();
+                light.color() * &(total / samples as f32)
+            })
+            .sum()
+    }
+
+    /// Number of rays to average at the surface, and the number the rays it spawns may average.
+    fn split(c: &Computations, samples: u32) -> (u32, u32) {
+        if c.object.material.roughness > 0.0 && samples > 1 {
+            (samples, 1)
+        } else {
+            (1, samples)
+        }
+    }
+
+    fn reflected_color(&self, c: &Computations, depth_remaining: u32, samples: u32) -> Tup {
         if depth_remaining == 0 {
             return color(0.0, 0.0, 0.0);
         }
-        if (*c.object).material.reflectiveness < std::f32::EPSILON {
+        let material = &c.object.material;
+        if material.reflectiveness < std::f32::EPSILON {
             return color(0.0, 0.0, 0.0);
         }
 
-        let reflect_ray = Ray {
-            origin: c.over_point.clone(),
-            direction: c.reflection.clone(),
-        };
-        let color = self.color_at(&reflect_ray, depth_remaining - 1);
+        let (rays, samples) = Self::split(c, samples);
+        let mut total = color(0.0, 0.0, 0.0);
+        for _ in 0..rays {
+            // Rough surfaces reflect off a microfacet rather than the surface itself, rays that
+            // end up below the surface are blocked by neighbouring microfacets.
+            let normal = material.microfacet_normal(&c.normal);
+            let direction = (-&c.eye).reflect(&normal);
+            if dot(&direction, &c.normal) <= 0.0 {
+                continue;
+            }
+
+            let reflect_ray = Ray {
+                origin: c.over_point.clone(),
+                direction,
+                time: c.time,
+                wavelength: c.wavelength,
+            };
+            let (color, distance) = self.trace(&reflect_ray, depth_remaining - 1, samples);
+
+            // Reflected off the inside of the object, the light travels through it to the next
+            // surface.
+            total = total
+                + if c.inside {
+                    &color * &material.absorb(distance * reflect_ray.direction.magnitude())
+                } else {
+                    color
+                };
+        }
 
-        &color * (*c.object).material.reflectiveness
+        &total * (material.reflectiveness / rays as f32)
     }
 
-    fn refracted_color(&self, c: &Computations, depth_remaining: u32) -> Tup {
+    fn refracted_color(&self, c: &Computations, depth_remaining: u32, samples: u32) -> Tup {
         if depth_remaining == 0 {
             return color(0.0, 0.0, 0.0);
         }
-        if c.object.material.transparency == 0.0 {
+        let material = &c.object.material;
+        if material.transparency == 0.0 {
             return color(0.0, 0.0, 0.0);
         }
 
@@ -206,25 +664,219 @@ impl World {
             return self.background_color.clone();
         }
 
-        let cos_i = dot(&c.eye, &c.normal);
-        let sin2_t = n_ratio.powi(2) * (1.0 - cos_i.powi(2));
-        if sin2_t > 1.0 {
-            // total internal reflection
-            return color(0.0, 0.0, 0.0);
+        let (rays, samples) = Self::split(c, samples);
+        let mut total = color(0.0, 0.0, 0.0);
+        for _ in 0..rays {
+            let normal = material.microfacet_normal(&c.normal);
+            let cos_i = dot(&c.eye, &normal);
+            let sin2_t = n_ratio.powi(2) * (1.0 - cos_i.powi(2));
+            if sin2_t > 1.0 || cos_i < 0.0 {
+                // total internal reflection
+                continue;
+            }
+
+            let cos_t = (1. - sin2_t).sqrt();
+
+            let direction = &(&normal * (n_ratio * cos_i - cos_t)) - &(&c.eye * n_ratio);
+            let refracted_ray = Ray {
+                origin: c.under_point.clone(),
+                direction,
+                time: c.time,
+                wavelength: c.wavelength,
+            };
+
+            let (color, distance) = self.trace(&refracted_ray, depth_remaining - 1, samples);
+
+            // Entering the object, the light travels through it to the next surface.
+            total = total
+                + if c.inside {
+                    color
+                } else {
+                    &color * &material.absorb(distance * refracted_ray.direction.magnitude())
+                };
+        }
+
+        &total * (material.transparency / rays as f32)
+    }
+}
+
+impl World {
+    /// Photon map of the caustics in the scene. Every light shoots photons at the box around every
+    /// reflective or transparent object, and the photons that land on a diffuse surface after
+    /// being reflected or refracted at least once are kept.
+    pub fn emit_photons(&self, settings: &Caustics, max_bounces: u32) -> PhotonMap {
+        let mut rng = rand::thread_rng();
+
+        let mut targets: HashMap<(u32, usize), (Tup, Tup)> = HashMap::new();
+        for (index, object) in self.objects.iter().enumerate() {
+            let material = &object.material;
+            if material.reflectiveness == 0.0 && material.transparency == 0.0 {
+                continue;
+            }
+            if let Some((min, max)) = object.bounds() {
+                let (lo, hi) = targets
+                    .entry(group(index, object))
+                    .or_insert((min.clone(), max.clone()));
+                *lo = point(min.x.min(lo.x), min.y.min(lo.y), min.z.min(lo.z));
+                *hi = point(max.x.max(hi.x), max.y.max(hi.y), max.z.max(hi.z));
+            }
         }
 
-        let cos_t = (1. - sin2_t).sqrt();
+        let count = settings.photons / (targets.len() * self.lights.len()).max(1) as u32;
+        let mut photons = vec![];
+        for light in &self.lights {
+            for (min, max) in targets.values() {
+                let center = (min + max) / 2.0;
+                let radius = (max - min).magnitude() / 2.0;
+
+                for _ in 0..count {
+                    let origin = light.sample_point(&center);
+
+                    let axis = &center - &origin;
+                    let distance = axis.magnitude();
+                    if distance <= radius {
+                        continue;
+                    }
+                    let axis = axis / distance;
+
+                    // Uniform over the cone of directions that hit the bounding sphere. Lights
+                    // don't fall off with distance here, so photons carry the light's colour as
+                    // seen from the object they are aimed at.
+                    let cos_max = (1.0 - (radius / distance).powi(2)).sqrt();
+                    let cos = 1.0 - rng.gen::<f32>() * (1.0 - cos_max);
+                    let sin = (1.0 - cos * cos).max(0.0).sqrt();
+                    let phi = 2.0 * std::f32::consts::PI * rng.gen::<f32>();
+                    let (tangent, bitangent) = basis(&axis);
+                    let direction = &(&(&tangent * (sin * phi.cos()))
+                        + &(&bitangent * (sin * phi.sin())))
+                        + &(&axis * cos);
+
+                    let solid_angle = 2.0 * std::f32::consts::PI * (1.0 - cos_max);
+                    let power = light.color() * (distance * distance * solid_angle / count as f32);
+                    let ray = Ray {
+                        origin,
+                        direction,
+                        time: 0.0,
+                        wavelength: None,
+                    };
+
+                    if let Some(photon) = self.trace_photon(ray, power, max_bounces, &mut rng) {
+                        photons.push(photon);
+                    }
+                }
+            }
+        }
 
-        let direction = &(&c.normal * (n_ratio * cos_i - cos_t)) - &(&c.eye * n_ratio);
-        let refracted_ray = Ray {
-            origin: c.under_point.clone(),
-            direction,
-        };
+        PhotonMap::new(photons, settings)
+    }
+
+    /// Follows a photon through the scene, choosing at random between reflection, refraction and
+    /// landing on every surface, so its power only changes by what is absorbed on the way.
+    fn trace_photon(
+        &self,
+        mut ray: Ray,
+        mut power: Tup,
+        max_bounces: u32,
+        rng: &mut impl Rng,
+    ) -> Option<Photon> {
+        let mut specular = false;
+        let mut absorbing: Option<&Material> = None;
+
+        for _ in 0..=max_bounces {
+            let intersections = self.intersect(&ray);
+            let i = match hit(&intersections) {
+                (_, i, true) => i,
+                (_, _, false) => return None,
+            };
+            let c = intersections[i].computations(&ray, Some(&intersections));
+            let material = &intersections[i].object.material;
+
+            if let Some(absorbing) = absorbing {
+                power = &power * &absorbing.absorb(c.t * ray.direction.magnitude());
+            }
 
-        &self.color_at(&refracted_ray, depth_remaining - 1) * c.object.material.transparency
+            let (reflect, refract) = if material.reflectiveness > 0.0 && material.transparency > 0.0
+            {
+                let reflectance = c.schlick();
+                (
+                    material.reflectiveness * reflectance,
+                    material.transparency * (1.0 - reflectance),
+                )
+            } else {
+                (material.reflectiveness, material.transparency)
+            };
+
+            let u = rng.gen::<f32>();
+            let normal = material.microfacet_normal(&c.normal);
+            let reflected = || Ray {
+                origin: c.over_point.clone(),
+                direction: (-&c.eye).reflect(&normal),
+                time: 0.0,
+                wavelength: None,
+            };
+
+            if u < reflect {
+                ray = reflected();
+                absorbing = if c.inside { Some(material) } else { None };
+            } else if u < reflect + refract {
+                let n_ratio = c.n1 / c.n2;
+                let cos_i = dot(&c.eye, &normal);
+                let sin2_t = n_ratio.powi(2) * (1.0 - cos_i.powi(2));
+                if sin2_t > 1.0 || cos_i < 0.0 {
+                    ray = reflected();
+                    absorbing = if c.inside { Some(material) } else { None };
+                } else {
+                    let cos_t = (1.0 - sin2_t).sqrt();
+                    ray = Ray {
+                        origin: c.under_point.clone(),
+                        direction: &(&normal * (n_ratio * cos_i - cos_t)) - &(&c.eye * n_ratio),
+                        time: 0.0,
+                        wavelength: None,
+                    };
+                    absorbing = if c.inside { None } else { Some(material) };
+                }
+            } else if specular && material.diffuse > 0.0 {
+                return Some(Photon {
+                    position: c.point.clone(),
+                    direction: ray.direction.normalize(),
+                    power,
+                });
+            } else {
+                return None;
+            }
+            specular = true;
+        }
+
+        None
+    }
+}
+
+/// The triangles of a model share its id, so objects are grouped by id to find where a ray enters
+/// and leaves the model. Objects without an id are each on their own, keyed by their index in the
+/// world's objects.
+fn group(index: usize, object: &Object) -> (u32, usize) {
+    match object.id {
+        0 => (0, index),
+        id => (id, 0),
     }
 }
 
+fn sphere_direction(rng: &mut impl Rng) -> Tup {
+    let z = rng.gen_range(-1.0, 1.0);
+    let phi = 2.0 * std::f32::consts::PI * rng.gen::<f32>();
+    let r = (1.0f32 - z * z).sqrt();
+    vector(r * phi.cos(), r * phi.sin(), z)
+}
+
+/// Direction in the hemisphere around the axis, more likely the closer it is to the axis.
+fn cosine_direction(axis: &Tup, rng: &mut impl Rng) -> Tup {
+    let (tangent, bitangent) = basis(axis);
+    let r = rng.gen::<f32>().sqrt();
+    let phi = 2.0 * std::f32::consts::PI * rng.gen::<f32>();
+    let z = (1.0 - r * r).max(0.0).sqrt();
+    (&(&(&tangent * (r * phi.cos())) + &(&bitangent * (r * phi.sin()))) + &(axis * z)).normalize()
+}
+
 impl Default for World {
     fn default() -> Self {
         Self::new()
@@ -234,7 +886,8 @@ impl Default for World {
 #[cfg(test)]
 mod tests {
     use super::super::intersections::Intersection;
-    use super::super::objects::Plane;
+    use super::super::material::Emission;
+    use super::super::objects::{Cube, Plane};
     use super::super::transformations::translation;
     use super::*;
     use std::rc::Rc;
@@ -245,8 +898,10 @@ mod tests {
         let r = Ray {
             origin: point(0.0, 0.0, -5.0),
             direction: vector(0.0, 0.0, 1.0),
+            time: 0.0,
+            wavelength: None,
         };
-        let ixs = w.intersect(&r, false);
+        let ixs = w.intersect(&r);
 
         assert_eq!(ixs.len(), 4);
         assert_eq!(ixs[0].t, 4.0);
@@ -262,10 +917,12 @@ mod tests {
         let r = Ray {
             origin: point(0.0, 0.0, 0.0),
             direction: vector(0.0, 0.0, 1.0),
+            time: 0.0,
+            wavelength: None,
         };
         let i = Intersection::new(1.0, &w.objects[1], None);
         let c = i.computations(&r, None);
-        let color = w.reflected_color(&c, 10);
+        let color = w.reflected_color(&c, 10, 1);
 
         assert!((color.x).abs() <= std::f32::EPSILON);
         assert!((color.y).abs() <= std::f32::EPSILON);
@@ -286,10 +943,12 @@ mod tests {
         let r = Ray {
             origin: point(0.0, 0.0, -3.0),
             direction: vector(0.0, -p, p),
+            time: 0.0,
+            wavelength: None,
         };
         let i = Intersection::new(2.0f32.sqrt(), &s, None);
         let c = i.computations(&r, None);
-        let color = w.reflected_color(&c, 10);
+        let color = w.reflected_color(&c, 10, 1);
 
         println!("{}", color);
         assert!((color.x - 0.19032).abs() < 10e-3);
@@ -311,10 +970,12 @@ mod tests {
         let r = Ray {
             origin: point(0.0, 0.0, -3.0),
             direction: vector(0.0, -p, p),
+            time: 0.0,
+            wavelength: None,
         };
         let i = Intersection::new(2.0f32.sqrt(), &s, None);
         let c = i.computations(&r, None);
-        let color = w.shade_hit(&c, 10);
+        let color = w.shade_hit(&c, 10, 1);
 
         println!("{}", color);
         assert!((color.x - 0.87677).abs() < 10e-3);
@@ -336,10 +997,12 @@ mod tests {
         let r = Ray {
             origin: point(0.0, 0.0, -2.0),
             direction: vector(0.0, -p, p),
+            time: 0.0,
+            wavelength: None,
         };
         let i = Intersection::new(2.0f32.sqrt(), &s, None);
         let c = i.computations(&r, None);
-        let color = w.reflected_color(&c, 0);
+        let color = w.reflected_color(&c, 0, 1);
 
         assert_eq!(color.y, 0.0);
         assert_eq!(color.z, 0.0);
@@ -351,6 +1014,8 @@ mod tests {
         let r = Ray {
             origin: point(0.0, 0.0, -5.0),
             direction: vector(0.0, 0.0, 1.0),
+            time: 0.0,
+            wavelength: None,
         };
         let xs: Intersections = vec![
             Intersection::new(4.0, &w.objects[0], None),
@@ -358,7 +1023,7 @@ mod tests {
         ];
         let comps = xs[0].computations(&r, Some(&xs));
 
-        let color = w.refracted_color(&comps, 5);
+        let color = w.refracted_color(&comps, 5, 1);
 
         assert_eq!(color.x, 0.0);
         assert_eq!(color.y, 0.0);
@@ -371,6 +1036,8 @@ mod tests {
         let r = Ray {
             origin: point(0.0, 0.0, -5.0),
             direction: vector(0.0, 0.0, 1.0),
+            time: 0.0,
+            wavelength: None,
         };
         let xs: Intersections = vec![
             Intersection::new(4.0, &w.objects[0], None),
@@ -378,7 +1045,7 @@ mod tests {
         ];
         let comps = xs[0].computations(&r, Some(&xs));
 
-        let color = w.refracted_color(&comps, 0);
+        let color = w.refracted_color(&comps, 0, 1);
 
         assert_eq!(color.x, 0.0);
         assert_eq!(color.y, 0.0);
@@ -399,6 +1066,8 @@ mod tests {
         let r = Ray {
             origin: point(0.0, 0.0, p),
             direction: vector(0.0, 1.0, 0.0),
+            time: 0.0,
+            wavelength: None,
         };
         let xs: Intersections = vec![
             Intersection::new(-p, &w.objects[0], None),
@@ -406,7 +1075,7 @@ mod tests {
         ];
         let comps = xs[1].computations(&r, Some(&xs));
 
-        let color = w.refracted_color(&comps, 5);
+        let color = w.refracted_color(&comps, 5, 1);
 
         assert_eq!(color.x, 0.0);
         assert_eq!(color.y, 0.0);
@@ -438,13 +1107,410 @@ mod tests {
         let r = Ray {
             origin: point(0., 0., -3.),
             direction: vector(0., -p, p),
+            time: 0.0,
+            wavelength: None,
         };
         let comps = xs[0].computations(&r, Some(&xs));
-        let color = w.shade_hit(&comps, 5);
+        let color = w.shade_hit(&comps, 5, 1);
 
         println!("{}", color);
         assert!((color.x - 1.1149998).abs() < 10e-3);
         assert!((color.y - 0.696432).abs() < 10e-3);
         assert!((color.z - 0.6924281).abs() < 10e-3);
     }
+
+    fn medium(density: f32) -> Medium {
+        Medium {
+            density,
+            color: color(1.0, 1.0, 1.0),
+            anisotropy: 0.0,
+            steps: 8,
+        }
+    }
+
+    #[test]
+    fn fog_hides_the_background() {
+        let mut w = World::new();
+        w.background_color = color(1.0, 1.0, 1.0);
+        w.lights = vec![];
+        w.fog = Some(Fog {
+            medium: medium(0.5),
+            height: 0.0,
+            falloff: 0.0,
+            distance: 2.0,
+        });
+        let r = Ray {
+            origin: point(0.0, 0.0, 0.0),
+            direction: vector(0.0, 0.0, 1.0),
+            time: 0.0,
+            wavelength: None,
+        };
+
+        let c = w.color_at(&r, 5);
+        assert!((c.x - (-1f32).exp()).abs() < 10e-5);
+    }
+
+    #[test]
+    fn volumes_scatter_light_and_cast_shadows() {
+        let mut w = World::new();
+        let mut material = Material::new();
+        material.medium = Some(medium(1.0));
+        w.objects.push(Object::new(
+            Geometry::Sphere(Sphere::default()),
+            material,
+            None,
+        ));
+        let through = Ray {
+            origin: point(0.0, 0.0, -5.0),
+            direction: vector(0.0, 0.0, 1.0),
+            time: 0.0,
+            wavelength: None,
+        };
+        let past = Ray {
+            origin: point(0.0, 2.0, -5.0),
+            direction: vector(0.0, 0.0, 1.0),
+            time: 0.0,
+            wavelength: None,
+        };
+
+        assert!(w.color_at(&through, 5).x > 0.0);
+        assert_eq!(w.color_at(&past, 5), color(0.0, 0.0, 0.0));
+
+        let behind = point(10.0, -10.0, 10.0);
+        let shadowed = w.light_transmittance(w.lights[0].position(), &behind, 0.0);
+        assert!(shadowed.x > 0.0 && shadowed.x < 0.2);
+    }
+
+    #[test]
+    fn coloured_glass_casts_coloured_shadows() {
+        let mut w = World::new();
+        let glass = |transform| {
+            let mut material = Material::new();
+            material.transparency = 1.0;
+            material.absorption = color(1.0, 0.5, 0.5);
+            Object::new(Geometry::Sphere(Sphere::new(transform)), material, None)
+        };
+        w.objects.push(glass(translation(-3.0, 3.0, -3.0)));
+        let p = point(0.0, 0.0, 0.0);
+
+        let one = w.light_transmittance(w.lights[0].position(), &p, 0.0);
+        assert!((one.x - 1.0).abs() < 10e-5);
+        assert!((one.y - 0.25).abs() < 10e-5);
+
+        // A second pane of glass further along, and thicker glass, let less light through.
+        w.objects.push(glass(translation(-6.0, 6.0, -6.0)));
+        let two = w.light_transmittance(w.lights[0].position(), &p, 0.0);
+        assert!((two.y - 0.0625).abs() < 10e-5);
+
+        w.objects[0] = glass(scaling(2.0, 2.0, 2.0) * translation(-1.5, 1.5, -1.5));
+        assert!(w.light_transmittance(w.lights[0].position(), &p, 0.0).y < two.y);
+    }
+
+    #[test]
+    fn rooms_around_the_light_cast_no_shadow() {
+        let mut w = World::new();
+        w.lights = vec![Light::new_point(point(0.0, 5.0, 0.0), color(1.0, 1.0, 1.0))];
+        w.objects = vec![
+            Object::new(
+                Geometry::Cube(Cube::new(scaling(10.0, 10.0, 10.0))),
+                Material::new(),
+                None,
+            ),
+            Object::new(Geometry::Sphere(Sphere::default()), Material::new(), None),
+        ];
+
+        let light = w.lights[0].position();
+        assert_eq!(
+            w.light_transmittance(light, &point(0.0, 1.001, 0.0), 0.0),
+            color(1.0, 1.0, 1.0)
+        );
+        assert_eq!(
+            w.light_transmittance(light, &point(0.0, -1.001, 0.0), 0.0),
+            color(0.0, 0.0, 0.0)
+        );
+    }
+
+    #[test]
+    fn light_scatters_through_translucent_objects() {
+        let mut w = World::new();
+        w.lights = vec![Light::new_point(
+            point(0.0, 0.0, 10.0),
+            color(1.0, 1.0, 1.0),
+        )];
+        let sphere = |subsurface| {
+            let mut material = Material::new();
+            material.ambient = 0.0;
+            material.specular = 0.0;
+            material.subsurface = subsurface;
+            Object::new(Geometry::Sphere(Sphere::default()), material, None)
+        };
+        let r = Ray {
+            origin: point(0.0, 0.0, -5.0),
+            direction: vector(0.0, 0.0, 1.0),
+            time: 0.0,
+            wavelength: None,
+        };
+
+        // Lit from behind, an opaque sphere is black where the ray hits it.
+        w.objects = vec![sphere(None)];
+        assert_eq!(w.color_at(&r, 5), color(0.0, 0.0, 0.0));
+
+        w.objects = vec![sphere(Some(Subsurface {
+            color: color(0.9, 0.9, 0.9),
+            mean_free_path: 0.5,
+            samples: 64,
+        }))];
+        assert!(w.color_at(&r, 5).x > 0.0);
+    }
+
+    #[test]
+    fn glowing_objects_shine_through_translucent_objects() {
+        let mut glow = Material::new();
+        glow.emission = Some(Emission {
+            color: color(1.0, 1.0, 1.0),
+            strength: 1.0,
+            pattern: None,
+            light: true,
+            samples: 16,
+        });
+        let lamp = Object::new(
+            Geometry::Sphere(Sphere::new(translation(0.0, 0.0, 4.0))),
+            glow,
+            None,
+        );
+        let mut material = Material::new();
+        material.ambient = 0.0;
+        material.specular = 0.0;
+        material.subsurface = Some(Subsurface {
+            color: color(0.9, 0.9, 0.9),
+            mean_free_path: 0.5,
+            samples: 64,
+        });
+        let sphere = Object::new(Geometry::Sphere(Sphere::default()), material, None);
+
+        let mut w = World::new();
+        w.lights = vec![Light::Object(ObjectLight {
+            position: lamp.center(),
+            color: color(1.0, 1.0, 1.0),
+            parts: vec![lamp.clone()],
+            samples: 16,
+        })];
+        w.objects = vec![sphere, lamp];
+
+        // The lamp is behind the sphere, so only light scattered through the sphere is seen.
+        let r = Ray {
+            origin: point(0.0, 0.0, -5.0),
+            direction: vector(0.0, 0.0, 1.0),
+            time: 0.0,
+            wavelength: None,
+        };
+        assert!(w.color_at(&r, 5).x > 0.0);
+    }
+
+    #[test]
+    fn glowing_objects_light_the_scene() {
+        let mut material = Material::new();
+        material.emission = Some(Emission {
+            color: color(1.0, 0.0, 0.0),
+            strength: 2.0,
+            pattern: None,
+            light: true,
+            samples: 16,
+        });
+        let lamp = Object::new(Geometry::Sphere(Sphere::default()), material, None);
+        let floor = Object::new(
+            Geometry::Plane(Plane::new(translation(0.0, -2.0, 0.0))),
+            Material::new(),
+            None,
+        );
+
+        let mut w = World::new();
+        w.lights = vec![Light::Object(ObjectLight {
+            position: lamp.center(),
+            color: color(2.0, 0.0, 0.0),
+            parts: vec![lamp.clone()],
+            samples: 16,
+        })];
+        w.objects = vec![lamp, floor];
+
+        let ray = |origin| Ray {
+            origin,
+            direction: vector(0.0, -1.0, 0.0),
+            time: 0.0,
+            wavelength: None,
+        };
+
+        assert!(w.color_at(&ray(point(0.0, 5.0, 0.0)), 5).x >= 2.0);
+
+        let lit = w.color_at(&ray(point(3.0, 5.0, 0.0)), 5);
+        assert!(lit.x > 0.0);
+        assert_eq!(lit.y, 0.0);
+    }
+
+    #[test]
+    fn glowing_objects_shine_from_the_side_facing_the_point() {
+        let lamp = Object::new(
+            Geometry::Sphere(Sphere::new(translation(0.0, 3.0, 0.0))),
+            Material::new(),
+            None,
+        );
+        let light = Light::Object(ObjectLight {
+            position: lamp.center(),
+            color: color(1.0, 1.0, 1.0),
+            parts: vec![lamp.clone()],
+            samples: 16,
+        });
+        let mut w = World::new();
+        w.objects = vec![lamp];
+
+        let p = point(0.0, 0.0, 0.0);
+        for _ in 0..64 {
+            let from = light.sample_point(&p);
+            assert!(from.y < 3.0);
+            assert_eq!(w.light_transmittance(&from, &p, 0.0), color(1.0, 1.0, 1.0));
+        }
+    }
+
+    #[test]
+    fn glass_focuses_photons_into_caustics() {
+        let mut glass = Material::new();
+        glass.diffuse = 0.0;
+        glass.transparency = 1.0;
+        glass.refractive_index = 1.5;
+
+        let mut w = World::new();
+        w.lights = vec![Light::new_point(
+            point(0.0, 10.0, 0.0),
+            color(1.0, 1.0, 1.0),
+        )];
+        w.objects = vec![
+            Object::new(Geometry::Sphere(Sphere::default()), glass, None),
+            Object::new(
+                Geometry::Plane(Plane::new(translation(0.0, -2.0, 0.0))),
+                Material::new(),
+                None,
+            ),
+        ];
+
+        let settings = Caustics {
+            photons: 20000,
+            neighbours: 50,
+            radius: 0.5,
+        };
+        let caustics = w.emit_photons(&settings, 5);
+        assert!(!caustics.is_empty());
+
+        // The ball focuses light from above just below itself.
+        let up = vector(0.0, 1.0, 0.0);
+        assert!(caustics.irradiance(&point(0.0, -2.0, 0.0), &up).x > 1.0);
+        assert!(caustics.irradiance(&point(5.0, -2.0, 0.0), &up).x < 0.1);
+
+        // Light through the ball then only reaches the floor as caustics, not as direct light.
+        let below = point(0.0, -1.99, 0.0);
+        assert!(w.light_transmittance(w.lights[0].position(), &below, 0.0).x > 0.0);
+        w.caustics = Some(caustics);
+        assert_eq!(
+            w.light_transmittance(w.lights[0].position(), &below, 0.0),
+            color(0.0, 0.0, 0.0)
+        );
+    }
+
+    #[test]
+    fn clay_render_darkens_where_objects_touch() {
+        let mut w = World::new();
+        w.objects = vec![
+            Object::new(Geometry::Sphere(Sphere::default()), Material::new(), None),
+            Object::new(
+                Geometry::Plane(Plane::new(translation(0.0, -1.0, 0.0))),
+                Material::new(),
+                None,
+            ),
+        ];
+        w.ambient_occlusion = Some(AmbientOcclusion {
+            samples: 64,
+            distance: 2.0,
+            clay: true,
+        });
+        let down = |x| Ray {
+            origin: point(x, 5.0, 0.0),
+            direction: vector(0.0, -1.0, 0.0),
+            time: 0.0,
+            wavelength: None,
+        };
+
+        let open = w.color_at(&down(10.0), 5);
+        assert_eq!(open, color(1.0, 1.0, 1.0));
+
+        let crease = w.color_at(&down(1.1), 5);
+        assert!(crease.x < 0.9);
+        assert_eq!(crease.x, crease.y);
+    }
+
+    #[test]
+    fn sampled_lights_average_out_to_all_lights() {
+        let mut w = World::new();
+        w.objects = vec![Object::new(
+            Geometry::Sphere(Sphere::default()),
+            Material::new(),
+            None,
+        )];
+        w.lights = (0..6)
+            .map(|i| {
+                let position = point(i as f32 * 2.0 - 5.0, 4.0, -6.0);
+                Light::new_point(position, color(1.0, 1.0, 1.0))
+            })
+            .collect();
+        let r = Ray {
+            origin: point(0.3, 0.2, -5.0),
+            direction: vector(0.0, 0.0, 1.0),
+            time: 0.0,
+            wavelength: None,
+        };
+        let expected = w.color_at(&r, 5);
+
+        w.light_tree = Some(LightTree::new(&w.lights, 2));
+        let n = 2000;
+        let average = (0..n).map(|_| w.color_at(&r, 5)).sum::<Tup>() / n as f32;
+
+        assert!((average.x - expected.x).abs() / expected.x < 0.05);
+        assert!((average.z - expected.z).abs() / expected.z < 0.05);
+    }
+
+    #[test]
+    fn sampled_lights_are_shadowed_one_by_one() {
+        let mut w = World::new();
+        w.objects = vec![
+            Object::new(Geometry::Plane(Plane::default()), Material::new(), None),
+            Object::new(
+                Geometry::Sphere(Sphere::new(
+                    translation(-2.0, 2.0, 0.0) * scaling(0.5, 0.5, 0.5),
+                )),
+                Material::new(),
+                None,
+            ),
+        ];
+        // The ball only hides the red light.
+        w.lights = vec![
+            Light::new_point(point(-4.0, 4.0, 0.0), color(1.0, 0.0, 0.0)),
+            Light::new_point(point(4.0, 4.0, 0.0), color(0.0, 1.0, 0.0)),
+            Light::new_point(point(0.0, 4.0, 4.0), color(0.0, 0.0, 1.0)),
+            Light::new_point(point(0.0, 4.0, -4.0), color(1.0, 1.0, 1.0)),
+        ];
+        let r = Ray {
+            origin: point(0.0, 5.0, -5.0),
+            direction: vector(0.0, -1.0, 1.0).normalize(),
+            time: 0.0,
+            wavelength: None,
+        };
+        let expected = w.color_at(&r, 5);
+        assert!(expected.x < expected.y);
+
+        w.light_tree = Some(LightTree::new(&w.lights, 2));
+        let n = 2000;
+        let average = (0..n).map(|_| w.color_at(&r, 5)).sum::<Tup>() / n as f32;
+
+        assert!((average.x - expected.x).abs() / expected.x < 0.05);
+        assert!((average.y - expected.y).abs() / expected.y < 0.05);
+        assert!((average.z - expected.z).abs() / expected.z < 0.05);
+    }
 }